@@ -3,6 +3,111 @@
 #include <stdint.h>
 #include <stdlib.h>
 
+/**
+ * Current layout of `BlockStoreExtensions`. Hosts set `version` to the
+ * value they were built against and `size` to the size of the struct they
+ * fill in; fields past `size` are treated as absent.
+ */
+#define BLOCK_STORE_EXTENSIONS_VERSION 1
+
+/**
+ * Encoding of structured outputs. `Legacy` is the `???`/`!!!` delimited
+ * listing and is only understood by ls.
+ */
+typedef enum OutputFormat {
+  Legacy = 0,
+  Json = 1,
+  Cbor = 2,
+} OutputFormat;
+
+/**
+ * Stable error codes carried by every `RustResult`. Values are part of the
+ * C ABI: never renumber, only append.
+ */
+typedef enum WnfsErrorCode {
+  Ok = 0,
+  Unknown = 1,
+  /**
+   * A null handle, malformed option or otherwise unusable argument.
+   */
+  InvalidArgument = 2,
+  /**
+   * The root CID string could not be parsed.
+   */
+  InvalidCid = 3,
+  /**
+   * The path does not exist in the forest.
+   */
+  NotFound = 4,
+  /**
+   * The host block store failed to return or store a block.
+   */
+  BlockStore = 5,
+  /**
+   * The forest could not be opened or decrypted with the given key.
+   */
+  WrongKey = 6,
+  /**
+   * Reading or writing a path on the local filesystem failed.
+   */
+  LocalIo = 7,
+  /**
+   * A mutation was attempted through a read-only (shared) session.
+   */
+  ReadOnly = 8,
+} WnfsErrorCode;
+
+typedef enum CarVersion {
+  V1 = 1,
+  /**
+   * A CARv1 payload behind a CARv2 header, without an index.
+   */
+  V2 = 2,
+} CarVersion;
+
+/**
+ * What to do with a file that already exists where an export or import
+ * would write it.
+ */
+typedef enum ExistingFiles {
+  Skip = 0,
+  Overwrite = 1,
+} ExistingFiles;
+
+/**
+ * How the pattern given to `find_native` is read.
+ */
+typedef enum PatternSyntax {
+  /**
+   * `*`, `?` and `[...]` classes, negated with `[!...]`, over the whole
+   * name.
+   */
+  Glob = 0,
+  /**
+   * Regular expression matching anywhere in the name unless anchored.
+   */
+  Regex = 1,
+} PatternSyntax;
+
+typedef enum KindFilter {
+  AnyKind = 0,
+  FilesOnly = 1,
+  DirsOnly = 2,
+} KindFilter;
+
+typedef enum TransactionOpKind {
+  Mkdir = 0,
+  Write = 1,
+  WriteFromPath = 2,
+  Cp = 3,
+  Mv = 4,
+  Rm = 5,
+} TransactionOpKind;
+
+typedef struct WnfsFileWriter WnfsFileWriter;
+
+typedef struct WnfsSession WnfsSession;
+
 typedef struct RustString {
   const char *str;
 } RustString;
@@ -11,6 +116,11 @@ typedef struct RustResult_RustString {
   bool ok;
   struct RustString err;
   struct RustString result;
+  enum WnfsErrorCode code;
+  /**
+   * Optional JSON object with structured error context, null on success.
+   */
+  struct RustString detail;
 } RustResult_RustString;
 
 typedef struct RustBytes {
@@ -23,6 +133,11 @@ typedef struct RustResult_RustBytes {
   bool ok;
   struct RustString err;
   struct RustBytes result;
+  enum WnfsErrorCode code;
+  /**
+   * Optional JSON object with structured error context, null on success.
+   */
+  struct RustString detail;
 } RustResult_RustBytes;
 
 typedef struct RustVoid {
@@ -33,18 +148,173 @@ typedef struct RustResult_RustVoid {
   bool ok;
   struct RustString err;
   struct RustVoid result;
+  enum WnfsErrorCode code;
+  /**
+   * Optional JSON object with structured error context, null on success.
+   */
+  struct RustString detail;
 } RustResult_RustVoid;
 
+/**
+ * Result a host block store callback returns. It keeps the three-field
+ * layout `RustResult` had before `code` and `detail` were added, so host
+ * callbacks built against that layout still match; the host reports
+ * failures through `err` alone.
+ */
+typedef struct HostResult_RustVoid {
+  bool ok;
+  struct RustString err;
+  struct RustVoid result;
+} HostResult_RustVoid;
+
+/**
+ * Result a host block store callback returns. It keeps the three-field
+ * layout `RustResult` had before `code` and `detail` were added, so host
+ * callbacks built against that layout still match; the host reports
+ * failures through `err` alone.
+ */
+typedef struct HostResult_RustBytes {
+  bool ok;
+  struct RustString err;
+  struct RustBytes result;
+} HostResult_RustBytes;
+
+typedef struct RustBytesArray {
+  const struct RustBytes *data;
+  size_t len;
+} RustBytesArray;
+
+/**
+ * Result a host block store callback returns. It keeps the three-field
+ * layout `RustResult` had before `code` and `detail` were added, so host
+ * callbacks built against that layout still match; the host reports
+ * failures through `err` alone.
+ */
+typedef struct HostResult_RustBytesArray {
+  bool ok;
+  struct RustString err;
+  struct RustBytesArray result;
+} HostResult_RustBytesArray;
+
+typedef struct RustSession {
+  struct WnfsSession *session;
+} RustSession;
+
+typedef struct RustResult_RustSession {
+  bool ok;
+  struct RustString err;
+  struct RustSession result;
+  enum WnfsErrorCode code;
+  /**
+   * Optional JSON object with structured error context, null on success.
+   */
+  struct RustString detail;
+} RustResult_RustSession;
+
+typedef struct RustFileWriter {
+  struct WnfsFileWriter *writer;
+} RustFileWriter;
+
+typedef struct RustResult_RustFileWriter {
+  bool ok;
+  struct RustString err;
+  struct RustFileWriter result;
+  enum WnfsErrorCode code;
+  /**
+   * Optional JSON object with structured error context, null on success.
+   */
+  struct RustString detail;
+} RustResult_RustFileWriter;
+
+/**
+ * One step of a batched transaction.
+ */
+typedef struct TransactionOp {
+  enum TransactionOpKind kind;
+  /**
+   * Path created, written or removed; the source path for cp and mv.
+   */
+  struct RustString path_segments;
+  /**
+   * Destination path for cp and mv, local filename for `WriteFromPath`.
+   * Ignored by the other kinds.
+   */
+  struct RustString target;
+  /**
+   * File content for `Write`. Ignored by the other kinds.
+   */
+  struct RustBytes content;
+} TransactionOp;
+
+typedef struct FindOptions {
+  enum PatternSyntax syntax;
+  enum KindFilter kind;
+  bool case_insensitive;
+  /**
+   * Zero means unlimited.
+   */
+  size_t max_results;
+  /**
+   * Fill in `size` and `cid` of matching files, which requires reading
+   * them.
+   */
+  bool with_content;
+} FindOptions;
+
+typedef void (*StringCallback)(void *userdata, struct RustResult_RustString result);
+
+typedef void (*BytesCallback)(void *userdata, struct RustResult_RustBytes result);
+
+typedef void (*VoidCallback)(void *userdata, struct RustResult_RustVoid result);
+
 typedef struct BlockStoreInterface {
   void *userdata;
-  struct RustResult_RustVoid (*put_fn)(void *userdata, struct RustBytes cid, struct RustBytes bytes);
-  struct RustResult_RustBytes (*get_fn)(void *userdata, struct RustBytes cid);
-  void (*dealloc_after_get)(struct RustResult_RustBytes data);
-  void (*dealloc_after_put)(struct RustResult_RustVoid data);
+  struct HostResult_RustVoid (*put_fn)(void *userdata, struct RustBytes cid, struct RustBytes bytes);
+  struct HostResult_RustBytes (*get_fn)(void *userdata, struct RustBytes cid);
+  void (*dealloc_after_get)(struct HostResult_RustBytes data);
+  void (*dealloc_after_put)(struct HostResult_RustVoid data);
 } BlockStoreInterface;
 
+/**
+ * Optional callbacks a host block store can provide on top of
+ * `BlockStoreInterface`. Every call that walks or writes more than a single
+ * path takes them, and so does `wnfs_session_open`, whose session then uses
+ * them for every call made through it; the single-path `*_native` calls
+ * keep their original signatures. Every callback may be null, in which case
+ * the single-block `put_fn`/`get_fn` path is used instead.
+ */
+typedef struct BlockStoreExtensions {
+  /**
+   * `sizeof(BlockStoreExtensions)` as the host sees it.
+   */
+  size_t size;
+  uint32_t version;
+  /**
+   * Asked before every put, to leave out blocks the host has.
+   */
+  bool (*has_fn)(void *userdata, struct RustBytes cid);
+  struct HostResult_RustVoid (*delete_fn)(void *userdata, struct RustBytes cid);
+  struct HostResult_RustVoid (*put_many_fn)(void *userdata,
+                                            const struct RustBytes *cids,
+                                            const struct RustBytes *blocks,
+                                            size_t count);
+  /**
+   * Must return exactly `count` blocks, in the order of `cids`.
+   */
+  struct HostResult_RustBytesArray (*get_many_fn)(void *userdata,
+                                                  const struct RustBytes *cids,
+                                                  size_t count);
+  void (*dealloc_after_get_many)(struct HostResult_RustBytesArray data);
+} BlockStoreExtensions;
+
+/**
+ * Frees the result, error message and detail of `arg`.
+ */
 void rust_result_string_free(struct RustResult_RustString arg);
 
+/**
+ * Frees the result, error message and detail of `arg`.
+ */
 void rust_result_bytes_free(struct RustResult_RustBytes arg);
 
 struct RustResult_RustVoid load_with_wnfs_key_native(struct BlockStoreInterface block_store_interface,
@@ -99,3 +369,679 @@ struct RustResult_RustString rm_native(struct BlockStoreInterface block_store_in
 struct RustResult_RustBytes ls_native(struct BlockStoreInterface block_store_interface,
                                       struct RustString cid,
                                       struct RustString path_segments);
+
+/**
+ * Same as `ls_native`, but encodes the listing as `format`. Every entry
+ * carries the CID of its node and files their size, which is taken from
+ * the file header rather than the body.
+ */
+struct RustResult_RustBytes ls_with_format_native(struct BlockStoreInterface block_store_interface,
+                                                  struct RustString cid,
+                                                  struct RustString path_segments,
+                                                  enum OutputFormat format);
+
+/**
+ * Reads `length` bytes starting at `offset` from a file. Reads past the
+ * end of the file are truncated.
+ */
+struct RustResult_RustBytes read_file_range_native(struct BlockStoreInterface block_store_interface,
+                                                   struct RustString cid,
+                                                   struct RustString path_segments,
+                                                   uint64_t offset,
+                                                   uint64_t length);
+
+/**
+ * Applies `ops_len` operations from `ops` in order against a single load
+ * of the forest at `cid` and returns the final root CID. If any operation
+ * fails nothing is returned but the error, whose detail carries the
+ * failing `op_index`, and `cid` remains the current root.
+ */
+struct RustResult_RustString transaction_native(struct BlockStoreInterface block_store_interface,
+                                                const struct BlockStoreExtensions *extensions,
+                                                struct RustString cid,
+                                                const struct TransactionOp *ops,
+                                                size_t ops_len);
+
+/**
+ * Enables the block cache, or resizes it, dropping its current content.
+ * Passing zero for either limit disables it. The limits are shared, but
+ * each entry belongs to the host store it was read from, as identified
+ * by the interface `userdata`, and is dropped when that store deletes
+ * the block or reports it dead in a gc. Written blocks are only cached
+ * when `cache_puts` is set.
+ */
+struct RustResult_RustVoid block_cache_configure_native(size_t max_entries,
+                                                        size_t max_bytes,
+                                                        bool cache_puts);
+
+/**
+ * Empties the block cache without changing its limits.
+ */
+struct RustResult_RustVoid block_cache_purge_native(void);
+
+/**
+ * Reports the blocks reachable from `drop_cids` that are not reachable
+ * from any of `keep_cids`, i.e. the blocks that can be removed once the
+ * dropped roots are forgotten. Each one is also passed to `on_dead`, if
+ * set, as soon as it is found; the string is only valid during the call.
+ * With `delete` set they are removed through the `delete_fn` of
+ * `extensions`, which may be null when no callback is needed.
+ */
+struct RustResult_RustBytes gc_native(struct BlockStoreInterface block_store_interface,
+                                      const struct BlockStoreExtensions *extensions,
+                                      const struct RustString *keep_cids,
+                                      size_t keep_len,
+                                      const struct RustString *drop_cids,
+                                      size_t drop_len,
+                                      bool delete,
+                                      enum OutputFormat format,
+                                      void (*on_dead)(void *userdata, struct RustString cid),
+                                      void *userdata);
+
+/**
+ * Describes a single path, encoded as `format`. A missing path is not an
+ * error: the result has `exists` set to false. `size` is only filled in
+ * when `with_content` is set, without reading the file body.
+ */
+struct RustResult_RustBytes stat_native(struct BlockStoreInterface block_store_interface,
+                                        struct RustString cid,
+                                        struct RustString path_segments,
+                                        enum OutputFormat format,
+                                        bool with_content);
+
+/**
+ * Lists everything below `path_segments` in one call, depth first. Zero
+ * for `max_depth` or `max_entries` means unlimited; `truncated` is set in
+ * the result when `max_entries` was reached. With `on_entry` set, every
+ * entry is encoded as `format` and passed to it as soon as it is found,
+ * and the returned `entries` is left empty; the bytes are only valid
+ * during the call.
+ */
+struct RustResult_RustBytes tree_native(struct BlockStoreInterface block_store_interface,
+                                        const struct BlockStoreExtensions *extensions,
+                                        struct RustString cid,
+                                        struct RustString path_segments,
+                                        uint32_t max_depth,
+                                        size_t max_entries,
+                                        enum OutputFormat format,
+                                        bool with_content,
+                                        void (*on_entry)(void *userdata, struct RustBytes entry),
+                                        void *userdata);
+
+/**
+ * Moves the private tree at `cid` from `old_wnfs_key` to `new_wnfs_key`
+ * and returns the new root CID. Only the latest revision of each node is
+ * copied, so revision history does not carry over. Nothing is deleted:
+ * once the new root is stored, the old one can be passed to `gc_native`
+ * as a dropped root.
+ */
+struct RustResult_RustString rotate_key_native(struct BlockStoreInterface block_store_interface,
+                                               const struct BlockStoreExtensions *extensions,
+                                               struct RustBytes old_wnfs_key,
+                                               struct RustBytes new_wnfs_key,
+                                               struct RustString cid);
+
+/**
+ * Returns the access key of the file or directory at `path_segments` as
+ * `{version, cid, kind, access_key}` encoded as `format`, with the key
+ * base64 encoded. A directory's key opens it through
+ * `load_shared_native` and a file's is read with
+ * `read_shared_file_native`; neither opens anything above the node. It
+ * is the node's own key: opened against a later root of the drive, the
+ * share shows the node as it is in that root.
+ */
+struct RustResult_RustBytes share_native(struct BlockStoreInterface block_store_interface,
+                                         const struct BlockStoreExtensions *extensions,
+                                         struct RustString cid,
+                                         struct RustString path_segments,
+                                         enum OutputFormat format);
+
+/**
+ * Lists the revisions of `path_segments` in the forest at `cid`, ordered
+ * oldest first, encoded as `format`. They are read from the node's own
+ * previous-revision links, back to the revision the path had in
+ * `since_cid`, an older root of the same drive in which it exists.
+ * `with_content` also fills in each revision's size.
+ */
+struct RustResult_RustBytes revisions_native(struct BlockStoreInterface block_store_interface,
+                                             const struct BlockStoreExtensions *extensions,
+                                             struct RustString cid,
+                                             struct RustString since_cid,
+                                             struct RustString path_segments,
+                                             enum OutputFormat format,
+                                             bool with_content);
+
+/**
+ * Reads the file at `path_segments` as of `revision`, numbered as by
+ * `revisions_native` with the same `since_cid`.
+ */
+struct RustResult_RustBytes read_revision_native(struct BlockStoreInterface block_store_interface,
+                                                 const struct BlockStoreExtensions *extensions,
+                                                 struct RustString cid,
+                                                 struct RustString since_cid,
+                                                 struct RustString path_segments,
+                                                 uint32_t revision);
+
+/**
+ * Makes `revision` of `path_segments`, numbered as by `revisions_native`
+ * with the same `since_cid`, the current one in the forest at `cid` and
+ * returns the new root CID.
+ */
+struct RustResult_RustString restore_revision_native(struct BlockStoreInterface block_store_interface,
+                                                     const struct BlockStoreExtensions *extensions,
+                                                     struct RustString cid,
+                                                     struct RustString since_cid,
+                                                     struct RustString path_segments,
+                                                     uint32_t revision);
+
+/**
+ * Lists what was added, removed or modified below `path_segments` going
+ * from `cid_a` to `cid_b`, encoded as `format`. Paths are relative to
+ * `path_segments`. Files are compared by node CID, so every write shows
+ * up, and directories whose node did not change are skipped. Sizes are
+ * only reported with `with_content`.
+ */
+struct RustResult_RustBytes diff_native(struct BlockStoreInterface block_store_interface,
+                                        const struct BlockStoreExtensions *extensions,
+                                        struct RustString cid_a,
+                                        struct RustString cid_b,
+                                        struct RustString path_segments,
+                                        enum OutputFormat format,
+                                        bool with_content);
+
+/**
+ * Three-way merge: replays what changed from `base_cid` to `theirs_cid`
+ * onto `ours_cid`. Files changed differently on both sides keep our
+ * version in place and get theirs next to it, named with
+ * `conflict_label` ("conflict" when empty). Returns
+ * `{version, cid, actions}` encoded as `format`, where `cid` is the
+ * merged root and `actions` reports every path that was touched. User
+ * metadata travels with the entries copied from theirs but is never
+ * merged on its own; metadata edits to directories and symlinks are not
+ * seen as changes.
+ */
+struct RustResult_RustBytes merge_native(struct BlockStoreInterface block_store_interface,
+                                         const struct BlockStoreExtensions *extensions,
+                                         struct RustString base_cid,
+                                         struct RustString ours_cid,
+                                         struct RustString theirs_cid,
+                                         struct RustString conflict_label,
+                                         enum OutputFormat format);
+
+/**
+ * Creates a symlink at `link_path_segments` pointing to
+ * `target_path_segments` and returns the new root CID. The target does
+ * not have to exist. A link is an empty private file whose metadata
+ * holds the target, the way wnfs keeps any node attribute: it shows up
+ * with kind `symlink` in structured listings and as a file in
+ * `ls_native` and to other wnfs clients. Links follow `mv_native` and
+ * `cp_native`, are removed by `unlink_native` or `rm_native`, and
+ * writing a file over one fails.
+ */
+struct RustResult_RustString symlink_native(struct BlockStoreInterface block_store_interface,
+                                            struct RustString cid,
+                                            struct RustString link_path_segments,
+                                            struct RustString target_path_segments);
+
+struct RustResult_RustString unlink_native(struct BlockStoreInterface block_store_interface,
+                                           struct RustString cid,
+                                           struct RustString link_path_segments);
+
+/**
+ * Returns the target of the symlink at `link_path_segments`. With
+ * `follow` set, links are resolved until the path contains none and the
+ * final path is returned; loops fail.
+ */
+struct RustResult_RustString readlink_native(struct BlockStoreInterface block_store_interface,
+                                             struct RustString cid,
+                                             struct RustString link_path_segments,
+                                             bool follow);
+
+/**
+ * Same as `read_file_native`, but symlinks anywhere in the path are
+ * followed.
+ */
+struct RustResult_RustBytes read_file_follow_native(struct BlockStoreInterface block_store_interface,
+                                                    struct RustString cid,
+                                                    struct RustString path_segments);
+
+/**
+ * Same as `ls_with_format_native`, but every entry also carries its user
+ * metadata under `meta`.
+ */
+struct RustResult_RustBytes ls_with_meta_native(struct BlockStoreInterface block_store_interface,
+                                                struct RustString cid,
+                                                struct RustString path_segments,
+                                                enum OutputFormat format);
+
+/**
+ * Returns every user metadata key of the node at `path_segments`,
+ * encoded as `format`. The drive root has metadata too.
+ */
+struct RustResult_RustBytes get_metadata_native(struct BlockStoreInterface block_store_interface,
+                                                struct RustString cid,
+                                                struct RustString path_segments,
+                                                enum OutputFormat format);
+
+/**
+ * Sets a string metadata key on the node at `path_segments` and returns
+ * the new root CID. Metadata is kept in the node's own wnfs metadata,
+ * encrypted with the node: anyone who can read the node, including
+ * through a share, can read it. It follows the node through `mv_native`
+ * and `cp_native`, goes away with it on `rm_native`, and every change
+ * makes a new revision of the node.
+ */
+struct RustResult_RustString set_metadata_string_native(struct BlockStoreInterface block_store_interface,
+                                                        struct RustString cid,
+                                                        struct RustString path_segments,
+                                                        struct RustString key,
+                                                        struct RustString value);
+
+/**
+ * Same as `set_metadata_string_native` for a number, which must be
+ * finite.
+ */
+struct RustResult_RustString set_metadata_number_native(struct BlockStoreInterface block_store_interface,
+                                                        struct RustString cid,
+                                                        struct RustString path_segments,
+                                                        struct RustString key,
+                                                        double value);
+
+/**
+ * Same as `set_metadata_string_native` for raw bytes.
+ */
+struct RustResult_RustString set_metadata_bytes_native(struct BlockStoreInterface block_store_interface,
+                                                       struct RustString cid,
+                                                       struct RustString path_segments,
+                                                       struct RustString key,
+                                                       struct RustBytes value);
+
+/**
+ * Removes a metadata key from the node at `path_segments` and returns
+ * the new root CID. Fails with `NotFound` if the key is not set.
+ */
+struct RustResult_RustString delete_metadata_native(struct BlockStoreInterface block_store_interface,
+                                                    struct RustString cid,
+                                                    struct RustString path_segments,
+                                                    struct RustString key);
+
+/**
+ * Searches everything below `path_segments` for entries whose name
+ * matches `pattern` and returns them in one `tree_native` shaped result
+ * encoded as `format`, with paths relative to `path_segments`. Symlinks
+ * are listed but not followed.
+ */
+struct RustResult_RustBytes find_native(struct BlockStoreInterface block_store_interface,
+                                        const struct BlockStoreExtensions *extensions,
+                                        struct RustString cid,
+                                        struct RustString path_segments,
+                                        struct RustString pattern,
+                                        struct FindOptions options,
+                                        enum OutputFormat format);
+
+/**
+ * Recreates the directory at `path_segments` and everything below it
+ * under `local_dir`, keeping modification times. Existing local files
+ * are skipped or overwritten per `existing`. Failures of single entries
+ * do not stop the export; they are listed in the result, encoded as
+ * `format`, next to the number of files written and skipped.
+ * Symlinks found under `local_dir` are never written through.
+ */
+struct RustResult_RustBytes export_dir_to_path_native(struct BlockStoreInterface block_store_interface,
+                                                      const struct BlockStoreExtensions *extensions,
+                                                      struct RustString cid,
+                                                      struct RustString path_segments,
+                                                      struct RustString local_dir,
+                                                      enum ExistingFiles existing,
+                                                      enum OutputFormat format);
+
+/**
+ * Copies the local directory `local_dir` into `path_segments` and
+ * returns the final root CID with a status for every file, encoded as
+ * `format`. Everything is written through one loaded forest, so no
+ * intermediate roots are handed out.
+ *
+ * `include` and `exclude` are glob lists matched against names.
+ * Excluded files and directories are left out of the report; when
+ * `include` is not empty, only files matching one of its globs are
+ * imported. Files already in the drive are skipped or overwritten per
+ * `existing`. Files are streamed from disk, and files and directories
+ * keep their modification times.
+ */
+struct RustResult_RustBytes import_dir_from_path_native(struct BlockStoreInterface block_store_interface,
+                                                        const struct BlockStoreExtensions *extensions,
+                                                        struct RustString cid,
+                                                        struct RustString local_dir,
+                                                        struct RustString path_segments,
+                                                        const struct RustString *include,
+                                                        size_t include_len,
+                                                        const struct RustString *exclude,
+                                                        size_t exclude_len,
+                                                        enum ExistingFiles existing,
+                                                        enum OutputFormat format);
+
+/**
+ * Writes everything reachable from `cid`, content included, to a CAR
+ * file at `output_path` with `cid` as its root, and returns a summary
+ * encoded as `format`. Blocks are streamed to disk as they are fetched,
+ * several at a time when `extensions` has a `get_many_fn`.
+ */
+struct RustResult_RustBytes export_car_native(struct BlockStoreInterface block_store_interface,
+                                              const struct BlockStoreExtensions *extensions,
+                                              struct RustString cid,
+                                              struct RustString output_path,
+                                              enum CarVersion car_version,
+                                              enum OutputFormat format);
+
+/**
+ * Stores every block of the CAR file at `car_path` in the host block
+ * store, checking each against its CID first, and returns the header
+ * roots encoded as `format`. CARv1 and CARv2 are both read. Blocks are
+ * stored in batches when `extensions` has a `put_many_fn`, leaving out
+ * those its `has_fn` reports as present.
+ */
+struct RustResult_RustBytes import_car_native(struct BlockStoreInterface block_store_interface,
+                                              const struct BlockStoreExtensions *extensions,
+                                              struct RustString car_path,
+                                              enum OutputFormat format);
+
+/**
+ * Creates an empty public directory and returns its root CID. Public
+ * roots are independent of the private forest and need no key.
+ */
+struct RustResult_RustString public_init_native(struct BlockStoreInterface block_store_interface);
+
+/**
+ * Writes `_content` to `path_segments` of the public directory at `cid`
+ * and returns the new root CID. The content is stored as a UnixFS file
+ * of raw leaves, so any IPFS node can read it from the file's CID.
+ */
+struct RustResult_RustString public_write_file_native(struct BlockStoreInterface block_store_interface,
+                                                      struct RustString cid,
+                                                      struct RustString path_segments,
+                                                      struct RustBytes _content);
+
+struct RustResult_RustBytes public_read_file_native(struct BlockStoreInterface block_store_interface,
+                                                    struct RustString cid,
+                                                    struct RustString path_segments);
+
+/**
+ * Lists a public directory, encoded the same way as `ls_with_format_native`.
+ */
+struct RustResult_RustBytes public_ls_native(struct BlockStoreInterface block_store_interface,
+                                             struct RustString cid,
+                                             struct RustString path_segments,
+                                             enum OutputFormat format);
+
+struct RustResult_RustString public_mkdir_native(struct BlockStoreInterface block_store_interface,
+                                                 struct RustString cid,
+                                                 struct RustString path_segments);
+
+struct RustResult_RustString public_mv_native(struct BlockStoreInterface block_store_interface,
+                                              struct RustString cid,
+                                              struct RustString source_path_segments,
+                                              struct RustString target_path_segments);
+
+/**
+ * Copies a file or a whole directory. Copied files share their content
+ * blocks with the source.
+ */
+struct RustResult_RustString public_cp_native(struct BlockStoreInterface block_store_interface,
+                                              struct RustString cid,
+                                              struct RustString source_path_segments,
+                                              struct RustString target_path_segments);
+
+struct RustResult_RustString public_rm_native(struct BlockStoreInterface block_store_interface,
+                                              struct RustString cid,
+                                              struct RustString path_segments);
+
+struct RustResult_RustSession wnfs_session_open(struct BlockStoreInterface block_store_interface,
+                                                const struct BlockStoreExtensions *extensions,
+                                                struct RustBytes wnfs_key,
+                                                struct RustString cid);
+
+/**
+ * Frees a session returned by `wnfs_session_open`. Passing null is a no-op.
+ */
+void wnfs_session_close(struct WnfsSession *session);
+
+struct RustResult_RustString wnfs_session_cid(struct WnfsSession *session);
+
+struct RustResult_RustString wnfs_session_write_file(struct WnfsSession *session,
+                                                     struct RustString path_segments,
+                                                     struct RustBytes _content);
+
+struct RustResult_RustString wnfs_session_write_file_from_path(struct WnfsSession *session,
+                                                               struct RustString path_segments,
+                                                               struct RustString _filename);
+
+struct RustResult_RustBytes wnfs_session_read_file(struct WnfsSession *session,
+                                                   struct RustString path_segments);
+
+struct RustResult_RustString wnfs_session_read_file_to_path(struct WnfsSession *session,
+                                                            struct RustString path_segments,
+                                                            struct RustString _filename);
+
+struct RustResult_RustString wnfs_session_read_filestream_to_path(struct WnfsSession *session,
+                                                                  struct RustString path_segments,
+                                                                  struct RustString _filename);
+
+struct RustResult_RustString wnfs_session_mkdir(struct WnfsSession *session,
+                                                struct RustString path_segments);
+
+struct RustResult_RustString wnfs_session_mv(struct WnfsSession *session,
+                                             struct RustString source_path_segments,
+                                             struct RustString target_path_segments);
+
+struct RustResult_RustString wnfs_session_cp(struct WnfsSession *session,
+                                             struct RustString source_path_segments,
+                                             struct RustString target_path_segments);
+
+struct RustResult_RustString wnfs_session_rm(struct WnfsSession *session,
+                                             struct RustString path_segments);
+
+struct RustResult_RustBytes wnfs_session_ls(struct WnfsSession *session,
+                                            struct RustString path_segments);
+
+struct RustResult_RustBytes wnfs_session_ls_with_format(struct WnfsSession *session,
+                                                        struct RustString path_segments,
+                                                        enum OutputFormat format);
+
+/**
+ * Starts a chunked write to `path_segments`. Nothing is written to the
+ * forest until the writer is finalized.
+ */
+struct RustResult_RustFileWriter file_writer_open_native(struct RustString path_segments);
+
+struct RustResult_RustVoid file_writer_push_native(struct WnfsFileWriter *writer,
+                                                   struct RustBytes chunk);
+
+/**
+ * Writes everything pushed so far to the forest at `cid` and returns the
+ * new root CID. The writer is freed whether or not this succeeds.
+ */
+struct RustResult_RustString file_writer_finalize_native(struct WnfsFileWriter *writer,
+                                                         struct BlockStoreInterface block_store_interface,
+                                                         const struct BlockStoreExtensions *extensions,
+                                                         struct RustString cid);
+
+/**
+ * Discards a writer and everything pushed to it.
+ */
+void file_writer_abort_native(struct WnfsFileWriter *writer);
+
+/**
+ * Session counterpart of `file_writer_finalize_native`.
+ */
+struct RustResult_RustString wnfs_session_file_writer_finalize(struct WnfsSession *session,
+                                                               struct WnfsFileWriter *writer);
+
+struct RustResult_RustBytes wnfs_session_read_file_range(struct WnfsSession *session,
+                                                         struct RustString path_segments,
+                                                         uint64_t offset,
+                                                         uint64_t length);
+
+struct RustResult_RustString wnfs_session_transaction(struct WnfsSession *session,
+                                                      const struct TransactionOp *ops,
+                                                      size_t ops_len);
+
+struct RustResult_RustBytes wnfs_session_stat(struct WnfsSession *session,
+                                              struct RustString path_segments,
+                                              enum OutputFormat format,
+                                              bool with_content);
+
+struct RustResult_RustBytes wnfs_session_tree(struct WnfsSession *session,
+                                              struct RustString path_segments,
+                                              uint32_t max_depth,
+                                              size_t max_entries,
+                                              enum OutputFormat format,
+                                              bool with_content,
+                                              void (*on_entry)(void *userdata, struct RustBytes entry),
+                                              void *userdata);
+
+/**
+ * Opens a directory shared by `share_native` from its base64 encoded
+ * access key and a root CID of the drive, either the one `share_native` returned
+ * or a later one. The shared directory is the root of the returned
+ * session, which is read-only: every `wnfs_session_*` mutation on it
+ * fails with `ReadOnly`. Free it with `wnfs_session_close`.
+ */
+struct RustResult_RustSession load_shared_native(struct BlockStoreInterface block_store_interface,
+                                                 const struct BlockStoreExtensions *extensions,
+                                                 struct RustString access_key,
+                                                 struct RustString cid);
+
+/**
+ * Reads a file shared by `share_native` from its base64 encoded access
+ * key and a root CID of the drive, either the one `share_native`
+ * returned or a later one.
+ */
+struct RustResult_RustBytes read_shared_file_native(struct BlockStoreInterface block_store_interface,
+                                                    const struct BlockStoreExtensions *extensions,
+                                                    struct RustString access_key,
+                                                    struct RustString cid);
+
+void load_with_wnfs_key_native_async(struct BlockStoreInterface block_store_interface,
+                                     struct RustBytes wnfs_key,
+                                     struct RustString cid,
+                                     VoidCallback callback,
+                                     void *userdata);
+
+void init_native_async(struct BlockStoreInterface block_store_interface,
+                       struct RustBytes wnfs_key,
+                       StringCallback callback,
+                       void *userdata);
+
+void write_file_from_path_native_async(struct BlockStoreInterface block_store_interface,
+                                       struct RustString cid,
+                                       struct RustString path_segments,
+                                       struct RustString _filename,
+                                       StringCallback callback,
+                                       void *userdata);
+
+void read_filestream_to_path_native_async(struct BlockStoreInterface block_store_interface,
+                                          struct RustString cid,
+                                          struct RustString path_segments,
+                                          struct RustString _filename,
+                                          StringCallback callback,
+                                          void *userdata);
+
+void read_file_to_path_native_async(struct BlockStoreInterface block_store_interface,
+                                    struct RustString cid,
+                                    struct RustString path_segments,
+                                    struct RustString _filename,
+                                    StringCallback callback,
+                                    void *userdata);
+
+void write_file_native_async(struct BlockStoreInterface block_store_interface,
+                             struct RustString cid,
+                             struct RustString path_segments,
+                             struct RustBytes _content,
+                             StringCallback callback,
+                             void *userdata);
+
+void read_file_native_async(struct BlockStoreInterface block_store_interface,
+                            struct RustString cid,
+                            struct RustString path_segments,
+                            BytesCallback callback,
+                            void *userdata);
+
+void read_file_range_native_async(struct BlockStoreInterface block_store_interface,
+                                  struct RustString cid,
+                                  struct RustString path_segments,
+                                  uint64_t offset,
+                                  uint64_t length,
+                                  BytesCallback callback,
+                                  void *userdata);
+
+void mkdir_native_async(struct BlockStoreInterface block_store_interface,
+                        struct RustString cid,
+                        struct RustString path_segments,
+                        StringCallback callback,
+                        void *userdata);
+
+void mv_native_async(struct BlockStoreInterface block_store_interface,
+                     struct RustString cid,
+                     struct RustString source_path_segments,
+                     struct RustString target_path_segments,
+                     StringCallback callback,
+                     void *userdata);
+
+void cp_native_async(struct BlockStoreInterface block_store_interface,
+                     struct RustString cid,
+                     struct RustString source_path_segments,
+                     struct RustString target_path_segments,
+                     StringCallback callback,
+                     void *userdata);
+
+void rm_native_async(struct BlockStoreInterface block_store_interface,
+                     struct RustString cid,
+                     struct RustString path_segments,
+                     StringCallback callback,
+                     void *userdata);
+
+void ls_native_async(struct BlockStoreInterface block_store_interface,
+                     struct RustString cid,
+                     struct RustString path_segments,
+                     BytesCallback callback,
+                     void *userdata);
+
+void ls_with_format_native_async(struct BlockStoreInterface block_store_interface,
+                                 struct RustString cid,
+                                 struct RustString path_segments,
+                                 enum OutputFormat format,
+                                 BytesCallback callback,
+                                 void *userdata);
+
+void stat_native_async(struct BlockStoreInterface block_store_interface,
+                       struct RustString cid,
+                       struct RustString path_segments,
+                       enum OutputFormat format,
+                       bool with_content,
+                       BytesCallback callback,
+                       void *userdata);
+
+void ls_with_meta_native_async(struct BlockStoreInterface block_store_interface,
+                               struct RustString cid,
+                               struct RustString path_segments,
+                               enum OutputFormat format,
+                               BytesCallback callback,
+                               void *userdata);
+
+void find_native_async(struct BlockStoreInterface block_store_interface,
+                       const struct BlockStoreExtensions *extensions,
+                       struct RustString cid,
+                       struct RustString path_segments,
+                       struct RustString pattern,
+                       struct FindOptions options,
+                       enum OutputFormat format,
+                       BytesCallback callback,
+                       void *userdata);
+
+void transaction_native_async(struct BlockStoreInterface block_store_interface,
+                              const struct BlockStoreExtensions *extensions,
+                              struct RustString cid,
+                              const struct TransactionOp *ops,
+                              size_t ops_len,
+                              StringCallback callback,
+                              void *userdata);
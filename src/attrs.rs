@@ -0,0 +1,348 @@
+use std::collections::{BTreeMap, HashSet};
+use std::io::Read;
+
+use libipld::{Cid, Ipld};
+use log::trace;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use wnfs::common::Metadata;
+use wnfsutils::private_forest::PrivateDirectoryHelper;
+
+use crate::error::{classify_error, WnfsErrorCode};
+use crate::listing::DirectoryReader;
+use crate::node;
+use crate::runtime::block_on;
+
+/// Key of a node's `Metadata` holding the target of a symlink.
+pub const SYMLINK_KEY: &str = "symlink";
+
+/// Same limit as POSIX `SYMLOOP_MAX` on Linux.
+pub const MAX_LINK_HOPS: usize = 40;
+
+/// Key of a node's `Metadata` under which its user metadata is kept, as a
+/// single map so that user keys never clash with the ones wnfs sets.
+pub const USER_META_KEY: &str = "meta";
+
+/// Bumped whenever a field of `MetaOutput` is removed or changes meaning.
+pub const META_OUTPUT_VERSION: u32 = 1;
+
+/// Value of a user metadata key. Encoded as a string, a float or a byte
+/// string; JSON has no byte strings, so bytes come out as an array of
+/// integers there.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MetaValue {
+    String(String),
+    Number(f64),
+    Bytes(Vec<u8>),
+}
+
+impl Serialize for MetaValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            MetaValue::String(value) => serializer.serialize_str(value),
+            MetaValue::Number(value) => serializer.serialize_f64(*value),
+            MetaValue::Bytes(value) => serializer.serialize_bytes(value),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for MetaValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match Ipld::deserialize(deserializer)? {
+            Ipld::String(value) => Ok(MetaValue::String(value)),
+            Ipld::Float(value) => Ok(MetaValue::Number(value)),
+            Ipld::Integer(value) => Ok(MetaValue::Number(value as f64)),
+            Ipld::Bytes(value) => Ok(MetaValue::Bytes(value)),
+            other => Err(D::Error::custom(format!(
+                "unsupported metadata value: {:?}",
+                other
+            ))),
+        }
+    }
+}
+
+/// User metadata of a node, keyed by name.
+pub type NodeMeta = BTreeMap<String, MetaValue>;
+
+#[derive(Serialize)]
+pub struct MetaOutput {
+    pub version: u32,
+    pub meta: NodeMeta,
+}
+
+/// User metadata kept in `metadata`, empty if it has none.
+pub fn user_meta(metadata: &Metadata) -> Result<NodeMeta, String> {
+    match metadata.get(USER_META_KEY) {
+        Some(ipld) => libipld::serde::from_ipld(ipld.to_owned()).map_err(|e| e.to_string()),
+        None => Ok(NodeMeta::new()),
+    }
+}
+
+/// Replaces the user metadata kept in `metadata` with `meta`.
+pub fn put_user_meta(metadata: &mut Metadata, meta: &NodeMeta) -> Result<(), String> {
+    let ipld = libipld::serde::to_ipld(meta).map_err(|e| e.to_string())?;
+    metadata.put(USER_META_KEY, ipld);
+    Ok(())
+}
+
+/// Target of a symlink, if `metadata` belongs to one.
+pub fn symlink_target(metadata: &Metadata) -> Option<String> {
+    match metadata.get(SYMLINK_KEY) {
+        Some(Ipld::String(target)) => Some(target.to_owned()),
+        _ => None,
+    }
+}
+
+pub fn split_link_target(target: &str) -> Vec<String> {
+    target
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Target of `link_path` if it is a symlink. Paths that do not exist, or
+/// lead through a file, are not links.
+fn link_target<R: DirectoryReader + ?Sized>(
+    helper: &mut R,
+    link_path: &[String],
+) -> Result<Option<String>, String> {
+    if link_path.is_empty() {
+        return Ok(None);
+    }
+    match helper.node_info(link_path, false) {
+        Ok(info) => Ok(info.target),
+        Err(msg) if classify_error(&msg) == WnfsErrorCode::BlockStore => Err(msg),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Fails if `path_segments` is a symlink, which a file write would turn
+/// into a file that still claims to be a link. Writes below a link need no
+/// check: wnfs refuses them, since the link is a file.
+fn check_not_link<R: DirectoryReader + ?Sized>(
+    helper: &mut R,
+    path_segments: &[String],
+) -> Result<(), String> {
+    match link_target(helper, path_segments)? {
+        Some(_) => Err(format!("{} is a symlink", path_segments.join("/"))),
+        None => Ok(()),
+    }
+}
+
+/// `synced_write_file` for paths that are not symlinks.
+pub fn write_file(
+    helper: &mut PrivateDirectoryHelper,
+    path_segments: &[String],
+    content: Vec<u8>,
+    modified: i64,
+) -> Result<Cid, String> {
+    check_not_link(helper, path_segments)?;
+    helper.synced_write_file(path_segments, content, modified)
+}
+
+/// `synced_write_file_from_path` for paths that are not symlinks.
+pub fn write_file_from_path(
+    helper: &mut PrivateDirectoryHelper,
+    path_segments: &[String],
+    filename: &String,
+) -> Result<Cid, String> {
+    check_not_link(helper, path_segments)?;
+    helper.synced_write_file_from_path(path_segments, filename)
+}
+
+/// `node::write_file_streaming` for paths that are not symlinks.
+pub fn write_file_streaming(
+    helper: &mut PrivateDirectoryHelper,
+    path_segments: &[String],
+    reader: impl Read,
+    modified: i64,
+) -> Result<(), String> {
+    check_not_link(helper, path_segments)?;
+    node::write_file_streaming(helper, path_segments, reader, modified)
+}
+
+/// Creates a symlink at `link_path` pointing to `target`, a path in the
+/// same form as every other path argument. The target does not need to
+/// exist. Fails if anything already exists at `link_path`.
+///
+/// The link is an empty private file whose metadata holds the target under
+/// `SYMLINK_KEY`, so it is encrypted, moved, copied and removed like any
+/// other node. Other wnfs clients see it as an empty file.
+pub fn symlink(
+    helper: &mut PrivateDirectoryHelper,
+    link_path: &[String],
+    target: &str,
+) -> Result<Cid, String> {
+    if link_path.is_empty() {
+        return Err("a symlink needs a name".to_string());
+    }
+    if split_link_target(target).is_empty() {
+        return Err("a symlink needs a target".to_string());
+    }
+    if block_on(node::get_node(helper, link_path)).is_ok() {
+        return Err(format!("{} already exists", link_path.join("/")));
+    }
+    create_symlink(helper, link_path, target)?;
+    node::store_root(helper)
+}
+
+/// Creates the symlink at `link_path` without checking `link_path` and
+/// `target` or storing anything. Only the root in memory changes.
+pub fn create_symlink(
+    helper: &mut PrivateDirectoryHelper,
+    link_path: &[String],
+    target: &str,
+) -> Result<(), String> {
+    trace!("symlink {:?} -> {}", link_path, target);
+    node::create_file_with_metadata(helper, link_path, |metadata| {
+        metadata.put(SYMLINK_KEY, Ipld::String(target.to_string()));
+    })
+}
+
+/// Removes the symlink at `link_path`, leaving its target alone.
+pub fn unlink(helper: &mut PrivateDirectoryHelper, link_path: &[String]) -> Result<Cid, String> {
+    readlink(helper, link_path)?;
+    helper.synced_rm(link_path)
+}
+
+/// Target of the symlink at `link_path`, as it was given.
+pub fn readlink<R: DirectoryReader + ?Sized>(
+    helper: &mut R,
+    link_path: &[String],
+) -> Result<String, String> {
+    link_target(helper, link_path)?
+        .ok_or_else(|| format!("symlink not found: {}", link_path.join("/")))
+}
+
+/// Replaces every symlink in `path_segments`, including in its parents,
+/// with its target until none is left. Fails on a loop or after
+/// `MAX_LINK_HOPS` links.
+pub fn resolve_path<R: DirectoryReader + ?Sized>(
+    helper: &mut R,
+    path_segments: &[String],
+) -> Result<Vec<String>, String> {
+    let mut path = path_segments.to_vec();
+    let mut seen = HashSet::new();
+    let mut hops = 0;
+    let mut i = 0;
+    while i < path.len() {
+        match link_target(helper, &path[..=i])? {
+            Some(target) => {
+                hops += 1;
+                if hops > MAX_LINK_HOPS || !seen.insert(path.join("/")) {
+                    return Err(format!(
+                        "too many levels of symbolic links resolving {}",
+                        path_segments.join("/")
+                    ));
+                }
+                let mut resolved = split_link_target(&target);
+                resolved.extend_from_slice(&path[i + 1..]);
+                path = resolved;
+                i = 0;
+            }
+            None => i += 1,
+        }
+    }
+    Ok(path)
+}
+
+fn node_meta(
+    helper: &PrivateDirectoryHelper,
+    path_segments: &[String],
+) -> Result<NodeMeta, String> {
+    let node = block_on(node::get_node(helper, path_segments))?;
+    user_meta(node::node_metadata(&node))
+}
+
+/// User metadata of the file or directory at `path_segments`.
+pub fn get_meta(
+    helper: &mut PrivateDirectoryHelper,
+    path_segments: &[String],
+) -> Result<MetaOutput, String> {
+    Ok(MetaOutput {
+        version: META_OUTPUT_VERSION,
+        meta: node_meta(helper, path_segments)?,
+    })
+}
+
+/// Applies `update` to the user metadata of the node at `path_segments` and
+/// stores the new root.
+fn update_meta<F>(
+    helper: &mut PrivateDirectoryHelper,
+    path_segments: &[String],
+    update: F,
+) -> Result<Cid, String>
+where
+    F: FnOnce(&mut NodeMeta),
+{
+    node::update_metadata(helper, path_segments, |metadata| {
+        let mut meta = user_meta(metadata)?;
+        update(&mut meta);
+        put_user_meta(metadata, &meta)
+    })?;
+    node::store_root(helper)
+}
+
+/// Sets `key` on the node at `path_segments` and returns the new root CID.
+///
+/// The value is kept in the node's own metadata, encrypted with the node,
+/// so it follows the node through moves, copies and revisions, and anyone
+/// who can read the node can read it. Setting it makes a new revision of
+/// the node.
+pub fn set_meta(
+    helper: &mut PrivateDirectoryHelper,
+    path_segments: &[String],
+    key: &str,
+    value: MetaValue,
+) -> Result<Cid, String> {
+    if key.is_empty() {
+        return Err("a metadata key cannot be empty".to_string());
+    }
+    if matches!(value, MetaValue::Number(number) if !number.is_finite()) {
+        return Err("metadata numbers must be finite".to_string());
+    }
+    trace!("set metadata {} on {:?}", key, path_segments);
+    update_meta(helper, path_segments, |meta| {
+        meta.insert(key.to_string(), value);
+    })
+}
+
+/// Removes `key` from the node at `path_segments` and returns the new root
+/// CID. Fails if the node has no such key.
+pub fn delete_meta(
+    helper: &mut PrivateDirectoryHelper,
+    path_segments: &[String],
+    key: &str,
+) -> Result<Cid, String> {
+    if !node_meta(helper, path_segments)?.contains_key(key) {
+        return Err(format!(
+            "metadata key {} not found on {}",
+            key,
+            path_segments.join("/")
+        ));
+    }
+    trace!("delete metadata {} from {:?}", key, path_segments);
+    update_meta(helper, path_segments, |meta| {
+        meta.remove(key);
+    })
+}
+
+/// Copies the user metadata of the node at `source_path` to the node at
+/// `target_path` in `target`, which may belong to another forest. Only the
+/// target's root in memory changes. Returns whether there was any.
+pub fn copy_user_meta(
+    source: &PrivateDirectoryHelper,
+    source_path: &[String],
+    target: &mut PrivateDirectoryHelper,
+    target_path: &[String],
+) -> Result<bool, String> {
+    let meta = node_meta(source, source_path)?;
+    if meta.is_empty() {
+        return Ok(false);
+    }
+    node::update_metadata(target, target_path, |metadata| {
+        put_user_meta(metadata, &meta)
+    })?;
+    Ok(true)
+}
@@ -0,0 +1,172 @@
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+
+use log::trace;
+use lru::LruCache;
+use once_cell::sync::Lazy;
+
+/// Identifies the host store a block was read from, taken from the
+/// interface `userdata`.
+pub type StoreKey = usize;
+
+/// Size-bounded LRU of raw blocks keyed by host store and CID bytes.
+///
+/// A CID always names the same bytes, but not every host store holds every
+/// block, and one store deleting a block says nothing about another. Entries
+/// are therefore scoped to the store they came from, so a block is only
+/// served to callers of that same store.
+pub struct BlockCache {
+    entries: LruCache<(StoreKey, Vec<u8>), Vec<u8>>,
+    bytes: usize,
+    max_bytes: usize,
+    cache_puts: bool,
+}
+
+impl BlockCache {
+    pub fn new(max_entries: usize, max_bytes: usize, cache_puts: bool) -> Option<BlockCache> {
+        let max_entries = NonZeroUsize::new(max_entries)?;
+        if max_bytes == 0 {
+            return None;
+        }
+        Some(BlockCache {
+            entries: LruCache::new(max_entries),
+            bytes: 0,
+            max_bytes,
+            cache_puts,
+        })
+    }
+
+    pub fn get(&mut self, store: StoreKey, cid: &[u8]) -> Option<Vec<u8>> {
+        self.entries.get(&(store, cid.to_vec())).cloned()
+    }
+
+    pub fn insert(&mut self, store: StoreKey, cid: Vec<u8>, bytes: Vec<u8>) {
+        if bytes.len() > self.max_bytes {
+            return;
+        }
+        self.bytes += bytes.len();
+        // `push` hands back either the previous value for the key or the
+        // entry evicted to stay within the entry limit.
+        if let Some((_, evicted)) = self.entries.push((store, cid), bytes) {
+            self.bytes -= evicted.len();
+        }
+        while self.bytes > self.max_bytes {
+            match self.entries.pop_lru() {
+                Some((_, evicted)) => self.bytes -= evicted.len(),
+                None => break,
+            }
+        }
+    }
+
+    pub fn remove(&mut self, store: StoreKey, cid: &[u8]) {
+        if let Some(bytes) = self.entries.pop(&(store, cid.to_vec())) {
+            self.bytes -= bytes.len();
+        }
+    }
+
+    pub fn purge(&mut self) {
+        self.entries.clear();
+        self.bytes = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Total size of the cached blocks in bytes.
+    pub fn size(&self) -> usize {
+        self.bytes
+    }
+}
+
+/// Process-wide cache consulted by `BridgedStore`. Disabled until
+/// `configure_block_cache` is called with non-zero limits.
+static BLOCK_CACHE: Lazy<Mutex<Option<BlockCache>>> = Lazy::new(|| Mutex::new(None));
+
+/// Replaces the shared cache with an empty one using the given limits. A zero
+/// limit disables caching. Blocks written through the store are only cached
+/// when `cache_puts` is set; otherwise the cache holds blocks that were read.
+pub fn configure_block_cache(max_entries: usize, max_bytes: usize, cache_puts: bool) {
+    trace!(
+        "block cache configured: max_entries={} max_bytes={} cache_puts={}",
+        max_entries,
+        max_bytes,
+        cache_puts
+    );
+    *BLOCK_CACHE.lock().unwrap() = BlockCache::new(max_entries, max_bytes, cache_puts);
+}
+
+pub fn purge_block_cache() {
+    if let Some(cache) = BLOCK_CACHE.lock().unwrap().as_mut() {
+        cache.purge();
+    }
+}
+
+pub fn cached_block(store: StoreKey, cid: &[u8]) -> Option<Vec<u8>> {
+    BLOCK_CACHE.lock().unwrap().as_mut()?.get(store, cid)
+}
+
+/// Caches a block read from `store`.
+pub fn cache_block(store: StoreKey, cid: &[u8], bytes: &[u8]) {
+    if let Some(cache) = BLOCK_CACHE.lock().unwrap().as_mut() {
+        cache.insert(store, cid.to_vec(), bytes.to_vec());
+    }
+}
+
+/// Caches a block written to `store`, if the cache was configured to keep
+/// written blocks.
+pub fn cache_put_block(store: StoreKey, cid: &[u8], bytes: &[u8]) {
+    if let Some(cache) = BLOCK_CACHE.lock().unwrap().as_mut() {
+        if cache.cache_puts {
+            cache.insert(store, cid.to_vec(), bytes.to_vec());
+        }
+    }
+}
+
+pub fn uncache_block(store: StoreKey, cid: &[u8]) {
+    if let Some(cache) = BLOCK_CACHE.lock().unwrap().as_mut() {
+        cache.remove(store, cid);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BlockCache;
+
+    #[test]
+    fn test_block_cache_limits() {
+        let mut cache = BlockCache::new(2, 10, false).unwrap();
+        cache.insert(1, vec![1], vec![0; 4]);
+        cache.insert(1, vec![2], vec![0; 4]);
+        assert!(cache.get(1, &[1]).is_some());
+
+        // Entries are scoped to the store they were read from.
+        assert!(cache.get(2, &[1]).is_none());
+
+        // Entry limit: [2] is now the least recently used.
+        cache.insert(1, vec![3], vec![0; 4]);
+        assert!(cache.get(1, &[2]).is_none());
+        assert_eq!(cache.size(), 8);
+
+        // Byte limit: 8 + 6 > 10 evicts down to fit.
+        cache.insert(1, vec![4], vec![0; 6]);
+        assert!(cache.size() <= 10);
+        assert!(cache.get(1, &[4]).is_some());
+
+        // Oversized blocks are never cached.
+        cache.insert(1, vec![5], vec![0; 11]);
+        assert!(cache.get(1, &[5]).is_none());
+
+        cache.remove(1, &[4]);
+        assert!(cache.get(1, &[4]).is_none());
+
+        cache.purge();
+        assert!(cache.is_empty());
+        assert_eq!(cache.size(), 0);
+        assert!(BlockCache::new(0, 10, false).is_none());
+    }
+}
@@ -8,8 +8,10 @@ use anyhow::Result;
 use log::trace;
 use wnfsutils::blockstore::FFIStore;
 
-use crate::blockstore_interface::BlockStoreInterface;
+use crate::block_cache::{cache_block, cache_put_block, cached_block, uncache_block, StoreKey};
+use crate::blockstore_interface::{BlockStoreExtensions, BlockStoreInterface};
 use crate::c_types::RustBytes;
+use crate::error::BLOCK_STORE_ERROR_PREFIX;
 
 struct LongVec(Vec<u8>);
 impl Display for LongVec {
@@ -29,25 +31,264 @@ impl Display for LongVec {
 #[derive(Clone)]
 pub struct BridgedStore {
     block_store_interface: BlockStoreInterface,
+    extensions: Option<BlockStoreExtensions>,
+    discard_puts: bool,
 }
 
 impl<'a> BridgedStore {
     pub fn new(block_store_interface: BlockStoreInterface) -> BridgedStore {
-        let block_store_interface = block_store_interface;
-        return BridgedStore {
-            block_store_interface: block_store_interface,
-        };
+        Self::with_extensions(block_store_interface, None)
+    }
+
+    /// Store that batches `get_blocks` and `put_blocks` through `extensions`
+    /// when the host provides them.
+    pub fn with_extensions(
+        block_store_interface: BlockStoreInterface,
+        extensions: Option<BlockStoreExtensions>,
+    ) -> BridgedStore {
+        BridgedStore {
+            block_store_interface,
+            extensions,
+            discard_puts: false,
+        }
+    }
+
+    /// The same host store, but every put succeeds without reaching the
+    /// host. Serializing a node through it yields the CID it would be
+    /// stored under, so read paths can hash nodes without writing.
+    pub fn without_puts(&self) -> BridgedStore {
+        BridgedStore {
+            discard_puts: true,
+            ..self.to_owned()
+        }
+    }
+
+    /// Key scoping this store's entries in the block cache.
+    fn cache_key(&self) -> StoreKey {
+        self.block_store_interface.userdata as StoreKey
+    }
+
+    /// Drops a block from this store's cache entries, for blocks the host
+    /// may remove outside of `delete_block`.
+    pub fn forget_block(&self, cid: &[u8]) {
+        uncache_block(self.cache_key(), cid);
+    }
+
+    /// Whether the host has the block, or `None` if it cannot tell.
+    pub fn has_block(&self, cid: &[u8]) -> Option<bool> {
+        let has_fn = self.extensions?.has_fn?;
+        Some(has_fn(
+            self.block_store_interface.userdata,
+            RustBytes::from(cid.to_vec()),
+        ))
+    }
+
+    pub fn supports_delete(&self) -> bool {
+        self.extensions
+            .map(|extensions| extensions.delete_fn.is_some())
+            .unwrap_or(false)
+    }
+
+    pub fn delete_block(&self, cid: &[u8]) -> Result<()> {
+        let delete_fn = self
+            .extensions
+            .and_then(|extensions| extensions.delete_fn)
+            .ok_or_else(|| {
+                anyhow::format_err!(
+                    "{}delete is not supported by the host",
+                    BLOCK_STORE_ERROR_PREFIX
+                )
+            })?;
+        // A failed delete may still have removed the block, so it is never
+        // served from the cache again either way.
+        self.forget_block(cid);
+        let data = delete_fn(
+            self.block_store_interface.userdata,
+            RustBytes::from(cid.to_vec()),
+        );
+        if !data.ok {
+            let err_str = data.err.to_string_lossy();
+            return Err(anyhow::format_err!(
+                "{}{}",
+                BLOCK_STORE_ERROR_PREFIX,
+                err_str
+            ));
+        }
+        trace!("delete: cid({:?})", cid);
+        self.block_store_interface
+            .to_owned()
+            .dealloc_after_put(data);
+        Ok(())
+    }
+
+    /// Fetches several blocks, in one host call when `get_many_fn` is
+    /// available. Cached blocks are never requested from the host.
+    pub fn get_blocks(&self, cids: &[Vec<u8>]) -> Result<Vec<Vec<u8>>> {
+        let mut blocks: Vec<Option<Vec<u8>>> = cids
+            .iter()
+            .map(|cid| cached_block(self.cache_key(), cid))
+            .collect();
+        let missing: Vec<usize> = (0..cids.len()).filter(|&i| blocks[i].is_none()).collect();
+        if missing.is_empty() {
+            return Ok(blocks.into_iter().flatten().collect());
+        }
+        let get_many_fn = self
+            .extensions
+            .and_then(|extensions| extensions.get_many_fn);
+        match get_many_fn {
+            Some(get_many_fn) => {
+                let rust_cids: Vec<RustBytes> = missing
+                    .iter()
+                    .map(|&i| RustBytes::from(cids[i].to_owned()))
+                    .collect();
+                let data = get_many_fn(
+                    self.block_store_interface.userdata,
+                    rust_cids.as_ptr(),
+                    rust_cids.len(),
+                );
+                if !data.ok {
+                    let err_str = data.err.to_string_lossy();
+                    return Err(anyhow::format_err!(
+                        "{}{}",
+                        BLOCK_STORE_ERROR_PREFIX,
+                        err_str
+                    ));
+                }
+                let fetched = unsafe { data.result.to_vecs() };
+                if let Some(dealloc) = self
+                    .extensions
+                    .and_then(|extensions| extensions.dealloc_after_get_many)
+                {
+                    dealloc(data);
+                }
+                if fetched.len() != missing.len() {
+                    return Err(anyhow::format_err!(
+                        "{}get_many returned {} blocks for {} cids",
+                        BLOCK_STORE_ERROR_PREFIX,
+                        fetched.len(),
+                        missing.len()
+                    ));
+                }
+                trace!("get_many: {} blocks", fetched.len());
+                for (&i, block) in missing.iter().zip(fetched) {
+                    cache_block(self.cache_key(), &cids[i], &block);
+                    blocks[i] = Some(block);
+                }
+            }
+            None => {
+                for &i in missing.iter() {
+                    blocks[i] = Some(self.get_block(cids[i].to_owned())?);
+                }
+            }
+        }
+        Ok(blocks.into_iter().flatten().collect())
+    }
+
+    /// Stores several blocks, in one host call when `put_many_fn` is
+    /// available. Blocks the host reports as present through `has_fn` are
+    /// skipped.
+    pub fn put_blocks(&self, blocks: &[(Vec<u8>, Vec<u8>)]) -> Result<()> {
+        if self.discard_puts {
+            return Ok(());
+        }
+        let pending: Vec<&(Vec<u8>, Vec<u8>)> = blocks
+            .iter()
+            .filter(|(cid, _)| self.has_block(cid) != Some(true))
+            .collect();
+        if pending.is_empty() {
+            return Ok(());
+        }
+        let put_many_fn = self
+            .extensions
+            .and_then(|extensions| extensions.put_many_fn);
+        match put_many_fn {
+            Some(put_many_fn) => {
+                let rust_cids: Vec<RustBytes> = pending
+                    .iter()
+                    .map(|(cid, _)| RustBytes::from(cid.to_owned()))
+                    .collect();
+                let rust_blocks: Vec<RustBytes> = pending
+                    .iter()
+                    .map(|(_, bytes)| RustBytes::from(bytes.to_owned()))
+                    .collect();
+                let data = put_many_fn(
+                    self.block_store_interface.userdata,
+                    rust_cids.as_ptr(),
+                    rust_blocks.as_ptr(),
+                    pending.len(),
+                );
+                if !data.ok {
+                    let err_str = data.err.to_string_lossy();
+                    return Err(anyhow::format_err!(
+                        "{}{}",
+                        BLOCK_STORE_ERROR_PREFIX,
+                        err_str
+                    ));
+                }
+                trace!("put_many: {} blocks", pending.len());
+                self.block_store_interface
+                    .to_owned()
+                    .dealloc_after_put(data);
+                for (cid, bytes) in pending {
+                    cache_put_block(self.cache_key(), cid, bytes);
+                }
+                Ok(())
+            }
+            None => {
+                for (cid, bytes) in pending {
+                    self.put_new_block(cid.to_owned(), bytes.to_owned())?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Stores a block without asking the host whether it has it.
+    fn put_new_block(&self, _cid: Vec<u8>, _bytes: Vec<u8>) -> Result<()> {
+        if self.discard_puts {
+            return Ok(());
+        }
+        let cid = RustBytes::from(_cid.to_owned());
+        let bytes = RustBytes::from(_bytes.to_owned());
+        let data = self.block_store_interface.to_owned().put(cid, bytes);
+        if !data.ok {
+            let err_str: String = data.err.into();
+            Err(anyhow::format_err!(
+                "{}{}",
+                BLOCK_STORE_ERROR_PREFIX,
+                err_str
+            ))
+        } else {
+            trace!(
+                "get: cid({:?}) -> data({})",
+                _cid,
+                LongVec(_bytes.to_owned())
+            );
+            self.block_store_interface
+                .to_owned()
+                .dealloc_after_put(data);
+            cache_put_block(self.cache_key(), &_cid, &_bytes);
+            Ok(())
+        }
     }
 }
 
 impl<'a> FFIStore<'a> for BridgedStore {
     /// Retrieves an array of bytes from the block store with given CID.
     fn get_block(&self, _cid: Vec<u8>) -> Result<Vec<u8>> {
+        if let Some(cached) = cached_block(self.cache_key(), &_cid) {
+            trace!("get: cid({:?}) served from block cache", _cid);
+            return Ok(cached);
+        }
         let cid = RustBytes::from(_cid.to_owned());
         let data = self.block_store_interface.to_owned().get(cid);
         if !data.to_owned().ok {
             let err_str: String = data.to_owned().err.into();
-            Err(anyhow::format_err!(err_str))
+            Err(anyhow::format_err!(
+                "{}{}",
+                BLOCK_STORE_ERROR_PREFIX,
+                err_str
+            ))
         } else {
             let result: Vec<u8> = data.to_owned().result.into();
             trace!(
@@ -58,29 +299,22 @@ impl<'a> FFIStore<'a> for BridgedStore {
             self.block_store_interface
                 .to_owned()
                 .dealloc_after_get(data);
+            cache_block(self.cache_key(), &_cid, &result);
             Ok(result.to_owned())
         }
     }
 
-    /// Stores an array of bytes in the block store.
+    /// Stores an array of bytes in the block store, unless the host's
+    /// `has_fn` says it already has them.
     fn put_block(&self, _cid: Vec<u8>, _bytes: Vec<u8>) -> Result<()> {
-        let cid = RustBytes::from(_cid.to_owned());
-        let bytes = RustBytes::from(_bytes.to_owned());
-        let data = self.block_store_interface.to_owned().put(cid, bytes);
-        if !data.ok {
-            let err_str: String = data.err.into();
-            Err(anyhow::format_err!(err_str))
-        } else {
-            trace!(
-                "get: cid({:?}) -> data({})",
-                _cid,
-                LongVec(_bytes.to_owned())
-            );
-            self.block_store_interface
-                .to_owned()
-                .dealloc_after_put(data);
-            Ok(())
+        if self.discard_puts {
+            return Ok(());
+        }
+        if self.has_block(&_cid) == Some(true) {
+            trace!("put: cid({:?}) already stored", _cid);
+            return Ok(());
         }
+        self.put_new_block(_cid, _bytes)
     }
 }
 
@@ -1,6 +1,8 @@
+use std::mem::{size_of, MaybeUninit};
+
 use libc::c_void;
 
-use crate::c_types::{RustBytes, RustResult, RustVoid};
+use crate::c_types::{HostResult, RustBytes, RustBytesArray, RustVoid};
 
 #[repr(C)]
 #[derive(Clone)]
@@ -10,32 +12,32 @@ pub struct BlockStoreInterface {
         userdata: *mut c_void,
         cid: RustBytes,
         bytes: RustBytes,
-    ) -> RustResult<RustVoid>,
-    pub get_fn: extern "C" fn(userdata: *mut c_void, cid: RustBytes) -> RustResult<RustBytes>,
-    pub dealloc_after_get: extern "C" fn(data: RustResult<RustBytes>),
-    pub dealloc_after_put: extern "C" fn(data: RustResult<RustVoid>),
+    ) -> HostResult<RustVoid>,
+    pub get_fn: extern "C" fn(userdata: *mut c_void, cid: RustBytes) -> HostResult<RustBytes>,
+    pub dealloc_after_get: extern "C" fn(data: HostResult<RustBytes>),
+    pub dealloc_after_put: extern "C" fn(data: HostResult<RustVoid>),
 }
 
 unsafe impl Send for BlockStoreInterface {}
 
 impl BlockStoreInterface {
-    pub fn put(self, cid: RustBytes, bytes: RustBytes) -> RustResult<RustVoid> {
+    pub fn put(self, cid: RustBytes, bytes: RustBytes) -> HostResult<RustVoid> {
         let result = (self.put_fn)(self.userdata, cid, bytes);
         std::mem::forget(self);
         result
     }
-    pub fn get(self, cid: RustBytes) -> RustResult<RustBytes> {
+    pub fn get(self, cid: RustBytes) -> HostResult<RustBytes> {
         let result = (self.get_fn)(self.userdata, cid);
         std::mem::forget(self);
         result
     }
 
-    pub fn dealloc_after_get(self, data: RustResult<RustBytes>) {
+    pub fn dealloc_after_get(self, data: HostResult<RustBytes>) {
         (self.dealloc_after_get)(data);
         std::mem::forget(self);
     }
 
-    pub fn dealloc_after_put(self, data: RustResult<RustVoid>) {
+    pub fn dealloc_after_put(self, data: HostResult<RustVoid>) {
         (self.dealloc_after_put)(data);
         std::mem::forget(self);
     }
@@ -47,3 +49,116 @@ impl BlockStoreInterface {
 //         panic!("BlockStoreInterface must have explicit put or get call")
 //     }
 // }
+
+/// Current layout of `BlockStoreExtensions`. Hosts set `version` to the
+/// value they were built against and `size` to the size of the struct they
+/// fill in; fields past `size` are treated as absent.
+pub const BLOCK_STORE_EXTENSIONS_VERSION: u32 = 1;
+
+/// Optional callbacks a host block store can provide on top of
+/// `BlockStoreInterface`. Every call that walks or writes more than a single
+/// path takes them, and so does `wnfs_session_open`, whose session then uses
+/// them for every call made through it; the single-path `*_native` calls
+/// keep their original signatures. Every callback may be null, in which case
+/// the single-block `put_fn`/`get_fn` path is used instead.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct BlockStoreExtensions {
+    /// `sizeof(BlockStoreExtensions)` as the host sees it.
+    pub size: usize,
+    pub version: u32,
+    /// Asked before every put, to leave out blocks the host has.
+    pub has_fn: Option<extern "C" fn(userdata: *mut c_void, cid: RustBytes) -> bool>,
+    pub delete_fn:
+        Option<extern "C" fn(userdata: *mut c_void, cid: RustBytes) -> HostResult<RustVoid>>,
+    pub put_many_fn: Option<
+        extern "C" fn(
+            userdata: *mut c_void,
+            cids: *const RustBytes,
+            blocks: *const RustBytes,
+            count: usize,
+        ) -> HostResult<RustVoid>,
+    >,
+    /// Must return exactly `count` blocks, in the order of `cids`.
+    pub get_many_fn: Option<
+        extern "C" fn(
+            userdata: *mut c_void,
+            cids: *const RustBytes,
+            count: usize,
+        ) -> HostResult<RustBytesArray>,
+    >,
+    pub dealloc_after_get_many: Option<extern "C" fn(data: HostResult<RustBytesArray>)>,
+}
+
+fn size_of_pointee<T>(_: *const T) -> usize {
+    size_of::<T>()
+}
+
+/// Start and end offset of `$field` in `BlockStoreExtensions`.
+macro_rules! field_bounds {
+    ($field:ident) => {{
+        let layout = MaybeUninit::<BlockStoreExtensions>::uninit();
+        let base = layout.as_ptr();
+        let field = std::ptr::addr_of!((*base).$field);
+        let offset = field as usize - base as usize;
+        (offset, offset + size_of_pointee(field))
+    }};
+}
+
+impl BlockStoreExtensions {
+    /// Copies the extensions at `extensions`, null meaning none. Fields are
+    /// read one by one and only when they fit entirely within the first
+    /// `size` bytes, so a host built against an older, shorter layout leaves
+    /// the newer callbacks null. A `size` that ends inside a field is
+    /// rejected.
+    pub unsafe fn from_ptr(
+        extensions: *const BlockStoreExtensions,
+    ) -> Result<Option<BlockStoreExtensions>, String> {
+        if extensions.is_null() {
+            return Ok(None);
+        }
+        let size = std::ptr::addr_of!((*extensions).size).read();
+        let (_, header_end) = field_bounds!(version);
+        if size < header_end {
+            return Err(format!(
+                "block store extensions of size {} are too small",
+                size
+            ));
+        }
+        let version = std::ptr::addr_of!((*extensions).version).read();
+        if version == 0 {
+            return Err("unsupported block store extensions version 0".to_string());
+        }
+        let mut copied = BlockStoreExtensions {
+            size,
+            version,
+            has_fn: None,
+            delete_fn: None,
+            put_many_fn: None,
+            get_many_fn: None,
+            dealloc_after_get_many: None,
+        };
+        let mut on_boundary = size == header_end || size >= size_of::<BlockStoreExtensions>();
+        macro_rules! copy_field {
+            ($field:ident) => {
+                let (offset, end) = field_bounds!($field);
+                on_boundary |= size == offset || size == end;
+                if size >= end {
+                    copied.$field = std::ptr::addr_of!((*extensions).$field).read();
+                }
+            };
+        }
+        copy_field!(has_fn);
+        copy_field!(delete_fn);
+        copy_field!(put_many_fn);
+        copy_field!(get_many_fn);
+        copy_field!(dealloc_after_get_many);
+        if !on_boundary {
+            return Err(format!(
+                "block store extensions size {} does not end on a field boundary",
+                size
+            ));
+        }
+        Ok(Some(copied))
+    }
+}
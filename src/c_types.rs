@@ -10,6 +10,10 @@ use std::ffi::{CStr, CString};
 use wnfs::common::Metadata;
 use wnfsutils::private_forest::PrivateDirectoryHelper;
 
+use crate::error::{classify_error, WnfsErrorCode};
+use crate::session::WnfsSession;
+use crate::writer::WnfsFileWriter;
+
 pub trait Empty {
     fn empty() -> Self;
 }
@@ -157,6 +161,15 @@ impl Empty for RustString {
 }
 
 impl RustString {
+    /// Copies the string out without taking ownership of the pointer.
+    pub fn to_string_lossy(&self) -> String {
+        if self.str.is_null() {
+            String::new()
+        } else {
+            unsafe { CStr::from_ptr(self.str).to_string_lossy().into_owned() }
+        }
+    }
+
     fn free(self) {
         if !self.str.is_null() {
             unsafe { CString::from_raw(self.str as *mut c_char) };
@@ -181,15 +194,202 @@ impl Empty for RustVoid {
     }
 }
 
+#[derive(Clone)]
+#[repr(C)]
+pub struct RustBytesArray {
+    pub data: *const RustBytes,
+    pub len: size_t,
+}
+impl Empty for RustBytesArray {
+    fn empty() -> Self {
+        Self {
+            data: ::std::ptr::null(),
+            len: 0,
+        }
+    }
+}
+impl RustBytesArray {
+    /// Copies every element out without taking ownership of them.
+    pub unsafe fn to_vecs(&self) -> Vec<Vec<u8>> {
+        if self.data.is_null() {
+            return Vec::new();
+        }
+        std::slice::from_raw_parts(self.data, self.len)
+            .iter()
+            .map(|bytes| bytes.as_slice().to_vec())
+            .collect()
+    }
+}
+
+#[repr(C)]
+pub struct RustSession {
+    pub session: *mut WnfsSession,
+}
+impl From<WnfsSession> for RustSession {
+    fn from(value: WnfsSession) -> Self {
+        Self {
+            session: Box::into_raw(Box::new(value)),
+        }
+    }
+}
+impl Empty for RustSession {
+    fn empty() -> Self {
+        Self {
+            session: ::std::ptr::null_mut(),
+        }
+    }
+}
+
+#[repr(C)]
+pub struct RustFileWriter {
+    pub writer: *mut WnfsFileWriter,
+}
+impl From<WnfsFileWriter> for RustFileWriter {
+    fn from(value: WnfsFileWriter) -> Self {
+        Self {
+            writer: Box::into_raw(Box::new(value)),
+        }
+    }
+}
+impl Empty for RustFileWriter {
+    fn empty() -> Self {
+        Self {
+            writer: ::std::ptr::null_mut(),
+        }
+    }
+}
+
+impl RustBytes {
+    /// Borrows the bytes without copying them.
+    pub unsafe fn as_slice<'b>(&self) -> &'b [u8] {
+        if self.data.is_null() {
+            &[]
+        } else {
+            std::slice::from_raw_parts(self.data, self.len)
+        }
+    }
+}
+
+/// A copy of a host string that owns its allocation. Used to keep
+/// arguments alive after the FFI call that passed them has returned.
+pub struct OwnedRustString(RustString);
+
+impl OwnedRustString {
+    pub fn new(value: String) -> Self {
+        OwnedRustString(RustString::from(value))
+    }
+
+    pub fn copy_from(value: &RustString) -> Self {
+        if value.str.is_null() {
+            OwnedRustString(RustString::empty())
+        } else {
+            OwnedRustString(RustString::from(value.to_string_lossy()))
+        }
+    }
+
+    pub fn get(&self) -> RustString {
+        self.0.clone()
+    }
+}
+
+impl Drop for OwnedRustString {
+    fn drop(&mut self) {
+        self.0.clone().free();
+    }
+}
+
+/// Byte counterpart of `OwnedRustString`.
+pub struct OwnedRustBytes(RustBytes);
+
+impl OwnedRustBytes {
+    pub fn new(value: Vec<u8>) -> Self {
+        OwnedRustBytes(RustBytes::from(value))
+    }
+
+    pub fn copy_from(value: &RustBytes) -> Self {
+        OwnedRustBytes(RustBytes::from(unsafe { value.as_slice() }.to_vec()))
+    }
+
+    pub fn get(&self) -> RustBytes {
+        self.0.clone()
+    }
+}
+
+impl Drop for OwnedRustBytes {
+    fn drop(&mut self) {
+        if !self.0.data.is_null() {
+            unsafe { ::libc::free(self.0.data as *mut c_void) };
+        }
+    }
+}
+
+/// Encoding of structured outputs. `Legacy` is the `???`/`!!!` delimited
+/// listing and is only understood by ls.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(C)]
+pub enum OutputFormat {
+    Legacy = 0,
+    Json = 1,
+    Cbor = 2,
+}
+
 #[derive(Clone)]
 #[repr(C)]
 pub struct RustResult<T> {
     pub ok: bool,
     pub err: RustString,
     pub result: T,
+    pub code: WnfsErrorCode,
+    /// Optional JSON object with structured error context, null on success.
+    pub detail: RustString,
 }
 
 impl<T: Empty> RustResult<T> {
+    /// Builds an error whose code is inferred from the message.
+    pub fn error(err: RustString) -> Self {
+        let code = classify_error(&err.to_string_lossy());
+        Self::error_with_code(code, err)
+    }
+
+    pub fn error_with_code(code: WnfsErrorCode, err: RustString) -> Self {
+        Self {
+            ok: false,
+            err: err,
+            result: T::empty(),
+            code,
+            detail: RustString::empty(),
+        }
+    }
+
+    pub fn with_detail(mut self, detail: String) -> Self {
+        self.detail = RustString::from(detail);
+        self
+    }
+
+    pub fn ok(result: T) -> Self {
+        Self {
+            ok: true,
+            err: RustString::empty(),
+            result,
+            code: WnfsErrorCode::Ok,
+            detail: RustString::empty(),
+        }
+    }
+}
+
+/// Result a host block store callback returns. It keeps the three-field
+/// layout `RustResult` had before `code` and `detail` were added, so host
+/// callbacks built against that layout still match; the host reports
+/// failures through `err` alone.
+#[derive(Clone)]
+#[repr(C)]
+pub struct HostResult<T> {
+    pub ok: bool,
+    pub err: RustString,
+    pub result: T,
+}
+
+impl<T: Empty> HostResult<T> {
     pub fn error(err: RustString) -> Self {
         Self {
             ok: false,
@@ -214,6 +414,28 @@ pub unsafe fn prepare_path_segments(path_segments: RustString) -> Vec<String> {
         .collect()
 }
 
+/// Parses `len` CID strings starting at `cids`.
+pub unsafe fn prepare_cids(cids: *const RustString, len: usize) -> Result<Vec<Cid>, String> {
+    if cids.is_null() || len == 0 {
+        return Ok(Vec::new());
+    }
+    std::slice::from_raw_parts(cids, len)
+        .iter()
+        .map(|cid| cid.clone().try_into())
+        .collect()
+}
+
+/// Copies `len` strings starting at `strings`.
+pub unsafe fn prepare_strings(strings: *const RustString, len: usize) -> Vec<String> {
+    if strings.is_null() || len == 0 {
+        return Vec::new();
+    }
+    std::slice::from_raw_parts(strings, len)
+        .iter()
+        .map(|string| string.clone().into())
+        .collect()
+}
+
 pub fn prepare_ls_output(ls_result: Vec<(String, Metadata)>) -> Result<Vec<u8>, String> {
     let mut result: Vec<u8> = Vec::new();
 
@@ -243,12 +465,24 @@ pub fn prepare_ls_output(ls_result: Vec<(String, Metadata)>) -> Result<Vec<u8>,
     Ok(result)
 }
 
+impl<T> RustResult<T> {
+    /// Frees the error message and detail, returning the result to be freed
+    /// by the caller.
+    fn free_error(self) -> T {
+        self.err.free();
+        self.detail.free();
+        self.result
+    }
+}
+
+/// Frees the result, error message and detail of `arg`.
 #[no_mangle]
 pub extern "C" fn rust_result_string_free(arg: RustResult<RustString>) {
-    arg.result.free();
+    arg.free_error().free();
 }
 
+/// Frees the result, error message and detail of `arg`.
 #[no_mangle]
 pub extern "C" fn rust_result_bytes_free(arg: RustResult<RustBytes>) {
-    arg.result.free();
+    arg.free_error().free();
 }
@@ -0,0 +1,320 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use libipld::cbor::DagCborCodec;
+use libipld::codec::Codec;
+use libipld::multihash::{Code, MultihashDigest};
+use libipld::{Cid, Ipld};
+use log::trace;
+use serde::Serialize;
+
+use crate::blockstore::BridgedStore;
+use crate::dag::{walk_dag, WALK_BATCH_SIZE};
+
+/// Fixed bytes opening every CARv2 file.
+pub const CAR_V2_PRAGMA: [u8; 11] = [
+    0x0a, 0xa1, 0x67, 0x76, 0x65, 0x72, 0x73, 0x69, 0x6f, 0x6e, 0x02,
+];
+
+/// Characteristics, data offset, data size and index offset.
+pub const CAR_V2_HEADER_LEN: usize = 40;
+
+/// Largest header or block section accepted on import, far above any block
+/// wnfs writes, so that a corrupt length cannot exhaust memory.
+pub const MAX_SECTION_LEN: u64 = 16 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(C)]
+pub enum CarVersion {
+    V1 = 1,
+    /// A CARv1 payload behind a CARv2 header, without an index.
+    V2 = 2,
+}
+
+/// Bumped whenever a field of `CarExportOutput` is removed or changes
+/// meaning.
+pub const CAR_EXPORT_OUTPUT_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+pub struct CarExportOutput {
+    pub version: u32,
+    pub root: String,
+    pub car_version: u32,
+    pub blocks: usize,
+    /// Size of the written file.
+    pub bytes: u64,
+}
+
+/// Bumped whenever a field of `CarImportOutput` is removed or changes
+/// meaning.
+pub const CAR_IMPORT_OUTPUT_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+pub struct CarImportOutput {
+    pub version: u32,
+    /// Roots named in the archive header.
+    pub roots: Vec<String>,
+    pub blocks: usize,
+}
+
+pub fn write_varint<W: Write>(writer: &mut W, mut value: u64) -> io::Result<()> {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            return writer.write_all(&[byte]);
+        }
+        writer.write_all(&[byte | 0x80])?;
+    }
+}
+
+/// Reads an unsigned LEB128 varint, or `None` at the end of the input.
+pub fn read_varint<R: Read>(reader: &mut R) -> io::Result<Option<u64>> {
+    let mut value = 0u64;
+    for i in 0..10 {
+        let mut byte = [0u8; 1];
+        if reader.read(&mut byte)? == 0 {
+            return if i == 0 {
+                Ok(None)
+            } else {
+                Err(io::ErrorKind::UnexpectedEof.into())
+            };
+        }
+        value |= u64::from(byte[0] & 0x7f) << (7 * i);
+        if byte[0] & 0x80 == 0 {
+            return Ok(Some(value));
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "varint too long",
+    ))
+}
+
+/// DAG-CBOR encoded CARv1 header naming `roots`.
+pub fn car_header(roots: &[Cid]) -> Result<Vec<u8>, String> {
+    let mut header = BTreeMap::new();
+    header.insert(
+        "roots".to_string(),
+        Ipld::List(roots.iter().map(|cid| Ipld::Link(*cid)).collect()),
+    );
+    header.insert("version".to_string(), Ipld::Integer(1));
+    DagCborCodec
+        .encode(&Ipld::Map(header))
+        .map_err(|e| e.to_string())
+}
+
+fn partial_path(output_path: &Path) -> PathBuf {
+    let name = output_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    output_path.with_file_name(format!(".{}.wnfs-part", name))
+}
+
+fn write_car(
+    store: &BridgedStore,
+    root: Cid,
+    path: &Path,
+    car_version: CarVersion,
+) -> Result<CarExportOutput, String> {
+    let io_error = |e: io::Error| e.to_string();
+    let mut writer = BufWriter::new(File::create(path).map_err(io_error)?);
+    let data_offset = match car_version {
+        CarVersion::V1 => 0,
+        CarVersion::V2 => {
+            writer.write_all(&CAR_V2_PRAGMA).map_err(io_error)?;
+            writer
+                .write_all(&[0u8; CAR_V2_HEADER_LEN])
+                .map_err(io_error)?;
+            (CAR_V2_PRAGMA.len() + CAR_V2_HEADER_LEN) as u64
+        }
+    };
+    let header = car_header(&[root])?;
+    write_varint(&mut writer, header.len() as u64).map_err(io_error)?;
+    writer.write_all(&header).map_err(io_error)?;
+
+    let mut blocks = 0;
+    walk_dag(store, &[root], &HashSet::new(), true, |cid, block| {
+        let block = block.ok_or_else(|| format!("block {} was not fetched", cid))?;
+        let cid_bytes = cid.to_bytes();
+        write_varint(&mut writer, (cid_bytes.len() + block.len()) as u64).map_err(io_error)?;
+        writer.write_all(&cid_bytes).map_err(io_error)?;
+        writer.write_all(block).map_err(io_error)?;
+        blocks += 1;
+        Ok(())
+    })?;
+
+    let bytes = writer.stream_position().map_err(io_error)?;
+    if car_version == CarVersion::V2 {
+        let mut v2_header = [0u8; CAR_V2_HEADER_LEN];
+        v2_header[16..24].copy_from_slice(&data_offset.to_le_bytes());
+        v2_header[24..32].copy_from_slice(&(bytes - data_offset).to_le_bytes());
+        writer
+            .seek(SeekFrom::Start(CAR_V2_PRAGMA.len() as u64))
+            .map_err(io_error)?;
+        writer.write_all(&v2_header).map_err(io_error)?;
+    }
+    writer
+        .into_inner()
+        .map_err(|e| e.to_string())?
+        .sync_all()
+        .map_err(io_error)?;
+    Ok(CarExportOutput {
+        version: CAR_EXPORT_OUTPUT_VERSION,
+        root: root.to_string(),
+        car_version: car_version as u32,
+        blocks,
+        bytes,
+    })
+}
+
+/// Writes every block reachable from `root`, including the encrypted file
+/// content, to a CAR file at `output_path` with `root` as its only root.
+///
+/// Blocks are written as they are fetched and only their CIDs are kept, so
+/// memory use grows with the number of blocks rather than their size. The
+/// archive is assembled next to `output_path` and only moved in place once
+/// complete; a failed export leaves nothing behind.
+pub fn export_car(
+    store: &BridgedStore,
+    root: Cid,
+    output_path: &Path,
+    car_version: CarVersion,
+) -> Result<CarExportOutput, String> {
+    let partial_path = partial_path(output_path);
+    let result = write_car(store, root, &partial_path, car_version).and_then(|output| {
+        fs::rename(&partial_path, output_path).map_err(|e| e.to_string())?;
+        Ok(output)
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&partial_path);
+    }
+    if let Ok(output) = result.as_ref() {
+        trace!(
+            "exported {} blocks from cid={} to {:?}",
+            output.blocks,
+            root,
+            output_path
+        );
+    }
+    result
+}
+
+/// Reads the next length-prefixed section, or `None` at the end of the input.
+fn read_section<R: Read>(reader: &mut R) -> Result<Option<Vec<u8>>, String> {
+    let len = match read_varint(reader).map_err(|e| format!("invalid car: {}", e))? {
+        Some(len) => len,
+        None => return Ok(None),
+    };
+    if len > MAX_SECTION_LEN {
+        return Err(format!("invalid car: section of {} bytes", len));
+    }
+    let mut section = vec![0u8; len as usize];
+    reader
+        .read_exact(&mut section)
+        .map_err(|e| format!("invalid car: {}", e))?;
+    Ok(Some(section))
+}
+
+/// Version and roots of a CAR header. The CARv2 pragma reads as a header
+/// with version 2 and no roots.
+fn read_header<R: Read>(reader: &mut R) -> Result<(i128, Vec<Cid>), String> {
+    let section = read_section(reader)?.ok_or("invalid car: empty file")?;
+    let header = match DagCborCodec.decode(&section) {
+        Ok(Ipld::Map(header)) => header,
+        Ok(_) => return Err("invalid car header: not a map".to_string()),
+        Err(e) => return Err(format!("invalid car header: {}", e)),
+    };
+    let version = match header.get("version") {
+        Some(Ipld::Integer(version)) => *version,
+        _ => return Err("invalid car header: no version".to_string()),
+    };
+    let roots = match header.get("roots") {
+        Some(Ipld::List(roots)) => roots
+            .iter()
+            .map(|root| match root {
+                Ipld::Link(cid) => Ok(*cid),
+                _ => Err("invalid car header: root is not a cid".to_string()),
+            })
+            .collect::<Result<Vec<Cid>, String>>()?,
+        _ => Vec::new(),
+    };
+    Ok((version, roots))
+}
+
+/// Checks that `block` hashes to the digest in `cid`.
+pub fn verify_block(cid: &Cid, block: &[u8]) -> Result<(), String> {
+    let code =
+        Code::try_from(cid.hash().code()).map_err(|_| format!("unsupported hash in {}", cid))?;
+    if code.digest(block) == *cid.hash() {
+        Ok(())
+    } else {
+        Err(format!("block does not match its cid {}", cid))
+    }
+}
+
+/// Verifies and stores the block sections of a CARv1 payload, in batches.
+fn import_blocks<R: Read>(store: &BridgedStore, reader: &mut R) -> Result<usize, String> {
+    let mut count = 0;
+    let mut batch: Vec<(Vec<u8>, Vec<u8>)> = Vec::with_capacity(WALK_BATCH_SIZE);
+    while let Some(section) = read_section(reader)? {
+        let mut cursor = Cursor::new(section.as_slice());
+        let cid = Cid::read_bytes(&mut cursor).map_err(|e| format!("invalid car: {}", e))?;
+        let block = &section[cursor.position() as usize..];
+        verify_block(&cid, block)?;
+        batch.push((cid.to_bytes(), block.to_vec()));
+        count += 1;
+        if batch.len() == WALK_BATCH_SIZE {
+            store.put_blocks(&batch).map_err(|e| e.to_string())?;
+            batch.clear();
+        }
+    }
+    store.put_blocks(&batch).map_err(|e| e.to_string())?;
+    Ok(count)
+}
+
+/// Stores every block of the CARv1 or CARv2 archive at `car_path` in the
+/// host block store and returns the roots named in its header.
+///
+/// Each block is checked against its CID before it is stored, and the
+/// import stops at the first one that does not match. Blocks stored before
+/// that point are valid and simply unreferenced.
+pub fn import_car(store: &BridgedStore, car_path: &Path) -> Result<CarImportOutput, String> {
+    let mut reader = BufReader::new(File::open(car_path).map_err(|e| e.to_string())?);
+    let (version, roots) = read_header(&mut reader)?;
+    let (roots, blocks) = match version {
+        1 => (roots, import_blocks(store, &mut reader)?),
+        2 => {
+            let mut v2_header = [0u8; CAR_V2_HEADER_LEN];
+            reader
+                .read_exact(&mut v2_header)
+                .map_err(|e| format!("invalid car: {}", e))?;
+            let le_u64 = |range: std::ops::Range<usize>| {
+                let mut bytes = [0u8; 8];
+                bytes.copy_from_slice(&v2_header[range]);
+                u64::from_le_bytes(bytes)
+            };
+            let (data_offset, data_size) = (le_u64(16..24), le_u64(24..32));
+            reader
+                .seek(SeekFrom::Start(data_offset))
+                .map_err(|e| e.to_string())?;
+            let mut payload = (&mut reader).take(data_size);
+            match read_header(&mut payload)? {
+                (1, roots) => (roots, import_blocks(store, &mut payload)?),
+                (version, _) => {
+                    return Err(format!("invalid car: inner version {}", version));
+                }
+            }
+        }
+        version => return Err(format!("unsupported car version {}", version)),
+    };
+    trace!("imported {} blocks from {:?}", blocks, car_path);
+    Ok(CarImportOutput {
+        version: CAR_IMPORT_OUTPUT_VERSION,
+        roots: roots.iter().map(|root| root.to_string()).collect(),
+        blocks,
+    })
+}
@@ -0,0 +1,83 @@
+use std::collections::HashSet;
+
+use libipld::cbor::DagCborCodec;
+use libipld::codec::Codec;
+use libipld::pb::DagPbCodec;
+use libipld::{Cid, Ipld, IpldCodec};
+
+use crate::blockstore::BridgedStore;
+
+/// Number of blocks requested from the host at once while walking.
+pub const WALK_BATCH_SIZE: usize = 64;
+
+/// CIDs linked from a block. Everything in the private forest is either
+/// DAG-CBOR (HAMT nodes) or raw ciphertext, which has no links. Public
+/// directories are DAG-CBOR too and their file content is UnixFS, DAG-PB
+/// nodes over raw leaves.
+pub fn block_links(cid: &Cid, block: &[u8]) -> Result<Vec<Cid>, String> {
+    let ipld: Ipld = match IpldCodec::try_from(cid.codec()) {
+        Ok(IpldCodec::DagCbor) => DagCborCodec.decode(block).map_err(|e| e.to_string())?,
+        Ok(IpldCodec::DagPb) => DagPbCodec.decode(block).map_err(|e| e.to_string())?,
+        _ => return Ok(Vec::new()),
+    };
+    let mut links = Vec::new();
+    ipld.references(&mut links);
+    Ok(links)
+}
+
+fn is_leaf(cid: &Cid) -> bool {
+    cid.codec() == u64::from(IpldCodec::Raw)
+}
+
+/// Visits every block reachable from `roots` exactly once, depth first,
+/// and returns the set of visited CIDs.
+///
+/// Blocks in `skip` are neither visited nor descended into. Raw leaves are
+/// only fetched when `fetch_leaves` is set; otherwise `on_block` receives
+/// `None` for them. Up to `WALK_BATCH_SIZE` blocks are taken off the top of
+/// the stack at once, so pending links grow with the depth of the DAG
+/// rather than its width. Only the visited CIDs grow with its size.
+pub fn walk_dag<F>(
+    store: &BridgedStore,
+    roots: &[Cid],
+    skip: &HashSet<Cid>,
+    fetch_leaves: bool,
+    mut on_block: F,
+) -> Result<HashSet<Cid>, String>
+where
+    F: FnMut(&Cid, Option<&[u8]>) -> Result<(), String>,
+{
+    let mut visited: HashSet<Cid> = HashSet::new();
+    let mut pending: Vec<Cid> = roots.iter().rev().copied().collect();
+    while !pending.is_empty() {
+        let mut batch = pending.split_off(pending.len().saturating_sub(WALK_BATCH_SIZE));
+        batch.reverse();
+        batch.retain(|cid| !skip.contains(cid) && visited.insert(*cid));
+        let (fetch, leaves): (Vec<Cid>, Vec<Cid>) = batch
+            .into_iter()
+            .partition(|cid| fetch_leaves || !is_leaf(cid));
+        for cid in leaves.iter() {
+            on_block(cid, None)?;
+        }
+        if fetch.is_empty() {
+            continue;
+        }
+        let keys: Vec<Vec<u8>> = fetch.iter().map(|cid| cid.to_bytes()).collect();
+        let blocks = store.get_blocks(&keys).map_err(|e| e.to_string())?;
+        for (cid, block) in fetch.iter().zip(blocks.iter()) {
+            on_block(cid, Some(block))?;
+        }
+        // Links of the last block go on the stack first, so that the first
+        // block's come up next.
+        for (cid, block) in fetch.iter().zip(blocks.iter()).rev() {
+            let links = block_links(cid, block)?;
+            pending.extend(
+                links
+                    .into_iter()
+                    .rev()
+                    .filter(|link| !visited.contains(link)),
+            );
+        }
+    }
+    Ok(visited)
+}
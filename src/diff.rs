@@ -0,0 +1,265 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::ControlFlow;
+
+use libipld::Cid;
+use log::trace;
+use serde::Serialize;
+
+use crate::blockstore::BridgedStore;
+use crate::error::{classify_error, WnfsErrorCode};
+use crate::ios::load_helper;
+use crate::listing::{
+    child_path, collect_ls_entries, walk_tree, DirectoryReader, EntryKind, LsEntry,
+};
+
+/// Every entry below a directory, keyed by its `/` separated relative path.
+pub type TreeSnapshot = BTreeMap<String, LsEntry>;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Change {
+    pub path: String,
+    pub change: ChangeKind,
+    pub kind_before: Option<EntryKind>,
+    pub kind_after: Option<EntryKind>,
+    /// Only set when content info was requested.
+    pub size_before: Option<u64>,
+    pub size_after: Option<u64>,
+}
+
+/// Bumped whenever a field of `DiffOutput` is removed or changes meaning.
+pub const DIFF_OUTPUT_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+pub struct DiffOutput {
+    pub version: u32,
+    pub changes: Vec<Change>,
+}
+
+/// Walks the whole tree below `path_segments`. A missing directory is an
+/// empty snapshot, so that paths created or deleted between two roots show
+/// up as added or removed.
+pub fn snapshot<R: DirectoryReader + ?Sized>(
+    helper: &mut R,
+    path_segments: &[String],
+    with_content: bool,
+) -> Result<TreeSnapshot, String> {
+    let mut tree = TreeSnapshot::new();
+    let walked = walk_tree(helper, path_segments, None, None, with_content, |entry| {
+        tree.insert(entry.path, entry.entry);
+        Ok(ControlFlow::Continue(()))
+    });
+    match walked {
+        Ok(_) => Ok(tree),
+        Err(msg) if classify_error(&msg) == WnfsErrorCode::NotFound => Ok(TreeSnapshot::new()),
+        Err(msg) => Err(msg),
+    }
+}
+
+/// Whether an entry changed between two listings made with content info.
+/// Files are compared by node CID, which changes with every write, however
+/// close together. Directories only change kind; their content shows up as
+/// changes of their descendants.
+pub fn entry_changed(before: &LsEntry, after: &LsEntry) -> bool {
+    match (before.kind, after.kind) {
+        (EntryKind::File, EntryKind::File) => before.cid != after.cid,
+        (EntryKind::Symlink, EntryKind::Symlink) => before.target != after.target,
+        (before_kind, after_kind) => before_kind != after_kind,
+    }
+}
+
+fn added(path: String, entry: &LsEntry) -> Change {
+    Change {
+        path,
+        change: ChangeKind::Added,
+        kind_before: None,
+        kind_after: Some(entry.kind),
+        size_before: None,
+        size_after: entry.size,
+    }
+}
+
+fn removed(path: String, entry: &LsEntry) -> Change {
+    Change {
+        path,
+        change: ChangeKind::Removed,
+        kind_before: Some(entry.kind),
+        kind_after: None,
+        size_before: entry.size,
+        size_after: None,
+    }
+}
+
+fn modified(path: String, before: &LsEntry, after: &LsEntry) -> Change {
+    Change {
+        path,
+        change: ChangeKind::Modified,
+        kind_before: Some(before.kind),
+        kind_after: Some(after.kind),
+        size_before: before.size,
+        size_after: after.size,
+    }
+}
+
+/// Changes between two snapshots, sorted by path.
+pub fn diff_snapshots(before: &TreeSnapshot, after: &TreeSnapshot) -> Vec<Change> {
+    let mut changes = Vec::new();
+    for (path, old) in before.iter() {
+        match after.get(path) {
+            None => changes.push(removed(path.to_owned(), old)),
+            Some(new) if entry_changed(old, new) => {
+                changes.push(modified(path.to_owned(), old, new))
+            }
+            Some(_) => {}
+        }
+    }
+    for (path, new) in after.iter() {
+        if !before.contains_key(path) {
+            changes.push(added(path.to_owned(), new));
+        }
+    }
+    changes.sort_by(|a, b| a.path.cmp(&b.path));
+    changes
+}
+
+/// Entries of `dir_path` keyed by name, with node CIDs and, with
+/// `with_content`, file sizes. A missing directory has none.
+fn dir_entries<R: DirectoryReader + ?Sized>(
+    helper: &mut R,
+    dir_path: &[String],
+    with_content: bool,
+) -> Result<BTreeMap<String, LsEntry>, String> {
+    let entries = match collect_ls_entries(helper, dir_path, with_content, false) {
+        Ok(entries) => entries,
+        Err(msg) if classify_error(&msg) == WnfsErrorCode::NotFound => return Ok(BTreeMap::new()),
+        Err(msg) => return Err(msg),
+    };
+    Ok(entries
+        .into_iter()
+        .map(|entry| (entry.name.to_owned(), entry))
+        .collect())
+}
+
+/// Records `change` for everything below the directory at `dir_path`.
+fn subtree_changes<R: DirectoryReader + ?Sized>(
+    helper: &mut R,
+    dir_path: &[String],
+    relative: &str,
+    with_content: bool,
+    change: fn(String, &LsEntry) -> Change,
+    changes: &mut Vec<Change>,
+) -> Result<(), String> {
+    walk_tree(helper, dir_path, None, None, with_content, |entry| {
+        changes.push(change(format!("{}/{}", relative, entry.path), &entry.entry));
+        Ok(ControlFlow::Continue(()))
+    })?;
+    Ok(())
+}
+
+/// Compares the trees below `path_segments` in the forests at `cid_a` and
+/// `cid_b`. Both are walked side by side by node CID: files are modified
+/// when their node changed, however close together the writes were, and
+/// directories whose node is the same on both sides are skipped with
+/// everything below them. Sizes are only reported with `with_content`.
+pub fn diff_roots(
+    store: BridgedStore,
+    cid_a: Cid,
+    cid_b: Cid,
+    path_segments: &[String],
+    with_content: bool,
+) -> Result<DiffOutput, String> {
+    let before = &mut load_helper(store.to_owned(), cid_a)?;
+    let after = &mut load_helper(store, cid_b)?;
+    let root_len = path_segments.len();
+    let mut changes = Vec::new();
+    let mut pending = vec![path_segments.to_vec()];
+    while let Some(dir_path) = pending.pop() {
+        let before_entries = dir_entries(before, &dir_path, with_content)?;
+        let after_entries = dir_entries(after, &dir_path, with_content)?;
+        let names: BTreeSet<&String> = before_entries.keys().chain(after_entries.keys()).collect();
+        for name in names {
+            let entry_path = child_path(&dir_path, name);
+            let relative = entry_path[root_len..].join("/");
+            match (before_entries.get(name), after_entries.get(name)) {
+                (Some(old), Some(new))
+                    if old.kind == EntryKind::Dir && new.kind == EntryKind::Dir =>
+                {
+                    if old.cid != new.cid {
+                        pending.push(entry_path);
+                    }
+                }
+                (Some(old), Some(new)) => {
+                    if entry_changed(old, new) {
+                        changes.push(modified(relative.to_owned(), old, new));
+                    }
+                    if old.kind == EntryKind::Dir {
+                        subtree_changes(
+                            before,
+                            &entry_path,
+                            &relative,
+                            with_content,
+                            removed,
+                            &mut changes,
+                        )?;
+                    }
+                    if new.kind == EntryKind::Dir {
+                        subtree_changes(
+                            after,
+                            &entry_path,
+                            &relative,
+                            with_content,
+                            added,
+                            &mut changes,
+                        )?;
+                    }
+                }
+                (Some(old), None) => {
+                    changes.push(removed(relative.to_owned(), old));
+                    if old.kind == EntryKind::Dir {
+                        subtree_changes(
+                            before,
+                            &entry_path,
+                            &relative,
+                            with_content,
+                            removed,
+                            &mut changes,
+                        )?;
+                    }
+                }
+                (None, Some(new)) => {
+                    changes.push(added(relative.to_owned(), new));
+                    if new.kind == EntryKind::Dir {
+                        subtree_changes(
+                            after,
+                            &entry_path,
+                            &relative,
+                            with_content,
+                            added,
+                            &mut changes,
+                        )?;
+                    }
+                }
+                (None, None) => {}
+            }
+        }
+    }
+    changes.sort_by(|a, b| a.path.cmp(&b.path));
+    trace!(
+        "diff {} -> {} at {:?}: {} changes",
+        cid_a,
+        cid_b,
+        path_segments,
+        changes.len()
+    );
+    Ok(DiffOutput {
+        version: DIFF_OUTPUT_VERSION,
+        changes,
+    })
+}
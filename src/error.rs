@@ -0,0 +1,99 @@
+use serde::Serialize;
+
+/// Stable error codes carried by every `RustResult`. Values are part of the
+/// C ABI: never renumber, only append.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[repr(C)]
+pub enum WnfsErrorCode {
+    Ok = 0,
+    Unknown = 1,
+    /// A null handle, malformed option or otherwise unusable argument.
+    InvalidArgument = 2,
+    /// The root CID string could not be parsed.
+    InvalidCid = 3,
+    /// The path does not exist in the forest.
+    NotFound = 4,
+    /// The host block store failed to return or store a block.
+    BlockStore = 5,
+    /// The forest could not be opened or decrypted with the given key.
+    WrongKey = 6,
+    /// Reading or writing a path on the local filesystem failed.
+    LocalIo = 7,
+    /// A mutation was attempted through a read-only (shared) session.
+    ReadOnly = 8,
+}
+
+/// Prefix of every error raised by `BridgedStore` so that it can be told
+/// apart from wnfs errors once wnfsutils has flattened it into a string.
+pub const BLOCK_STORE_ERROR_PREFIX: &str = "block store error: ";
+
+/// Message of every mutation refused by a read-only session.
+pub const READ_ONLY_SESSION: &str = "session is read-only";
+
+/// Message of a garbage collection asked to delete through a host block
+/// store that has no `delete_fn`.
+pub const DELETE_UNSUPPORTED: &str =
+    "gc: delete requested but the host block store has no delete_fn";
+
+/// wnfsutils reports every failure as a plain `String`, so the code is
+/// recovered from the message, but only from markers that cannot turn up
+/// by accident: the ones this crate raises itself, std's I/O errors and
+/// wnfs's not-found errors. Anything else is `Unknown`. Call sites that
+/// know what failed, such as CID parsing or opening a forest with a key,
+/// pass their code explicitly.
+pub fn classify_error(msg: &str) -> WnfsErrorCode {
+    let lower = msg.to_lowercase();
+    if msg.contains(BLOCK_STORE_ERROR_PREFIX) {
+        WnfsErrorCode::BlockStore
+    } else if msg == READ_ONLY_SESSION {
+        WnfsErrorCode::ReadOnly
+    } else if msg == DELETE_UNSUPPORTED {
+        WnfsErrorCode::InvalidArgument
+    } else if lower.contains("(os error ") {
+        WnfsErrorCode::LocalIo
+    } else if lower.contains("not found") || lower.contains("cannot find") {
+        WnfsErrorCode::NotFound
+    } else {
+        WnfsErrorCode::Unknown
+    }
+}
+
+/// Code for a failure to open a forest with a key. Unless its blocks could
+/// not be read, the key did not open it, whatever wnfs reported.
+pub fn load_error_code(msg: &str) -> WnfsErrorCode {
+    match classify_error(msg) {
+        WnfsErrorCode::BlockStore => WnfsErrorCode::BlockStore,
+        _ => WnfsErrorCode::WrongKey,
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorDetail<'a> {
+    function: &'a str,
+    code: WnfsErrorCode,
+    message: &'a str,
+    /// Index of the failing operation for batched calls.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    op_index: Option<usize>,
+}
+
+/// JSON object describing where an error happened, returned in
+/// `RustResult::detail`.
+pub fn error_detail(function: &str, code: WnfsErrorCode, message: &str) -> String {
+    error_detail_with_index(function, code, message, None)
+}
+
+pub fn error_detail_with_index(
+    function: &str,
+    code: WnfsErrorCode,
+    message: &str,
+    op_index: Option<usize>,
+) -> String {
+    serde_json::to_string(&ErrorDetail {
+        function,
+        code,
+        message,
+        op_index,
+    })
+    .unwrap_or_default()
+}
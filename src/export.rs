@@ -0,0 +1,246 @@
+use std::ffi::CString;
+use std::fs;
+use std::io;
+use std::ops::ControlFlow;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+use log::trace;
+use serde::Serialize;
+use wnfsutils::private_forest::PrivateDirectoryHelper;
+
+use crate::error::{classify_error, WnfsErrorCode};
+use crate::listing::{child_path, walk_tree, EntryKind};
+
+/// What to do with a file that already exists where an export or import
+/// would write it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(C)]
+pub enum ExistingFiles {
+    Skip = 0,
+    Overwrite = 1,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct TransferFailure {
+    /// `/` separated path relative to the exported directory.
+    pub path: String,
+    pub code: WnfsErrorCode,
+    pub message: String,
+}
+
+/// Bumped whenever a field of `ExportOutput` is removed or changes meaning.
+pub const EXPORT_OUTPUT_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+pub struct ExportOutput {
+    pub version: u32,
+    /// Files written.
+    pub exported: usize,
+    /// Existing files left alone, and symlinks, which have no local
+    /// equivalent.
+    pub skipped: usize,
+    pub failed: Vec<TransferFailure>,
+}
+
+/// Names that would escape or alias the target directory on disk.
+pub fn is_unsafe_name(name: &str) -> bool {
+    name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\', '\0'])
+}
+
+/// Sets the access and modification times of `path` to `modified`, in
+/// seconds since the unix epoch.
+pub fn set_mtime(path: &Path, modified: i64) -> io::Result<()> {
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+    let time = libc::timeval {
+        tv_sec: modified as libc::time_t,
+        tv_usec: 0,
+    };
+    let times = [time, time];
+    if unsafe { libc::utimes(c_path.as_ptr(), times.as_ptr()) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// What is at `path`, without following a symlink there. `None` when
+/// nothing is.
+fn local_entry(path: &Path) -> Result<Option<fs::FileType>, String> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => Err(format!(
+            "refusing to write through symlink {}",
+            path.display()
+        )),
+        Ok(metadata) => Ok(Some(metadata.file_type())),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Creates one directory of the export. Its parent was created or checked
+/// just before, so nothing is created outside the target directory.
+fn create_local_dir(path: &Path) -> Result<(), String> {
+    match local_entry(path)? {
+        Some(file_type) if file_type.is_dir() => Ok(()),
+        Some(_) => Err(format!("{} exists and is not a directory", path.display())),
+        None => fs::create_dir(path).map_err(|e| e.to_string()),
+    }
+}
+
+fn failure(path: &str, message: String) -> TransferFailure {
+    TransferFailure {
+        path: path.to_string(),
+        code: classify_error(&message),
+        message,
+    }
+}
+
+/// Streams one file to `local_path` through a hidden partial file next to
+/// it, so that a failed read never leaves a truncated file behind.
+fn export_file(
+    helper: &mut PrivateDirectoryHelper,
+    path_segments: &[String],
+    local_path: &Path,
+    modified: Option<i64>,
+) -> Result<(), String> {
+    let name = local_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let partial_path = local_path.with_file_name(format!(".{}.wnfs-part", name));
+    let partial_name = partial_path.to_string_lossy().to_string();
+    if local_entry(&partial_path)?.is_some() {
+        fs::remove_file(&partial_path).map_err(|e| e.to_string())?;
+    }
+    let result = helper
+        .synced_read_filestream_to_path(&partial_name, path_segments, 0)
+        .and_then(|_| fs::rename(&partial_path, local_path).map_err(|e| e.to_string()));
+    if result.is_err() {
+        let _ = fs::remove_file(&partial_path);
+    }
+    result?;
+    if let Some(modified) = modified {
+        set_mtime(local_path, modified).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Recreates everything below `path_segments` under `local_dir`, which is
+/// created if needed.
+///
+/// Only failing to list the tree or to create `local_dir` aborts the export.
+/// Any other failure is recorded in `failed` and the export moves on; the
+/// entries below a directory that could not be created fail with it.
+/// Modification times are copied to files and directories. Nothing is
+/// written through a symlink found below `local_dir`; such entries fail.
+pub fn export_dir(
+    helper: &mut PrivateDirectoryHelper,
+    path_segments: &[String],
+    local_dir: &Path,
+    existing: ExistingFiles,
+) -> Result<ExportOutput, String> {
+    let mut entries = Vec::new();
+    walk_tree(helper, path_segments, None, None, false, |entry| {
+        entries.push(entry);
+        Ok(ControlFlow::Continue(()))
+    })?;
+    fs::create_dir_all(local_dir).map_err(|e| e.to_string())?;
+
+    let mut output = ExportOutput {
+        version: EXPORT_OUTPUT_VERSION,
+        exported: 0,
+        skipped: 0,
+        failed: Vec::new(),
+    };
+    // Directories that failed, whose entries are reported with them, and the
+    // ones created, whose times are set once their content is in place.
+    let mut failed_dirs: Vec<String> = Vec::new();
+    let mut created_dirs: Vec<(PathBuf, i64)> = Vec::new();
+    for found in entries {
+        let path = found.path;
+        if let Some(dir) = failed_dirs
+            .iter()
+            .find(|dir| path.starts_with(dir.as_str()) && path[dir.len()..].starts_with('/'))
+        {
+            output
+                .failed
+                .push(failure(&path, format!("parent directory {} failed", dir)));
+            continue;
+        }
+        let names: Vec<&str> = path.split('/').collect();
+        if names.iter().any(|name| is_unsafe_name(name)) {
+            output
+                .failed
+                .push(failure(&path, format!("unsafe name in path {:?}", path)));
+            if found.entry.kind == EntryKind::Dir {
+                failed_dirs.push(path);
+            }
+            continue;
+        }
+        let local_path = names
+            .iter()
+            .fold(local_dir.to_path_buf(), |local, name| local.join(name));
+        match found.entry.kind {
+            EntryKind::Dir => match create_local_dir(&local_path) {
+                Ok(()) => {
+                    if let Some(modified) = found.entry.modified {
+                        created_dirs.push((local_path, modified));
+                    }
+                }
+                Err(msg) => {
+                    output.failed.push(failure(&path, msg));
+                    failed_dirs.push(path);
+                }
+            },
+            EntryKind::Symlink => output.skipped += 1,
+            EntryKind::File => {
+                match local_entry(&local_path) {
+                    Ok(Some(file_type)) if file_type.is_dir() => {
+                        output.failed.push(failure(
+                            &path,
+                            format!("{} is a directory", local_path.display()),
+                        ));
+                        continue;
+                    }
+                    Ok(Some(_)) if existing == ExistingFiles::Skip => {
+                        output.skipped += 1;
+                        continue;
+                    }
+                    Ok(_) => {}
+                    Err(msg) => {
+                        output.failed.push(failure(&path, msg));
+                        continue;
+                    }
+                }
+                let entry_path = names.iter().fold(path_segments.to_vec(), |segments, name| {
+                    child_path(&segments, name)
+                });
+                match export_file(helper, &entry_path, &local_path, found.entry.modified) {
+                    Ok(()) => output.exported += 1,
+                    Err(msg) => output.failed.push(failure(&path, msg)),
+                }
+            }
+        }
+    }
+    // Deepest first, as writing into a directory changes its time.
+    for (local_path, modified) in created_dirs.iter().rev() {
+        if let Err(e) = set_mtime(local_path, *modified) {
+            let path = local_path
+                .strip_prefix(local_dir)
+                .unwrap_or(local_path)
+                .to_string_lossy()
+                .to_string();
+            output.failed.push(failure(&path, e.to_string()));
+        }
+    }
+    trace!(
+        "exported {:?} to {:?}: {} written, {} skipped, {} failed",
+        path_segments,
+        local_dir,
+        output.exported,
+        output.skipped,
+        output.failed.len()
+    );
+    Ok(output)
+}
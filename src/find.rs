@@ -0,0 +1,168 @@
+use std::ops::ControlFlow;
+
+use log::trace;
+use regex::{Regex, RegexBuilder};
+
+use crate::listing::{
+    child_path, fill_content, walk_tree, DirectoryReader, EntryKind, TreeOutput,
+    TREE_OUTPUT_VERSION,
+};
+
+/// How the pattern given to `find_native` is read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(C)]
+pub enum PatternSyntax {
+    /// `*`, `?` and `[...]` classes, negated with `[!...]`, over the whole
+    /// name.
+    Glob = 0,
+    /// Regular expression matching anywhere in the name unless anchored.
+    Regex = 1,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(C)]
+pub enum KindFilter {
+    AnyKind = 0,
+    FilesOnly = 1,
+    DirsOnly = 2,
+}
+
+impl KindFilter {
+    fn matches(self, kind: EntryKind) -> bool {
+        match self {
+            KindFilter::AnyKind => true,
+            KindFilter::FilesOnly => kind == EntryKind::File,
+            KindFilter::DirsOnly => kind == EntryKind::Dir,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct FindOptions {
+    pub syntax: PatternSyntax,
+    pub kind: KindFilter,
+    pub case_insensitive: bool,
+    /// Zero means unlimited.
+    pub max_results: usize,
+    /// Fill in `size` and `cid` of matching files, which requires reading
+    /// them.
+    pub with_content: bool,
+}
+
+/// Index of the `]` closing the class opened at `start`. A `]` right after
+/// the opening `[` or `[!` belongs to the class.
+fn class_end(chars: &[char], start: usize) -> Option<usize> {
+    let mut i = start + 1;
+    if chars.get(i) == Some(&'!') {
+        i += 1;
+    }
+    if chars.get(i) == Some(&']') {
+        i += 1;
+    }
+    chars
+        .get(i..)?
+        .iter()
+        .position(|&c| c == ']')
+        .map(|offset| i + offset)
+}
+
+/// Translates a glob into an anchored regular expression. A `[` without a
+/// closing `]` is literal.
+pub fn glob_to_regex(glob: &str) -> String {
+    let chars: Vec<char> = glob.chars().collect();
+    let mut translated = String::from("^");
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '*' => translated.push_str(".*"),
+            '?' => translated.push('.'),
+            '[' => match class_end(&chars, i) {
+                Some(end) => {
+                    let mut j = i + 1;
+                    translated.push('[');
+                    if chars[j] == '!' {
+                        translated.push('^');
+                        j += 1;
+                    }
+                    for &c in &chars[j..end] {
+                        if matches!(c, '\\' | '[' | ']' | '^' | '&' | '~') {
+                            translated.push('\\');
+                        }
+                        translated.push(c);
+                    }
+                    translated.push(']');
+                    i = end;
+                }
+                None => translated.push_str(r"\["),
+            },
+            c => translated.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+        i += 1;
+    }
+    translated.push('$');
+    translated
+}
+
+pub fn compile_pattern(
+    pattern: &str,
+    syntax: PatternSyntax,
+    case_insensitive: bool,
+) -> Result<Regex, String> {
+    let source = match syntax {
+        PatternSyntax::Glob => glob_to_regex(pattern),
+        PatternSyntax::Regex => pattern.to_string(),
+    };
+    RegexBuilder::new(&source)
+        .case_insensitive(case_insensitive)
+        .build()
+        .map_err(|e| format!("invalid pattern {:?}: {}", pattern, e))
+}
+
+/// Walks everything below `path_segments` and returns the entries whose name
+/// matches `pattern`, in walk order, with paths relative to
+/// `path_segments`. `truncated` is set when `max_results` was reached with
+/// more matches left.
+pub fn find_entries<R: DirectoryReader + ?Sized>(
+    helper: &mut R,
+    path_segments: &[String],
+    pattern: &Regex,
+    options: &FindOptions,
+) -> Result<TreeOutput, String> {
+    let max_results = Some(options.max_results).filter(|&max| max > 0);
+    let mut entries = Vec::new();
+    let (_, truncated) = walk_tree(helper, path_segments, None, None, false, |entry| {
+        if !options.kind.matches(entry.entry.kind) || !pattern.is_match(&entry.entry.name) {
+            return Ok(ControlFlow::Continue(()));
+        }
+        if max_results.map_or(false, |max| entries.len() >= max) {
+            return Ok(ControlFlow::Break(()));
+        }
+        entries.push(entry);
+        Ok(ControlFlow::Continue(()))
+    })?;
+    if options.with_content {
+        for found in entries
+            .iter_mut()
+            .filter(|found| found.entry.kind == EntryKind::File)
+        {
+            let entry_path = found
+                .path
+                .split('/')
+                .fold(path_segments.to_vec(), |path, name| child_path(&path, name));
+            fill_content(helper, &entry_path, &mut found.entry)?;
+        }
+    }
+    trace!(
+        "found {} entries matching {} below {:?}",
+        entries.len(),
+        pattern,
+        path_segments
+    );
+    Ok(TreeOutput {
+        version: TREE_OUTPUT_VERSION,
+        count: entries.len(),
+        truncated,
+        entries,
+    })
+}
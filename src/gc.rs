@@ -0,0 +1,80 @@
+use std::collections::HashSet;
+
+use libipld::Cid;
+use log::trace;
+use serde::Serialize;
+
+use crate::blockstore::BridgedStore;
+use crate::dag::walk_dag;
+use crate::error::DELETE_UNSUPPORTED;
+
+#[derive(Serialize, Debug, Default)]
+pub struct GcFailure {
+    pub cid: String,
+    pub error: String,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct GcReport {
+    pub version: u32,
+    /// Blocks reachable from a dropped root but from no kept root.
+    pub dead: Vec<String>,
+    /// How many of `dead` were deleted through the host `delete_fn`.
+    pub deleted: usize,
+    pub failed: Vec<GcFailure>,
+}
+
+/// Bumped whenever a field of `GcReport` is removed or changes meaning.
+pub const GC_REPORT_VERSION: u32 = 1;
+
+/// Finds the blocks that become garbage once `drop_roots` are forgotten and
+/// only `keep_roots` stay in use.
+///
+/// The host store cannot be enumerated, so candidates are whatever is
+/// reachable from the dropped roots. Each dead CID is passed to `on_dead` as
+/// soon as it is known, and deleted when `delete` is set and the host
+/// provided a `delete_fn`.
+pub fn collect_garbage<F>(
+    store: &BridgedStore,
+    keep_roots: &[Cid],
+    drop_roots: &[Cid],
+    delete: bool,
+    mut on_dead: F,
+) -> Result<GcReport, String>
+where
+    F: FnMut(&Cid),
+{
+    if delete && !store.supports_delete() {
+        return Err(DELETE_UNSUPPORTED.into());
+    }
+    let live = walk_dag(store, keep_roots, &HashSet::new(), false, |_, _| Ok(()))?;
+    trace!("gc: {} live blocks", live.len());
+
+    let mut report = GcReport {
+        version: GC_REPORT_VERSION,
+        ..Default::default()
+    };
+    let mut dead: Vec<Cid> = Vec::new();
+    walk_dag(store, drop_roots, &live, false, |cid, _| {
+        on_dead(cid);
+        dead.push(*cid);
+        Ok(())
+    })?;
+    trace!("gc: {} dead blocks", dead.len());
+
+    for cid in dead.iter() {
+        // The host may delete reported blocks itself once this returns.
+        store.forget_block(&cid.to_bytes());
+        if delete {
+            match store.delete_block(&cid.to_bytes()) {
+                Ok(_) => report.deleted += 1,
+                Err(e) => report.failed.push(GcFailure {
+                    cid: cid.to_string(),
+                    error: e.to_string(),
+                }),
+            }
+        }
+        report.dead.push(cid.to_string());
+    }
+    Ok(report)
+}
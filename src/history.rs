@@ -0,0 +1,299 @@
+use std::rc::Rc;
+
+use libipld::Cid;
+use log::trace;
+use serde::Serialize;
+use wnfs::common::Metadata;
+use wnfs::private::{PrivateDirectory, PrivateForest, PrivateNode, PrivateNodeOnPathHistory};
+use wnfsutils::private_forest::PrivateDirectoryHelper;
+
+use crate::attrs::{symlink_target, user_meta};
+use crate::blockstore::BridgedStore;
+use crate::ios::load_helper;
+use crate::listing::{child_path, stat_path, EntryKind};
+use crate::node::{
+    copy_file_content, get_node, info, make_dir_with_time, node_metadata, store_root,
+    update_metadata,
+};
+use crate::runtime::block_on;
+
+/// Bumped whenever a field of `RevisionsOutput` is removed or changes
+/// meaning.
+pub const REVISIONS_OUTPUT_VERSION: u32 = 1;
+
+/// How many ratchet steps `PrivateNodeOnPathHistory` may search between two
+/// revisions before giving up.
+const DISCREPANCY_BUDGET: usize = 1_000_000;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Revision {
+    /// 1 for the oldest revision listed. Pass it with the same `since` root
+    /// to `read_revision_native` and `restore_revision_native`.
+    pub revision: u32,
+    pub kind: EntryKind,
+    pub created: Option<i64>,
+    pub modified: Option<i64>,
+    /// Only set when content info was requested.
+    pub size: Option<u64>,
+    /// CID of the node in that revision.
+    pub cid: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct RevisionsOutput {
+    pub version: u32,
+    pub revisions: Vec<Revision>,
+}
+
+fn timestamp(metadata: &Metadata) -> i64 {
+    metadata.get_modified().map_or(0, |t| t.timestamp())
+}
+
+/// Revisions of the node at `path_segments` in the forest at `cid`, oldest
+/// first, following its previous-revision links back to the revision it had
+/// in the forest at `since`, along with a helper on the forest at `cid`.
+fn node_revisions(
+    store: BridgedStore,
+    cid: Cid,
+    since: Cid,
+    path_segments: &[String],
+) -> Result<(PrivateDirectoryHelper<'static>, Vec<PrivateNode>), String> {
+    let past = load_helper(store.to_owned(), since)?;
+    let current = load_helper(store, cid)?;
+    let revisions = block_on(async {
+        let mut history = PrivateNodeOnPathHistory::of(
+            Rc::clone(&current.root_dir),
+            Rc::clone(&past.root_dir),
+            DISCREPANCY_BUDGET,
+            path_segments,
+            true,
+            Rc::clone(&current.forest),
+            &current.store,
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+        let mut revisions = vec![get_node(&current, path_segments).await?];
+        while let Some(node) = history
+            .get_previous(&current.store)
+            .await
+            .map_err(|e| e.to_string())?
+        {
+            revisions.push(node);
+        }
+        revisions.reverse();
+        Ok::<Vec<PrivateNode>, String>(revisions)
+    })?;
+    Ok((current, revisions))
+}
+
+fn nth_revision(
+    store: BridgedStore,
+    cid: Cid,
+    since: Cid,
+    path_segments: &[String],
+    revision: u32,
+) -> Result<(PrivateDirectoryHelper<'static>, PrivateNode), String> {
+    let (current, mut revisions) = node_revisions(store, cid, since, path_segments)?;
+    if revision == 0 || revision as usize > revisions.len() {
+        return Err(format!(
+            "revision {} of {} not found",
+            revision,
+            path_segments.join("/")
+        ));
+    }
+    let node = revisions.swap_remove(revision as usize - 1);
+    Ok((current, node))
+}
+
+/// Lists the revisions of `path_segments` in the forest at `cid`, oldest
+/// first, from the node's own previous-revision links. wnfs ratchets only
+/// move forward, so the walk needs a lower bound: `since` is an older root
+/// of the same drive in which the path already exists, and the revision the
+/// path had there is the first one listed. Every write to the node is a
+/// revision, whatever its timestamp; changes elsewhere in the drive are not.
+///
+/// Symlinks are not nodes and have no revisions of their own; they come
+/// back with their directory.
+pub fn list_revisions(
+    store: BridgedStore,
+    cid: Cid,
+    since: Cid,
+    path_segments: &[String],
+    with_content: bool,
+) -> Result<RevisionsOutput, String> {
+    let (current, nodes) = node_revisions(store, cid, since, path_segments)?;
+    let revisions = block_on(async {
+        let mut revisions = Vec::new();
+        for (i, node) in nodes.iter().enumerate() {
+            let info = info(node, with_content, &current.forest, &current.store).await?;
+            let metadata = node_metadata(node);
+            revisions.push(Revision {
+                revision: i as u32 + 1,
+                kind: info.kind,
+                created: metadata.get_created().map(|t| t.timestamp()),
+                modified: metadata.get_modified().map(|t| t.timestamp()),
+                size: info.size,
+                cid: info.cid.map(|cid| cid.to_string()),
+            });
+        }
+        Ok::<Vec<Revision>, String>(revisions)
+    })?;
+    trace!(
+        "found {} revisions of {:?} since {}",
+        revisions.len(),
+        path_segments,
+        since
+    );
+    Ok(RevisionsOutput {
+        version: REVISIONS_OUTPUT_VERSION,
+        revisions,
+    })
+}
+
+/// Content of the file at `path_segments` as of `revision`, numbered as by
+/// `list_revisions` with the same `since`.
+pub fn read_revision(
+    store: BridgedStore,
+    cid: Cid,
+    since: Cid,
+    path_segments: &[String],
+    revision: u32,
+) -> Result<Vec<u8>, String> {
+    let (current, node) = nth_revision(store, cid, since, path_segments, revision)?;
+    match node {
+        PrivateNode::File(file) => {
+            block_on(file.get_content(&current.forest, &current.store)).map_err(|e| e.to_string())
+        }
+        PrivateNode::Dir(_) => Err(format!("{} is a directory", path_segments.join("/"))),
+    }
+}
+
+/// Gives the node at `path_segments` the metadata `node` had in its
+/// revision, with its times, user metadata and symlink target. Only the
+/// root in memory changes.
+fn restore_meta(
+    helper: &mut PrivateDirectoryHelper,
+    path_segments: &[String],
+    node: &PrivateNode,
+) -> Result<(), String> {
+    let revision_metadata = node_metadata(node).to_owned();
+    update_metadata(helper, path_segments, |metadata| {
+        *metadata = revision_metadata;
+        Ok(())
+    })
+}
+
+/// Whether `metadata` holds more than wnfs' own times, which a plain
+/// write or mkdir would not bring back.
+fn has_attributes(metadata: &Metadata) -> Result<bool, String> {
+    Ok(symlink_target(metadata).is_some() || !user_meta(metadata)?.is_empty())
+}
+
+/// Copies the tree below `dir`, as it was in that revision of
+/// `source_forest`, to `target_path`, which must already exist. Children are
+/// looked up without searching for their latest revision and file content
+/// is streamed block by block. Symlinks and user metadata live in node
+/// metadata and are put back once every entry exists. Only the root in
+/// memory changes.
+fn copy_revision_tree(
+    helper: &mut PrivateDirectoryHelper,
+    source_forest: &Rc<PrivateForest>,
+    dir: Rc<PrivateDirectory>,
+    target_path: &[String],
+) -> Result<(), String> {
+    let mut with_meta = Vec::new();
+    let mut pending = vec![(dir, target_path.to_vec())];
+    while let Some((dir, dir_path)) = pending.pop() {
+        let children = block_on(async {
+            let (forest, store) = (source_forest, &helper.store);
+            let mut children = Vec::new();
+            for (name, _) in dir
+                .ls(&[], false, forest, store)
+                .await
+                .map_err(|e| e.to_string())?
+            {
+                let child = dir
+                    .lookup_node(&name, false, forest, store)
+                    .await
+                    .map_err(|e| e.to_string())?
+                    .ok_or_else(|| format!("{} not found", name))?;
+                children.push((name, child));
+            }
+            Ok::<Vec<(String, PrivateNode)>, String>(children)
+        })?;
+        for (name, child) in children {
+            let child_path = child_path(&dir_path, &name);
+            if has_attributes(node_metadata(&child))? {
+                with_meta.push((child_path.to_owned(), child.to_owned()));
+            }
+            match child {
+                PrivateNode::Dir(child_dir) => {
+                    make_dir_with_time(helper, &child_path, timestamp(child_dir.get_metadata()))?;
+                    pending.push((child_dir, child_path));
+                }
+                PrivateNode::File(file) => copy_file_content(
+                    helper,
+                    &child_path,
+                    &file,
+                    source_forest,
+                    timestamp(file.get_metadata()),
+                )?,
+            }
+        }
+    }
+    for (path, node) in with_meta.iter() {
+        restore_meta(helper, path, node)?;
+    }
+    Ok(())
+}
+
+/// Makes `revision` of `path_segments`, numbered as by `list_revisions`
+/// with the same `since`, its current content in the forest at `cid` and
+/// returns the new root CID. Files keep the revision's modification time.
+/// Directories are restored as a whole, dropping entries that did not exist
+/// in the revision. Symlinks and user metadata come back as they were in
+/// the revision.
+pub fn restore_revision(
+    store: BridgedStore,
+    cid: Cid,
+    since: Cid,
+    path_segments: &[String],
+    revision: u32,
+) -> Result<Cid, String> {
+    let (mut current, node) = nth_revision(store, cid, since, path_segments, revision)?;
+    let current = &mut current;
+    // The revision's blocks stay reachable from the forest as loaded, while
+    // writes below move `current.forest` on.
+    let source_forest = Rc::clone(&current.forest);
+    match node.to_owned() {
+        PrivateNode::Dir(dir) => {
+            if stat_path(current, path_segments, false)?.exists {
+                current.synced_rm(path_segments)?;
+            }
+            make_dir_with_time(current, path_segments, timestamp(dir.get_metadata()))?;
+            copy_revision_tree(current, &source_forest, dir, path_segments)?;
+        }
+        PrivateNode::File(file) => {
+            let kind = stat_path(current, path_segments, false)?.kind;
+            if matches!(kind, Some(EntryKind::Dir) | Some(EntryKind::Symlink)) {
+                current.synced_rm(path_segments)?;
+            }
+            copy_file_content(
+                current,
+                path_segments,
+                &file,
+                &source_forest,
+                timestamp(file.get_metadata()),
+            )?;
+        }
+    }
+    restore_meta(current, path_segments, &node)?;
+    let new_cid = store_root(current)?;
+    trace!(
+        "restored revision {} of {:?} into cid={}",
+        revision,
+        path_segments,
+        new_cid
+    );
+    Ok(new_cid)
+}
@@ -0,0 +1,276 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::BufReader;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use libipld::Cid;
+use log::trace;
+use regex::Regex;
+use serde::Serialize;
+use wnfsutils::private_forest::PrivateDirectoryHelper;
+
+use crate::attrs::write_file_streaming;
+use crate::error::{classify_error, WnfsErrorCode};
+use crate::export::{is_unsafe_name, ExistingFiles};
+use crate::find::{compile_pattern, PatternSyntax};
+use crate::listing::{child_path, DirectoryReader};
+use crate::node::{make_dir_with_time, store_root};
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportStatus {
+    Imported,
+    /// Already present in the drive, or a local symlink.
+    Skipped,
+    Failed,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ImportEntry {
+    /// `/` separated path relative to the imported directory.
+    pub path: String,
+    pub status: ImportStatus,
+    pub code: Option<WnfsErrorCode>,
+    pub message: Option<String>,
+}
+
+/// Bumped whenever a field of `ImportOutput` is removed or changes meaning.
+pub const IMPORT_OUTPUT_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+pub struct ImportOutput {
+    pub version: u32,
+    /// Root CID with every imported file.
+    pub cid: String,
+    /// Every local file that was not excluded, in walk order.
+    pub entries: Vec<ImportEntry>,
+}
+
+/// Glob filters on local names. Excludes apply to files and directories,
+/// includes only to files; with no includes every file is taken.
+pub struct ImportFilter {
+    include: Vec<Regex>,
+    exclude: Vec<Regex>,
+}
+
+impl ImportFilter {
+    pub fn new(include: &[String], exclude: &[String]) -> Result<ImportFilter, String> {
+        let compile = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|pattern| compile_pattern(pattern, PatternSyntax::Glob, false))
+                .collect::<Result<Vec<Regex>, String>>()
+        };
+        Ok(ImportFilter {
+            include: compile(include)?,
+            exclude: compile(exclude)?,
+        })
+    }
+
+    fn excludes(&self, name: &str) -> bool {
+        self.exclude.iter().any(|pattern| pattern.is_match(name))
+    }
+
+    fn includes_file(&self, name: &str) -> bool {
+        self.include.is_empty() || self.include.iter().any(|pattern| pattern.is_match(name))
+    }
+}
+
+fn local_modified(metadata: &fs::Metadata) -> i64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |modified| modified.as_secs() as i64)
+}
+
+struct Importer<'h> {
+    helper: &'h mut PrivateDirectoryHelper<'static>,
+    filter: &'h ImportFilter,
+    existing: ExistingFiles,
+    /// Whether the root changed in memory since it was last stored.
+    changed: bool,
+    entries: Vec<ImportEntry>,
+}
+
+impl<'h> Importer<'h> {
+    fn record(&mut self, path: &str, result: Result<ImportStatus, String>) {
+        let entry = match result {
+            Ok(status) => ImportEntry {
+                path: path.to_string(),
+                status,
+                code: None,
+                message: None,
+            },
+            Err(msg) => ImportEntry {
+                path: path.to_string(),
+                status: ImportStatus::Failed,
+                code: Some(classify_error(&msg)),
+                message: Some(msg),
+            },
+        };
+        self.entries.push(entry);
+    }
+
+    /// Names already in `path_segments`, listed once per directory so that
+    /// skipping existing files does not look each of them up. Only needed
+    /// with `ExistingFiles::Skip`.
+    fn existing_names(&mut self, path_segments: &[String]) -> Result<BTreeSet<String>, String> {
+        if self.existing != ExistingFiles::Skip {
+            return Ok(BTreeSet::new());
+        }
+        match self.helper.ls_files(path_segments) {
+            Ok(ls_result) => Ok(ls_result.into_iter().map(|(name, _)| name).collect()),
+            Err(msg) if classify_error(&msg) == WnfsErrorCode::NotFound => Ok(BTreeSet::new()),
+            Err(msg) => Err(msg),
+        }
+    }
+
+    /// Imports the content of `local_dir` into `path_segments`, which must
+    /// already exist. `relative` is the report path of `local_dir`.
+    fn import_dir(&mut self, local_dir: &Path, path_segments: &[String], relative: &str) {
+        let mut children = match fs::read_dir(local_dir) {
+            Ok(read_dir) => read_dir
+                .filter_map(|child| child.ok())
+                .map(|child| child.path())
+                .collect::<Vec<_>>(),
+            Err(e) => return self.record(relative, Err(e.to_string())),
+        };
+        children.sort();
+        let existing = match self.existing_names(path_segments) {
+            Ok(existing) => existing,
+            Err(msg) => return self.record(relative, Err(msg)),
+        };
+        for local_path in children {
+            let name = match local_path.file_name().and_then(|name| name.to_str()) {
+                Some(name) => name.to_string(),
+                None => {
+                    let lossy = local_path.to_string_lossy().to_string();
+                    self.record(&lossy, Err("name is not valid UTF-8".to_string()));
+                    continue;
+                }
+            };
+            let path = if relative.is_empty() {
+                name.to_owned()
+            } else {
+                format!("{}/{}", relative, name)
+            };
+            if self.filter.excludes(&name) {
+                continue;
+            }
+            let metadata = match fs::symlink_metadata(&local_path) {
+                Ok(metadata) => metadata,
+                Err(e) => {
+                    self.record(&path, Err(e.to_string()));
+                    continue;
+                }
+            };
+            if metadata.is_file() && !self.filter.includes_file(&name) {
+                continue;
+            }
+            if is_unsafe_name(&name) {
+                self.record(&path, Err(format!("{} is not a valid name", name)));
+                continue;
+            }
+            let entry_path = child_path(path_segments, &name);
+            if metadata.is_dir() {
+                match make_dir_with_time(self.helper, &entry_path, local_modified(&metadata)) {
+                    Ok(()) => {
+                        self.changed = true;
+                        self.import_dir(&local_path, &entry_path, &path);
+                    }
+                    Err(msg) => self.record(&path, Err(msg)),
+                }
+            } else if metadata.is_file() {
+                let result = if existing.contains(&name) {
+                    Ok(ImportStatus::Skipped)
+                } else {
+                    self.import_file(&local_path, &entry_path, &metadata)
+                };
+                self.record(&path, result);
+            } else {
+                self.record(&path, Ok(ImportStatus::Skipped));
+            }
+        }
+    }
+
+    /// Streams the local file into the drive, keeping its modification time.
+    fn import_file(
+        &mut self,
+        local_path: &Path,
+        path_segments: &[String],
+        metadata: &fs::Metadata,
+    ) -> Result<ImportStatus, String> {
+        let file = fs::File::open(local_path).map_err(|e| e.to_string())?;
+        // A failed write can leave the file half written in memory.
+        self.changed = true;
+        write_file_streaming(
+            self.helper,
+            path_segments,
+            BufReader::new(file),
+            local_modified(metadata),
+        )?;
+        Ok(ImportStatus::Imported)
+    }
+}
+
+/// Copies the files below `local_dir` into `path_segments`, created if
+/// needed, in one loaded forest, and returns the final root with a report
+/// for every file.
+///
+/// Files are streamed from disk rather than read whole, and the root is
+/// stored once at the end instead of after every file. Files and
+/// directories keep their local modification time. Local symlinks are
+/// skipped rather than followed. A file that fails is reported and the
+/// import goes on; only a missing `local_dir`, a target that cannot be
+/// created or a root that cannot be stored fails the whole call, leaving
+/// the root unchanged.
+pub fn import_dir(
+    helper: &mut PrivateDirectoryHelper<'static>,
+    cid: Cid,
+    local_dir: &Path,
+    path_segments: &[String],
+    filter: &ImportFilter,
+    existing: ExistingFiles,
+) -> Result<ImportOutput, String> {
+    if !fs::metadata(local_dir)
+        .map_err(|e| format!("{}: {}", local_dir.to_string_lossy(), e))?
+        .is_dir()
+    {
+        return Err(format!(
+            "{} is not a directory",
+            local_dir.to_string_lossy()
+        ));
+    }
+    let cid = if path_segments.is_empty() {
+        cid
+    } else {
+        helper.synced_mkdir(path_segments)?
+    };
+    let mut importer = Importer {
+        helper,
+        filter,
+        existing,
+        changed: false,
+        entries: Vec::new(),
+    };
+    importer.import_dir(local_dir, path_segments, "");
+    let cid = if importer.changed {
+        store_root(importer.helper)?
+    } else {
+        cid
+    };
+    trace!(
+        "imported {:?} into {:?}: {} entries, cid={}",
+        local_dir,
+        path_segments,
+        importer.entries.len(),
+        cid
+    );
+    Ok(ImportOutput {
+        version: IMPORT_OUTPUT_VERSION,
+        cid: cid.to_string(),
+        entries: importer.entries,
+    })
+}
@@ -0,0 +1,521 @@
+//! Non-blocking counterparts of the `ios` entry points.
+//!
+//! Each `*_native_async` function copies its arguments, runs the matching
+//! `*_native` function on the shared runtime and reports the very same
+//! `RustResult` through `callback(userdata, result)`. The host block store
+//! callbacks may be invoked from several runtime threads at once.
+//!
+//! Only the calls that take a root CID and return a single result have an
+//! async variant: the single-path file and directory calls, `stat`,
+//! `ls_with_meta`, `find` and `transaction`. Sessions and file writers do
+//! not, since each holds state that must only be used from one thread at a
+//! time; hosts drive them from a queue of their own. Neither do the
+//! tree-wide calls such as diff, merge, gc, key rotation and the import and
+//! export calls, nor `tree_native`, whose `on_entry` is called while it runs.
+use std::mem::{size_of, zeroed};
+use std::ptr;
+
+use libc::c_void;
+
+use crate::blockstore_interface::{BlockStoreExtensions, BlockStoreInterface};
+use crate::c_types::{OutputFormat, OwnedRustBytes, OwnedRustString, RustBytes, RustString};
+use crate::find::FindOptions;
+use crate::ios::*;
+use crate::runtime::{
+    spawn_with_callback, AssertSend, BytesCallback, StringCallback, VoidCallback,
+};
+use crate::transaction::{ops_from_raw, TransactionOp, TransactionOpKind};
+
+/// Copies the first `size` bytes of the host's extensions, null meaning
+/// none, so they outlive the call. The copy keeps the host's `size` and is
+/// only checked when the `*_native` function reads it, which reports a bad
+/// layout the same way it would have synchronously.
+unsafe fn copy_extensions(extensions: *const BlockStoreExtensions) -> Option<BlockStoreExtensions> {
+    if extensions.is_null() {
+        return None;
+    }
+    let size = ptr::addr_of!((*extensions).size).read();
+    // Every field is an integer or a nullable function pointer, for which
+    // zero is a valid value.
+    let mut copy: BlockStoreExtensions = zeroed();
+    ptr::copy_nonoverlapping(
+        extensions as *const u8,
+        &mut copy as *mut BlockStoreExtensions as *mut u8,
+        size.min(size_of::<BlockStoreExtensions>()),
+    );
+    copy.size = size;
+    Some(copy)
+}
+
+fn extensions_ptr(extensions: &Option<BlockStoreExtensions>) -> *const BlockStoreExtensions {
+    extensions
+        .as_ref()
+        .map_or(ptr::null(), |extensions| extensions as *const _)
+}
+
+#[no_mangle]
+pub extern "C" fn load_with_wnfs_key_native_async(
+    block_store_interface: BlockStoreInterface,
+    wnfs_key: RustBytes,
+    cid: RustString,
+    callback: VoidCallback,
+    userdata: *mut c_void,
+) {
+    let args = AssertSend::new((
+        block_store_interface,
+        OwnedRustBytes::copy_from(&wnfs_key),
+        OwnedRustString::copy_from(&cid),
+    ));
+    spawn_with_callback(callback, userdata, move || {
+        let (block_store_interface, wnfs_key, cid) = args.into_inner();
+        load_with_wnfs_key_native(block_store_interface, wnfs_key.get(), cid.get())
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn init_native_async(
+    block_store_interface: BlockStoreInterface,
+    wnfs_key: RustBytes,
+    callback: StringCallback,
+    userdata: *mut c_void,
+) {
+    let args = AssertSend::new((block_store_interface, OwnedRustBytes::copy_from(&wnfs_key)));
+    spawn_with_callback(callback, userdata, move || {
+        let (block_store_interface, wnfs_key) = args.into_inner();
+        init_native(block_store_interface, wnfs_key.get())
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn write_file_from_path_native_async(
+    block_store_interface: BlockStoreInterface,
+    cid: RustString,
+    path_segments: RustString,
+    _filename: RustString,
+    callback: StringCallback,
+    userdata: *mut c_void,
+) {
+    let args = AssertSend::new((
+        block_store_interface,
+        OwnedRustString::copy_from(&cid),
+        OwnedRustString::copy_from(&path_segments),
+        OwnedRustString::copy_from(&_filename),
+    ));
+    spawn_with_callback(callback, userdata, move || {
+        let (block_store_interface, cid, path_segments, _filename) = args.into_inner();
+        write_file_from_path_native(
+            block_store_interface,
+            cid.get(),
+            path_segments.get(),
+            _filename.get(),
+        )
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn read_filestream_to_path_native_async(
+    block_store_interface: BlockStoreInterface,
+    cid: RustString,
+    path_segments: RustString,
+    _filename: RustString,
+    callback: StringCallback,
+    userdata: *mut c_void,
+) {
+    let args = AssertSend::new((
+        block_store_interface,
+        OwnedRustString::copy_from(&cid),
+        OwnedRustString::copy_from(&path_segments),
+        OwnedRustString::copy_from(&_filename),
+    ));
+    spawn_with_callback(callback, userdata, move || {
+        let (block_store_interface, cid, path_segments, _filename) = args.into_inner();
+        read_filestream_to_path_native(
+            block_store_interface,
+            cid.get(),
+            path_segments.get(),
+            _filename.get(),
+        )
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn read_file_to_path_native_async(
+    block_store_interface: BlockStoreInterface,
+    cid: RustString,
+    path_segments: RustString,
+    _filename: RustString,
+    callback: StringCallback,
+    userdata: *mut c_void,
+) {
+    let args = AssertSend::new((
+        block_store_interface,
+        OwnedRustString::copy_from(&cid),
+        OwnedRustString::copy_from(&path_segments),
+        OwnedRustString::copy_from(&_filename),
+    ));
+    spawn_with_callback(callback, userdata, move || {
+        let (block_store_interface, cid, path_segments, _filename) = args.into_inner();
+        read_file_to_path_native(
+            block_store_interface,
+            cid.get(),
+            path_segments.get(),
+            _filename.get(),
+        )
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn write_file_native_async(
+    block_store_interface: BlockStoreInterface,
+    cid: RustString,
+    path_segments: RustString,
+    _content: RustBytes,
+    callback: StringCallback,
+    userdata: *mut c_void,
+) {
+    let args = AssertSend::new((
+        block_store_interface,
+        OwnedRustString::copy_from(&cid),
+        OwnedRustString::copy_from(&path_segments),
+        OwnedRustBytes::copy_from(&_content),
+    ));
+    spawn_with_callback(callback, userdata, move || {
+        let (block_store_interface, cid, path_segments, _content) = args.into_inner();
+        write_file_native(
+            block_store_interface,
+            cid.get(),
+            path_segments.get(),
+            _content.get(),
+        )
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn read_file_native_async(
+    block_store_interface: BlockStoreInterface,
+    cid: RustString,
+    path_segments: RustString,
+    callback: BytesCallback,
+    userdata: *mut c_void,
+) {
+    let args = AssertSend::new((
+        block_store_interface,
+        OwnedRustString::copy_from(&cid),
+        OwnedRustString::copy_from(&path_segments),
+    ));
+    spawn_with_callback(callback, userdata, move || {
+        let (block_store_interface, cid, path_segments) = args.into_inner();
+        read_file_native(block_store_interface, cid.get(), path_segments.get())
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn read_file_range_native_async(
+    block_store_interface: BlockStoreInterface,
+    cid: RustString,
+    path_segments: RustString,
+    offset: u64,
+    length: u64,
+    callback: BytesCallback,
+    userdata: *mut c_void,
+) {
+    let args = AssertSend::new((
+        block_store_interface,
+        OwnedRustString::copy_from(&cid),
+        OwnedRustString::copy_from(&path_segments),
+        offset,
+        length,
+    ));
+    spawn_with_callback(callback, userdata, move || {
+        let (block_store_interface, cid, path_segments, offset, length) = args.into_inner();
+        read_file_range_native(
+            block_store_interface,
+            cid.get(),
+            path_segments.get(),
+            offset,
+            length,
+        )
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn mkdir_native_async(
+    block_store_interface: BlockStoreInterface,
+    cid: RustString,
+    path_segments: RustString,
+    callback: StringCallback,
+    userdata: *mut c_void,
+) {
+    let args = AssertSend::new((
+        block_store_interface,
+        OwnedRustString::copy_from(&cid),
+        OwnedRustString::copy_from(&path_segments),
+    ));
+    spawn_with_callback(callback, userdata, move || {
+        let (block_store_interface, cid, path_segments) = args.into_inner();
+        mkdir_native(block_store_interface, cid.get(), path_segments.get())
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn mv_native_async(
+    block_store_interface: BlockStoreInterface,
+    cid: RustString,
+    source_path_segments: RustString,
+    target_path_segments: RustString,
+    callback: StringCallback,
+    userdata: *mut c_void,
+) {
+    let args = AssertSend::new((
+        block_store_interface,
+        OwnedRustString::copy_from(&cid),
+        OwnedRustString::copy_from(&source_path_segments),
+        OwnedRustString::copy_from(&target_path_segments),
+    ));
+    spawn_with_callback(callback, userdata, move || {
+        let (block_store_interface, cid, source_path_segments, target_path_segments) =
+            args.into_inner();
+        mv_native(
+            block_store_interface,
+            cid.get(),
+            source_path_segments.get(),
+            target_path_segments.get(),
+        )
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn cp_native_async(
+    block_store_interface: BlockStoreInterface,
+    cid: RustString,
+    source_path_segments: RustString,
+    target_path_segments: RustString,
+    callback: StringCallback,
+    userdata: *mut c_void,
+) {
+    let args = AssertSend::new((
+        block_store_interface,
+        OwnedRustString::copy_from(&cid),
+        OwnedRustString::copy_from(&source_path_segments),
+        OwnedRustString::copy_from(&target_path_segments),
+    ));
+    spawn_with_callback(callback, userdata, move || {
+        let (block_store_interface, cid, source_path_segments, target_path_segments) =
+            args.into_inner();
+        cp_native(
+            block_store_interface,
+            cid.get(),
+            source_path_segments.get(),
+            target_path_segments.get(),
+        )
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn rm_native_async(
+    block_store_interface: BlockStoreInterface,
+    cid: RustString,
+    path_segments: RustString,
+    callback: StringCallback,
+    userdata: *mut c_void,
+) {
+    let args = AssertSend::new((
+        block_store_interface,
+        OwnedRustString::copy_from(&cid),
+        OwnedRustString::copy_from(&path_segments),
+    ));
+    spawn_with_callback(callback, userdata, move || {
+        let (block_store_interface, cid, path_segments) = args.into_inner();
+        rm_native(block_store_interface, cid.get(), path_segments.get())
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn ls_native_async(
+    block_store_interface: BlockStoreInterface,
+    cid: RustString,
+    path_segments: RustString,
+    callback: BytesCallback,
+    userdata: *mut c_void,
+) {
+    let args = AssertSend::new((
+        block_store_interface,
+        OwnedRustString::copy_from(&cid),
+        OwnedRustString::copy_from(&path_segments),
+    ));
+    spawn_with_callback(callback, userdata, move || {
+        let (block_store_interface, cid, path_segments) = args.into_inner();
+        ls_native(block_store_interface, cid.get(), path_segments.get())
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn ls_with_format_native_async(
+    block_store_interface: BlockStoreInterface,
+    cid: RustString,
+    path_segments: RustString,
+    format: OutputFormat,
+    callback: BytesCallback,
+    userdata: *mut c_void,
+) {
+    let args = AssertSend::new((
+        block_store_interface,
+        OwnedRustString::copy_from(&cid),
+        OwnedRustString::copy_from(&path_segments),
+        format,
+    ));
+    spawn_with_callback(callback, userdata, move || {
+        let (block_store_interface, cid, path_segments, format) = args.into_inner();
+        ls_with_format_native(
+            block_store_interface,
+            cid.get(),
+            path_segments.get(),
+            format,
+        )
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn stat_native_async(
+    block_store_interface: BlockStoreInterface,
+    cid: RustString,
+    path_segments: RustString,
+    format: OutputFormat,
+    with_content: bool,
+    callback: BytesCallback,
+    userdata: *mut c_void,
+) {
+    let args = AssertSend::new((
+        block_store_interface,
+        OwnedRustString::copy_from(&cid),
+        OwnedRustString::copy_from(&path_segments),
+        format,
+        with_content,
+    ));
+    spawn_with_callback(callback, userdata, move || {
+        let (block_store_interface, cid, path_segments, format, with_content) = args.into_inner();
+        stat_native(
+            block_store_interface,
+            cid.get(),
+            path_segments.get(),
+            format,
+            with_content,
+        )
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn ls_with_meta_native_async(
+    block_store_interface: BlockStoreInterface,
+    cid: RustString,
+    path_segments: RustString,
+    format: OutputFormat,
+    callback: BytesCallback,
+    userdata: *mut c_void,
+) {
+    let args = AssertSend::new((
+        block_store_interface,
+        OwnedRustString::copy_from(&cid),
+        OwnedRustString::copy_from(&path_segments),
+        format,
+    ));
+    spawn_with_callback(callback, userdata, move || {
+        let (block_store_interface, cid, path_segments, format) = args.into_inner();
+        ls_with_meta_native(
+            block_store_interface,
+            cid.get(),
+            path_segments.get(),
+            format,
+        )
+    });
+}
+
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn find_native_async(
+    block_store_interface: BlockStoreInterface,
+    extensions: *const BlockStoreExtensions,
+    cid: RustString,
+    path_segments: RustString,
+    pattern: RustString,
+    options: FindOptions,
+    format: OutputFormat,
+    callback: BytesCallback,
+    userdata: *mut c_void,
+) {
+    let args = AssertSend::new((
+        block_store_interface,
+        copy_extensions(extensions),
+        OwnedRustString::copy_from(&cid),
+        OwnedRustString::copy_from(&path_segments),
+        OwnedRustString::copy_from(&pattern),
+        options,
+        format,
+    ));
+    spawn_with_callback(callback, userdata, move || {
+        let (block_store_interface, extensions, cid, path_segments, pattern, options, format) =
+            args.into_inner();
+        find_native(
+            block_store_interface,
+            extensions_ptr(&extensions),
+            cid.get(),
+            path_segments.get(),
+            pattern.get(),
+            options,
+            format,
+        )
+    });
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn transaction_native_async(
+    block_store_interface: BlockStoreInterface,
+    extensions: *const BlockStoreExtensions,
+    cid: RustString,
+    ops: *const TransactionOp,
+    ops_len: usize,
+    callback: StringCallback,
+    userdata: *mut c_void,
+) {
+    let owned_ops: Vec<(
+        TransactionOpKind,
+        OwnedRustString,
+        OwnedRustString,
+        OwnedRustBytes,
+    )> = ops_from_raw(ops, ops_len)
+        .iter()
+        .map(|op| {
+            (
+                op.kind,
+                OwnedRustString::copy_from(&op.path_segments),
+                OwnedRustString::copy_from(&op.target),
+                OwnedRustBytes::copy_from(&op.content),
+            )
+        })
+        .collect();
+    let args = AssertSend::new((
+        block_store_interface,
+        copy_extensions(extensions),
+        OwnedRustString::copy_from(&cid),
+        owned_ops,
+    ));
+    spawn_with_callback(callback, userdata, move || {
+        let (block_store_interface, extensions, cid, owned_ops) = args.into_inner();
+        let ops: Vec<TransactionOp> = owned_ops
+            .iter()
+            .map(|(kind, path_segments, target, content)| TransactionOp {
+                kind: *kind,
+                path_segments: path_segments.get(),
+                target: target.get(),
+                content: content.get(),
+            })
+            .collect();
+        transaction_native(
+            block_store_interface,
+            extensions_ptr(&extensions),
+            cid.get(),
+            ops.as_ptr(),
+            ops.len(),
+        )
+    });
+}
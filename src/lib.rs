@@ -1,21 +1,123 @@
+pub mod attrs;
+pub mod block_cache;
 pub mod blockstore;
 pub mod blockstore_interface;
 pub mod c_types;
+pub mod car;
+pub mod dag;
+pub mod diff;
+pub mod error;
+pub mod export;
+pub mod find;
+pub mod gc;
+pub mod history;
+pub mod import;
+pub mod ios_async;
+pub mod listing;
+pub mod merge;
+pub mod node;
+pub mod public_directory;
+pub mod range;
+pub mod rotation;
+pub mod runtime;
+pub mod session;
+pub mod share;
 pub mod tests;
+pub mod transaction;
+pub mod unixfs;
+pub mod writer;
 pub mod ios {
     extern crate libc;
+    use crate::attrs::{
+        delete_meta, get_meta, readlink, resolve_path, set_meta, symlink, unlink, write_file,
+        write_file_from_path, write_file_streaming, MetaValue,
+    };
+    use crate::block_cache::{configure_block_cache, purge_block_cache};
     use crate::blockstore::BridgedStore;
-    use crate::blockstore_interface::BlockStoreInterface;
+    use crate::blockstore_interface::{BlockStoreExtensions, BlockStoreInterface};
     use crate::c_types::{
-        prepare_ls_output, prepare_path_segments, RustBytes, RustResult, RustString, RustVoid,
+        prepare_cids, prepare_ls_output, prepare_path_segments, prepare_strings, Empty,
+        OutputFormat, OwnedRustBytes, OwnedRustString, RustBytes, RustFileWriter, RustResult,
+        RustSession, RustString, RustVoid,
+    };
+    use crate::car::{export_car, import_car, CarVersion};
+    use crate::diff::diff_roots;
+    use crate::error::{
+        classify_error, error_detail, error_detail_with_index, load_error_code, WnfsErrorCode,
     };
+    use crate::export::{export_dir, ExistingFiles};
+    use crate::find::{compile_pattern, find_entries, FindOptions};
+    use crate::gc::collect_garbage;
+    use crate::history::{list_revisions, read_revision, restore_revision};
+    use crate::import::{import_dir, ImportFilter};
+    use crate::listing::{
+        encode_output, prepare_ls_output_with_format, prepare_tree_output, stat_path,
+    };
+    use crate::merge::merge_roots;
+    use crate::node::{decode_access_key, read_shared_file, store_root};
+    use crate::public_directory::PublicDirectoryHelper;
+    use crate::range::read_file_range;
+    use crate::rotation::rotate_key;
+    use crate::session::WnfsSession;
+    use crate::share::{decode_share_key, share_subtree};
+    use crate::transaction::{apply_operations, ops_from_raw, TransactionError, TransactionOp};
+    use crate::writer::WnfsFileWriter;
+    use libc::c_void;
     use libipld::Cid;
     use log::trace;
     use std::boxed::Box;
+    use std::path::Path;
 
     use wnfsutils::blockstore::FFIFriendlyBlockStore;
     use wnfsutils::private_forest::PrivateDirectoryHelper;
 
+    fn error_result<T: Empty>(function: &str, msg: String) -> RustResult<T> {
+        let code = classify_error(&msg);
+        error_result_with_code(function, code, msg)
+    }
+
+    fn error_result_with_code<T: Empty>(
+        function: &str,
+        code: WnfsErrorCode,
+        msg: String,
+    ) -> RustResult<T> {
+        trace!("wnfsError in {}: {:?} ({:?})", function, msg, code);
+        let detail = error_detail(function, code, &msg);
+        RustResult::error_with_code(code, msg.into()).with_detail(detail)
+    }
+
+    /// `error_result` for a forest that could not be loaded, coded with
+    /// `load_error_code`.
+    fn load_error_result<T: Empty>(function: &str, msg: String) -> RustResult<T> {
+        error_result_with_code(function, load_error_code(&msg), msg)
+    }
+
+    unsafe fn bridged_store<T: Empty>(
+        block_store_interface: BlockStoreInterface,
+        extensions: *const BlockStoreExtensions,
+        fn_name: &str,
+    ) -> Result<BridgedStore, RustResult<T>> {
+        let extensions = BlockStoreExtensions::from_ptr(extensions)
+            .map_err(|msg| error_result_with_code(fn_name, WnfsErrorCode::InvalidArgument, msg))?;
+        Ok(BridgedStore::with_extensions(
+            block_store_interface,
+            extensions,
+        ))
+    }
+
+    fn transaction_error_result<T: Empty>(function: &str, err: TransactionError) -> RustResult<T> {
+        let code = classify_error(&err.message);
+        trace!(
+            "wnfsError in {} at op #{}: {:?} ({:?})",
+            function,
+            err.index,
+            err.message,
+            code
+        );
+        let detail = error_detail_with_index(function, code, &err.message, Some(err.index));
+        RustResult::error_with_code(code, err.message.into()).with_detail(detail)
+    }
+
     #[no_mangle]
     pub extern "C" fn load_with_wnfs_key_native(
         block_store_interface: BlockStoreInterface,
@@ -29,7 +131,11 @@ pub mod ios {
         let wnfs_key: Vec<u8> = wnfs_key.into();
         let cid_res: Result<Cid, String> = cid.try_into();
         if cid_res.is_err() {
-            RustResult::error(RustString::from(cid_res.err().unwrap().to_string()))
+            error_result_with_code(
+                "load_with_wnfs_key_native",
+                WnfsErrorCode::InvalidCid,
+                cid_res.err().unwrap(),
+            )
         } else {
             let cid = cid_res.unwrap();
             let helper_res =
@@ -39,8 +145,11 @@ pub mod ios {
                 RustResult::ok(RustVoid::void())
             } else {
                 let msg = helper_res.err().unwrap();
-                trace!("wnfsError in load_with_wnfs_key_native: {:?}", msg);
-                RustResult::error(msg.into())
+                error_result_with_code(
+                    "load_with_wnfs_key_native",
+                    load_error_code(&msg),
+                    msg.to_owned(),
+                )
             }
         }
     }
@@ -61,8 +170,7 @@ pub mod ios {
             RustResult::ok(RustString::from(cid))
         } else {
             let msg = helper_res.err().unwrap();
-            trace!("wnfsError in init_native: {:?}", msg.to_owned());
-            RustResult::error(msg.to_owned().into())
+            error_result("init_native", msg.to_owned())
         }
     }
 
@@ -79,7 +187,11 @@ pub mod ios {
         let block_store = &mut FFIFriendlyBlockStore::new(Box::new(store));
         let cid_res: Result<Cid, String> = cid.try_into();
         if cid_res.is_err() {
-            RustResult::error(RustString::from(cid_res.err().unwrap().to_string()))
+            error_result_with_code(
+                "write_file_from_path_native",
+                WnfsErrorCode::InvalidCid,
+                cid_res.err().unwrap(),
+            )
         } else {
             let cid = cid_res.unwrap();
             let helper_res = PrivateDirectoryHelper::synced_reload(block_store, cid);
@@ -90,8 +202,7 @@ pub mod ios {
 
                 let filename = _filename.into();
                 trace!("filename, path: {:?} -- {:?}", filename, path_segments);
-                let write_file_result =
-                    helper.synced_write_file_from_path(&path_segments, &filename);
+                let write_file_result = write_file_from_path(helper, &path_segments, &filename);
                 trace!("**********************write_file_from_path_native finished**************");
                 if write_file_result.is_ok() {
                     let cid = write_file_result.ok().unwrap();
@@ -99,16 +210,11 @@ pub mod ios {
                     return RustResult::ok(cid.into());
                 } else {
                     let msg = write_file_result.err().unwrap();
-                    trace!("wnfsError in write_file_from_path_native: {:?}", msg);
-                    RustResult::ok(msg.into())
+                    error_result("write_file_from_path_native", msg.to_owned())
                 }
             } else {
                 let msg = &mut helper_res.err().unwrap();
-                trace!(
-                    "wnfsError in write_file_from_path_native: {:?}",
-                    msg.to_owned()
-                );
-                RustResult::error(msg.to_owned().into())
+                load_error_result("write_file_from_path_native", msg.to_owned())
             }
         }
     }
@@ -126,7 +232,11 @@ pub mod ios {
         let block_store = &mut FFIFriendlyBlockStore::new(Box::new(store));
         let cid_res: Result<Cid, String> = cid.try_into();
         if cid_res.is_err() {
-            RustResult::error(RustString::from(cid_res.err().unwrap().to_string()))
+            error_result_with_code(
+                "read_filestream_to_path_native",
+                WnfsErrorCode::InvalidCid,
+                cid_res.err().unwrap(),
+            )
         } else {
             let cid = cid_res.unwrap();
             let helper_res = PrivateDirectoryHelper::synced_reload(block_store, cid);
@@ -142,19 +252,11 @@ pub mod ios {
                     RustResult::ok(filename.into())
                 } else {
                     let err = result.err().unwrap();
-                    trace!(
-                        "wnfsError occured in read_filestream_to_path_native on result: {:?}",
-                        err.to_owned()
-                    );
-                    RustResult::error(err.to_owned().into())
+                    error_result("read_filestream_to_path_native", err.to_owned())
                 }
             } else {
                 let msg = helper_res.err().unwrap();
-                trace!(
-                    "wnfsError in read_filestream_to_path_native: {:?}",
-                    msg.to_owned()
-                );
-                RustResult::error(msg.to_owned().into())
+                load_error_result("read_filestream_to_path_native", msg.to_owned())
             }
         }
     }
@@ -171,7 +273,11 @@ pub mod ios {
         let block_store = &mut FFIFriendlyBlockStore::new(Box::new(store));
         let cid_res: Result<Cid, String> = cid.try_into();
         if cid_res.is_err() {
-            RustResult::error(RustString::from(cid_res.err().unwrap().to_string()))
+            error_result_with_code(
+                "read_file_to_path_native",
+                WnfsErrorCode::InvalidCid,
+                cid_res.err().unwrap(),
+            )
         } else {
             let cid = cid_res.unwrap();
             let helper_res = PrivateDirectoryHelper::synced_reload(block_store, cid);
@@ -190,19 +296,11 @@ pub mod ios {
                     RustResult::ok(filename.into())
                 } else {
                     let err = result.err().unwrap();
-                    trace!(
-                        "wnfsError occured in read_file_to_path_native on result: {:?}",
-                        err.to_owned()
-                    );
-                    RustResult::error(err.to_owned().into())
+                    error_result("read_file_to_path_native", err.to_owned())
                 }
             } else {
                 let msg = helper_res.err().unwrap();
-                trace!(
-                    "wnfsError in read_file_to_path_native: {:?}",
-                    msg.to_owned()
-                );
-                RustResult::error(msg.to_owned().into())
+                load_error_result("read_file_to_path_native", msg.to_owned())
             }
         }
     }
@@ -218,7 +316,11 @@ pub mod ios {
         let block_store = &mut FFIFriendlyBlockStore::new(Box::new(store));
         let cid_res: Result<Cid, String> = cid.try_into();
         if cid_res.is_err() {
-            RustResult::error(RustString::from(cid_res.err().unwrap().to_string()))
+            error_result_with_code(
+                "write_file_native",
+                WnfsErrorCode::InvalidCid,
+                cid_res.err().unwrap(),
+            )
         } else {
             let cid = cid_res.unwrap();
             let helper_res = PrivateDirectoryHelper::synced_reload(block_store, cid);
@@ -227,20 +329,18 @@ pub mod ios {
                 let helper = &mut helper_res.ok().unwrap();
                 let path_segments = unsafe { prepare_path_segments(path_segments) };
                 let content: Vec<u8> = _content.into();
-                let write_file_res = helper.synced_write_file(&path_segments, content, 0);
+                let write_file_res = write_file(helper, &path_segments, content, 0);
                 trace!("**********************write_file_native finished**************");
                 if write_file_res.is_ok() {
                     let cid = write_file_res.ok().unwrap();
                     RustResult::ok(RustString::from(cid))
                 } else {
                     let msg = write_file_res.err().unwrap();
-                    trace!("wnfsError in write_file_native: {:?}", msg);
-                    RustResult::error(msg.to_owned().into())
+                    error_result("write_file_native", msg.to_owned())
                 }
             } else {
                 let msg = helper_res.err().unwrap();
-                trace!("wnfsError in write_file_native: {:?}", msg.to_owned());
-                RustResult::error(msg.to_owned().into())
+                load_error_result("write_file_native", msg.to_owned())
             }
         }
     }
@@ -255,7 +355,11 @@ pub mod ios {
         let block_store = &mut FFIFriendlyBlockStore::new(Box::new(store));
         let cid_res: Result<Cid, String> = cid.try_into();
         if cid_res.is_err() {
-            RustResult::error(RustString::from(cid_res.err().unwrap().to_string()))
+            error_result_with_code(
+                "read_file_native",
+                WnfsErrorCode::InvalidCid,
+                cid_res.err().unwrap(),
+            )
         } else {
             let cid = cid_res.unwrap();
             let helper_res = PrivateDirectoryHelper::synced_reload(block_store, cid);
@@ -268,13 +372,11 @@ pub mod ios {
                     RustResult::ok(result.unwrap().into())
                 } else {
                     let msg = result.err().unwrap();
-                    trace!("wnfsError in read_file_native: {:?}", msg);
-                    RustResult::error(msg.to_owned().into())
+                    error_result("read_file_native", msg.to_owned())
                 }
             } else {
                 let msg = helper_res.err().unwrap();
-                trace!("wnfsError in read_file_native: {:?}", msg.to_owned());
-                RustResult::error(msg.to_owned().into())
+                load_error_result("read_file_native", msg.to_owned())
             }
         }
     }
@@ -290,7 +392,11 @@ pub mod ios {
         let block_store = &mut FFIFriendlyBlockStore::new(Box::new(store));
         let cid_res: Result<Cid, String> = cid.try_into();
         if cid_res.is_err() {
-            RustResult::error(RustString::from(cid_res.err().unwrap().to_string()))
+            error_result_with_code(
+                "mkdir_native",
+                WnfsErrorCode::InvalidCid,
+                cid_res.err().unwrap(),
+            )
         } else {
             let cid = cid_res.unwrap();
             let helper_res = PrivateDirectoryHelper::synced_reload(block_store, cid);
@@ -305,13 +411,11 @@ pub mod ios {
                     RustResult::ok(cid.into())
                 } else {
                     let msg = mkdir_res.err().unwrap();
-                    trace!("wnfsError in mkdir_native: {:?}", msg.to_owned());
-                    RustResult::error(msg.to_owned().into())
+                    error_result("mkdir_native", msg.to_owned())
                 }
             } else {
                 let msg = helper_res.err().unwrap();
-                trace!("wnfsError in mkdir_native: {:?}", msg.to_owned());
-                RustResult::error(msg.to_owned().into())
+                load_error_result("mkdir_native", msg.to_owned())
             }
         }
     }
@@ -328,7 +432,11 @@ pub mod ios {
         let block_store = &mut FFIFriendlyBlockStore::new(Box::new(store));
         let cid_res: Result<Cid, String> = cid.try_into();
         if cid_res.is_err() {
-            RustResult::error(RustString::from(cid_res.err().unwrap().to_string()))
+            error_result_with_code(
+                "mv_native",
+                WnfsErrorCode::InvalidCid,
+                cid_res.err().unwrap(),
+            )
         } else {
             let cid = cid_res.unwrap();
             let helper_res = PrivateDirectoryHelper::synced_reload(block_store, cid);
@@ -344,13 +452,11 @@ pub mod ios {
                     RustResult::ok(cid.into())
                 } else {
                     let msg = result.err().unwrap();
-                    trace!("wnfsError occured in mv_native: {:?}", msg.to_owned());
-                    RustResult::error(msg.to_owned().into())
+                    error_result("mv_native", msg.to_owned())
                 }
             } else {
                 let msg = helper_res.err().unwrap();
-                trace!("wnfsError in mv_native: {:?}", msg.to_owned());
-                RustResult::error(msg.to_owned().into())
+                load_error_result("mv_native", msg.to_owned())
             }
         }
     }
@@ -367,7 +473,11 @@ pub mod ios {
         let block_store = &mut FFIFriendlyBlockStore::new(Box::new(store));
         let cid_res: Result<Cid, String> = cid.try_into();
         if cid_res.is_err() {
-            RustResult::error(RustString::from(cid_res.err().unwrap().to_string()))
+            error_result_with_code(
+                "cp_native",
+                WnfsErrorCode::InvalidCid,
+                cid_res.err().unwrap(),
+            )
         } else {
             let cid = cid_res.unwrap();
             let helper_res = PrivateDirectoryHelper::synced_reload(block_store, cid);
@@ -383,13 +493,11 @@ pub mod ios {
                     RustResult::ok(cid.into())
                 } else {
                     let msg = result.err().unwrap();
-                    trace!("wnfsError occured in cp_native: {:?}", msg.to_owned());
-                    RustResult::error(msg.to_owned().into())
+                    error_result("cp_native", msg.to_owned())
                 }
             } else {
                 let msg = helper_res.err().unwrap();
-                trace!("wnfsError in cp_native: {:?}", msg.to_owned());
-                RustResult::error(msg.to_owned().into())
+                load_error_result("cp_native", msg.to_owned())
             }
         }
     }
@@ -405,7 +513,11 @@ pub mod ios {
         let block_store = &mut FFIFriendlyBlockStore::new(Box::new(store));
         let cid_res: Result<Cid, String> = cid.try_into();
         if cid_res.is_err() {
-            RustResult::error(RustString::from(cid_res.err().unwrap().to_string()))
+            error_result_with_code(
+                "rm_native",
+                WnfsErrorCode::InvalidCid,
+                cid_res.err().unwrap(),
+            )
         } else {
             let cid = cid_res.unwrap();
             let helper_res = PrivateDirectoryHelper::synced_reload(block_store, cid);
@@ -420,13 +532,11 @@ pub mod ios {
                     RustResult::ok(cid.into())
                 } else {
                     let msg = rm_res.err().unwrap();
-                    trace!("wnfsError in rm_native: {:?}", msg.to_owned());
-                    RustResult::error(msg.to_owned().into())
+                    error_result("rm_native", msg.to_owned())
                 }
             } else {
                 let msg = helper_res.err().unwrap();
-                trace!("wnfsError in rm_native: {:?}", msg.to_owned());
-                RustResult::error(msg.to_owned().into())
+                load_error_result("rm_native", msg.to_owned())
             }
         }
     }
@@ -441,7 +551,11 @@ pub mod ios {
         let block_store = &mut FFIFriendlyBlockStore::new(Box::new(store));
         let cid_res: Result<Cid, String> = cid.try_into();
         if cid_res.is_err() {
-            RustResult::error(RustString::from(cid_res.err().unwrap().to_string()))
+            error_result_with_code(
+                "ls_native",
+                WnfsErrorCode::InvalidCid,
+                cid_res.err().unwrap(),
+            )
         } else {
             let cid = cid_res.unwrap();
             let helper_res = PrivateDirectoryHelper::synced_reload(block_store, cid);
@@ -457,28 +571,1933 @@ pub mod ios {
                         RustResult::ok(res.into())
                     } else {
                         let msg = output.err().unwrap().to_string();
-                        trace!(
-                            "wnfsError occured in ls_native output: {:?}",
-                            msg.to_owned()
-                        );
-                        RustResult::error(msg.to_owned().into())
+                        error_result("ls_native", msg.to_owned())
                     }
                 } else {
                     let msg = ls_res.err().unwrap();
-                    trace!(
-                        "wnfsError occured in ls_native ls_res: {:?}",
-                        msg.to_owned()
-                    );
-                    RustResult::error(msg.to_owned().into())
+                    error_result("ls_native", msg.to_owned())
                 }
             } else {
                 let msg = helper_res.err().unwrap();
-                trace!(
-                    "wnfsError occured in ls_native forest_res: {:?}",
-                    msg.to_owned()
+                load_error_result("ls_native", msg.to_owned())
+            }
+        }
+    }
+
+    /// Same as `ls_native`, but encodes the listing as `format`. Every entry
+    /// carries the CID of its node and files their size, which is taken from
+    /// the file header rather than the body.
+    #[no_mangle]
+    pub extern "C" fn ls_with_format_native(
+        block_store_interface: BlockStoreInterface,
+        cid: RustString,
+        path_segments: RustString,
+        format: OutputFormat,
+    ) -> RustResult<RustBytes> {
+        trace!("**********************ls_with_format_native started**************");
+        let store = BridgedStore::new(block_store_interface);
+        let block_store = &mut FFIFriendlyBlockStore::new(Box::new(store));
+        let cid_res: Result<Cid, String> = cid.try_into();
+        if cid_res.is_err() {
+            error_result_with_code(
+                "ls_with_format_native",
+                WnfsErrorCode::InvalidCid,
+                cid_res.err().unwrap(),
+            )
+        } else {
+            let cid = cid_res.unwrap();
+            let helper_res = PrivateDirectoryHelper::synced_reload(block_store, cid);
+            if helper_res.is_ok() {
+                let helper = &mut helper_res.ok().unwrap();
+                let path_segments = unsafe { prepare_path_segments(path_segments) };
+                let output = prepare_ls_output_with_format(helper, &path_segments, format, false);
+                trace!("**********************ls_with_format_native finished**************");
+                if output.is_ok() {
+                    RustResult::ok(output.ok().unwrap().into())
+                } else {
+                    let msg = output.err().unwrap();
+                    error_result("ls_with_format_native", msg.to_owned())
+                }
+            } else {
+                let msg = helper_res.err().unwrap();
+                load_error_result("ls_with_format_native", msg.to_owned())
+            }
+        }
+    }
+
+    /// Reads `length` bytes starting at `offset` from a file. Reads past the
+    /// end of the file are truncated.
+    #[no_mangle]
+    pub extern "C" fn read_file_range_native(
+        block_store_interface: BlockStoreInterface,
+        cid: RustString,
+        path_segments: RustString,
+        offset: u64,
+        length: u64,
+    ) -> RustResult<RustBytes> {
+        trace!("**********************read_file_range_native started**************");
+        let store = BridgedStore::new(block_store_interface);
+        let block_store = &mut FFIFriendlyBlockStore::new(Box::new(store));
+        let cid_res: Result<Cid, String> = cid.try_into();
+        if cid_res.is_err() {
+            error_result_with_code(
+                "read_file_range_native",
+                WnfsErrorCode::InvalidCid,
+                cid_res.err().unwrap(),
+            )
+        } else {
+            let cid = cid_res.unwrap();
+            let helper_res = PrivateDirectoryHelper::synced_reload(block_store, cid);
+            if helper_res.is_ok() {
+                let helper = &mut helper_res.ok().unwrap();
+                let path_segments = unsafe { prepare_path_segments(path_segments) };
+                let result = read_file_range(helper, &path_segments, offset, length);
+                trace!("**********************read_file_range_native finished**************");
+                if result.is_ok() {
+                    RustResult::ok(result.ok().unwrap().into())
+                } else {
+                    let msg = result.err().unwrap();
+                    error_result("read_file_range_native", msg)
+                }
+            } else {
+                let msg = helper_res.err().unwrap();
+                load_error_result("read_file_range_native", msg)
+            }
+        }
+    }
+
+    /// Applies `ops_len` operations from `ops` in order against a single load
+    /// of the forest at `cid` and returns the final root CID. If any operation
+    /// fails nothing is returned but the error, whose detail carries the
+    /// failing `op_index`, and `cid` remains the current root.
+    #[no_mangle]
+    pub unsafe extern "C" fn transaction_native(
+        block_store_interface: BlockStoreInterface,
+        extensions: *const BlockStoreExtensions,
+        cid: RustString,
+        ops: *const TransactionOp,
+        ops_len: usize,
+    ) -> RustResult<RustString> {
+        trace!("**********************transaction_native started**************");
+        let store = match bridged_store(block_store_interface, extensions, "transaction_native") {
+            Ok(store) => store,
+            Err(err) => return err,
+        };
+        let block_store = &mut FFIFriendlyBlockStore::new(Box::new(store));
+        let cid_res: Result<Cid, String> = cid.try_into();
+        if cid_res.is_err() {
+            error_result_with_code(
+                "transaction_native",
+                WnfsErrorCode::InvalidCid,
+                cid_res.err().unwrap(),
+            )
+        } else {
+            let cid = cid_res.unwrap();
+            let helper_res = PrivateDirectoryHelper::synced_reload(block_store, cid);
+            if helper_res.is_ok() {
+                let helper = &mut helper_res.ok().unwrap();
+                let ops = ops_from_raw(ops, ops_len);
+                let result = apply_operations(helper, ops);
+                trace!("**********************transaction_native finished**************");
+                match result {
+                    Ok(new_cid) => RustResult::ok(new_cid.unwrap_or(cid).into()),
+                    Err(err) => transaction_error_result("transaction_native", err),
+                }
+            } else {
+                let msg = helper_res.err().unwrap();
+                load_error_result("transaction_native", msg)
+            }
+        }
+    }
+
+    /// Enables the block cache, or resizes it, dropping its current content.
+    /// Passing zero for either limit disables it. The limits are shared, but
+    /// each entry belongs to the host store it was read from, as identified
+    /// by the interface `userdata`, and is dropped when that store deletes
+    /// the block or reports it dead in a gc. Written blocks are only cached
+    /// when `cache_puts` is set.
+    #[no_mangle]
+    pub extern "C" fn block_cache_configure_native(
+        max_entries: usize,
+        max_bytes: usize,
+        cache_puts: bool,
+    ) -> RustResult<RustVoid> {
+        trace!("**********************block_cache_configure_native started**************");
+        configure_block_cache(max_entries, max_bytes, cache_puts);
+        RustResult::ok(RustVoid::void())
+    }
+
+    /// Empties the block cache without changing its limits.
+    #[no_mangle]
+    pub extern "C" fn block_cache_purge_native() -> RustResult<RustVoid> {
+        trace!("**********************block_cache_purge_native started**************");
+        purge_block_cache();
+        RustResult::ok(RustVoid::void())
+    }
+
+    /// Reports the blocks reachable from `drop_cids` that are not reachable
+    /// from any of `keep_cids`, i.e. the blocks that can be removed once the
+    /// dropped roots are forgotten. Each one is also passed to `on_dead`, if
+    /// set, as soon as it is found; the string is only valid during the call.
+    /// With `delete` set they are removed through the `delete_fn` of
+    /// `extensions`, which may be null when no callback is needed.
+    #[no_mangle]
+    #[allow(clippy::too_many_arguments)]
+    pub unsafe extern "C" fn gc_native(
+        block_store_interface: BlockStoreInterface,
+        extensions: *const BlockStoreExtensions,
+        keep_cids: *const RustString,
+        keep_len: usize,
+        drop_cids: *const RustString,
+        drop_len: usize,
+        delete: bool,
+        format: OutputFormat,
+        on_dead: Option<extern "C" fn(userdata: *mut c_void, cid: RustString)>,
+        userdata: *mut c_void,
+    ) -> RustResult<RustBytes> {
+        trace!("**********************gc_native started**************");
+        let store = match bridged_store(block_store_interface, extensions, "gc_native") {
+            Ok(store) => store,
+            Err(err) => return err,
+        };
+        let roots_res = prepare_cids(keep_cids, keep_len)
+            .and_then(|keep| Ok((keep, prepare_cids(drop_cids, drop_len)?)));
+        if roots_res.is_err() {
+            return error_result_with_code(
+                "gc_native",
+                WnfsErrorCode::InvalidCid,
+                roots_res.err().unwrap(),
+            );
+        }
+        let (keep_roots, drop_roots) = roots_res.unwrap();
+        let report = collect_garbage(&store, &keep_roots, &drop_roots, delete, |cid| {
+            if let Some(on_dead) = on_dead {
+                let cid = OwnedRustString::new(cid.to_string());
+                on_dead(userdata, cid.get());
+            }
+        })
+        .and_then(|report| encode_output(&report, format));
+        trace!("**********************gc_native finished**************");
+        match report {
+            Ok(report) => RustResult::ok(report.into()),
+            Err(msg) => error_result("gc_native", msg),
+        }
+    }
+
+    /// Describes a single path, encoded as `format`. A missing path is not an
+    /// error: the result has `exists` set to false. `size` is only filled in
+    /// when `with_content` is set, without reading the file body.
+    #[no_mangle]
+    pub extern "C" fn stat_native(
+        block_store_interface: BlockStoreInterface,
+        cid: RustString,
+        path_segments: RustString,
+        format: OutputFormat,
+        with_content: bool,
+    ) -> RustResult<RustBytes> {
+        trace!("**********************stat_native started**************");
+        let store = BridgedStore::new(block_store_interface);
+        let block_store = &mut FFIFriendlyBlockStore::new(Box::new(store));
+        let cid_res: Result<Cid, String> = cid.try_into();
+        if cid_res.is_err() {
+            error_result_with_code(
+                "stat_native",
+                WnfsErrorCode::InvalidCid,
+                cid_res.err().unwrap(),
+            )
+        } else {
+            let cid = cid_res.unwrap();
+            let helper_res = PrivateDirectoryHelper::synced_reload(block_store, cid);
+            if helper_res.is_ok() {
+                let helper = &mut helper_res.ok().unwrap();
+                let path_segments = unsafe { prepare_path_segments(path_segments) };
+                let output = stat_path(helper, &path_segments, with_content)
+                    .and_then(|stat| encode_output(&stat, format));
+                trace!("**********************stat_native finished**************");
+                if output.is_ok() {
+                    RustResult::ok(output.ok().unwrap().into())
+                } else {
+                    let msg = output.err().unwrap();
+                    error_result("stat_native", msg)
+                }
+            } else {
+                let msg = helper_res.err().unwrap();
+                load_error_result("stat_native", msg)
+            }
+        }
+    }
+
+    fn tree_limits(max_depth: u32, max_entries: usize) -> (Option<u32>, Option<usize>) {
+        (
+            Some(max_depth).filter(|&max| max > 0),
+            Some(max_entries).filter(|&max| max > 0),
+        )
+    }
+
+    /// Lists everything below `path_segments` in one call, depth first. Zero
+    /// for `max_depth` or `max_entries` means unlimited; `truncated` is set in
+    /// the result when `max_entries` was reached. With `on_entry` set, every
+    /// entry is encoded as `format` and passed to it as soon as it is found,
+    /// and the returned `entries` is left empty; the bytes are only valid
+    /// during the call.
+    #[no_mangle]
+    #[allow(clippy::too_many_arguments)]
+    pub unsafe extern "C" fn tree_native(
+        block_store_interface: BlockStoreInterface,
+        extensions: *const BlockStoreExtensions,
+        cid: RustString,
+        path_segments: RustString,
+        max_depth: u32,
+        max_entries: usize,
+        format: OutputFormat,
+        with_content: bool,
+        on_entry: Option<extern "C" fn(userdata: *mut c_void, entry: RustBytes)>,
+        userdata: *mut c_void,
+    ) -> RustResult<RustBytes> {
+        trace!("**********************tree_native started**************");
+        let store = match bridged_store(block_store_interface, extensions, "tree_native") {
+            Ok(store) => store,
+            Err(err) => return err,
+        };
+        let block_store = &mut FFIFriendlyBlockStore::new(Box::new(store));
+        let cid_res: Result<Cid, String> = cid.try_into();
+        if cid_res.is_err() {
+            error_result_with_code(
+                "tree_native",
+                WnfsErrorCode::InvalidCid,
+                cid_res.err().unwrap(),
+            )
+        } else {
+            let cid = cid_res.unwrap();
+            let helper_res = PrivateDirectoryHelper::synced_reload(block_store, cid);
+            if helper_res.is_ok() {
+                let helper = &mut helper_res.ok().unwrap();
+                let path_segments = prepare_path_segments(path_segments);
+                let (max_depth, max_entries) = tree_limits(max_depth, max_entries);
+                let output = prepare_tree_output(
+                    helper,
+                    &path_segments,
+                    max_depth,
+                    max_entries,
+                    format,
+                    with_content,
+                    on_entry.map(|on_entry| {
+                        move |entry: Vec<u8>| {
+                            let entry = OwnedRustBytes::new(entry);
+                            on_entry(userdata, entry.get());
+                        }
+                    }),
                 );
-                RustResult::error(msg.to_owned().into())
+                trace!("**********************tree_native finished**************");
+                if output.is_ok() {
+                    RustResult::ok(output.ok().unwrap().into())
+                } else {
+                    let msg = output.err().unwrap();
+                    error_result("tree_native", msg)
+                }
+            } else {
+                let msg = helper_res.err().unwrap();
+                load_error_result("tree_native", msg)
+            }
+        }
+    }
+
+    /// Moves the private tree at `cid` from `old_wnfs_key` to `new_wnfs_key`
+    /// and returns the new root CID. Only the latest revision of each node is
+    /// copied, so revision history does not carry over. Nothing is deleted:
+    /// once the new root is stored, the old one can be passed to `gc_native`
+    /// as a dropped root.
+    #[no_mangle]
+    pub unsafe extern "C" fn rotate_key_native(
+        block_store_interface: BlockStoreInterface,
+        extensions: *const BlockStoreExtensions,
+        old_wnfs_key: RustBytes,
+        new_wnfs_key: RustBytes,
+        cid: RustString,
+    ) -> RustResult<RustString> {
+        trace!("**********************rotate_key_native started**************");
+        let store = match bridged_store(block_store_interface, extensions, "rotate_key_native") {
+            Ok(store) => store,
+            Err(err) => return err,
+        };
+        let old_wnfs_key: Vec<u8> = old_wnfs_key.into();
+        let new_wnfs_key: Vec<u8> = new_wnfs_key.into();
+        let cid_res: Result<Cid, String> = cid.try_into();
+        if cid_res.is_err() {
+            return error_result_with_code(
+                "rotate_key_native",
+                WnfsErrorCode::InvalidCid,
+                cid_res.err().unwrap(),
+            );
+        }
+        let result = rotate_key(store, old_wnfs_key, new_wnfs_key, cid_res.unwrap());
+        trace!("**********************rotate_key_native finished**************");
+        match result {
+            Ok(new_cid) => RustResult::ok(new_cid.into()),
+            Err(msg) => error_result("rotate_key_native", msg),
+        }
+    }
+
+    /// Returns the access key of the file or directory at `path_segments` as
+    /// `{version, cid, kind, access_key}` encoded as `format`, with the key
+    /// base64 encoded. A directory's key opens it through
+    /// `load_shared_native` and a file's is read with
+    /// `read_shared_file_native`; neither opens anything above the node. It
+    /// is the node's own key: opened against a later root of the drive, the
+    /// share shows the node as it is in that root.
+    #[no_mangle]
+    pub unsafe extern "C" fn share_native(
+        block_store_interface: BlockStoreInterface,
+        extensions: *const BlockStoreExtensions,
+        cid: RustString,
+        path_segments: RustString,
+        format: OutputFormat,
+    ) -> RustResult<RustBytes> {
+        trace!("**********************share_native started**************");
+        let store = match bridged_store(block_store_interface, extensions, "share_native") {
+            Ok(store) => store,
+            Err(err) => return err,
+        };
+        let cid_res: Result<Cid, String> = cid.try_into();
+        if cid_res.is_err() {
+            return error_result_with_code(
+                "share_native",
+                WnfsErrorCode::InvalidCid,
+                cid_res.err().unwrap(),
+            );
+        }
+        let path_segments = prepare_path_segments(path_segments);
+        let output = share_subtree(store, cid_res.unwrap(), &path_segments)
+            .and_then(|share| encode_output(&share, format));
+        trace!("**********************share_native finished**************");
+        match output {
+            Ok(output) => RustResult::ok(output.into()),
+            Err(msg) => error_result("share_native", msg),
+        }
+    }
+
+    /// Lists the revisions of `path_segments` in the forest at `cid`, ordered
+    /// oldest first, encoded as `format`. They are read from the node's own
+    /// previous-revision links, back to the revision the path had in
+    /// `since_cid`, an older root of the same drive in which it exists.
+    /// `with_content` also fills in each revision's size.
+    #[no_mangle]
+    pub unsafe extern "C" fn revisions_native(
+        block_store_interface: BlockStoreInterface,
+        extensions: *const BlockStoreExtensions,
+        cid: RustString,
+        since_cid: RustString,
+        path_segments: RustString,
+        format: OutputFormat,
+        with_content: bool,
+    ) -> RustResult<RustBytes> {
+        trace!("**********************revisions_native started**************");
+        let store = match bridged_store(block_store_interface, extensions, "revisions_native") {
+            Ok(store) => store,
+            Err(err) => return err,
+        };
+        let cid_res: Result<Cid, String> = cid.try_into();
+        let since_res: Result<Cid, String> = since_cid.try_into();
+        let (cid, since) = match (cid_res, since_res) {
+            (Ok(cid), Ok(since)) => (cid, since),
+            (Err(msg), _) | (_, Err(msg)) => {
+                return error_result_with_code("revisions_native", WnfsErrorCode::InvalidCid, msg)
+            }
+        };
+        let path_segments = prepare_path_segments(path_segments);
+        let output = list_revisions(store, cid, since, &path_segments, with_content)
+            .and_then(|revisions| encode_output(&revisions, format));
+        trace!("**********************revisions_native finished**************");
+        match output {
+            Ok(output) => RustResult::ok(output.into()),
+            Err(msg) => error_result("revisions_native", msg),
+        }
+    }
+
+    /// Reads the file at `path_segments` as of `revision`, numbered as by
+    /// `revisions_native` with the same `since_cid`.
+    #[no_mangle]
+    pub unsafe extern "C" fn read_revision_native(
+        block_store_interface: BlockStoreInterface,
+        extensions: *const BlockStoreExtensions,
+        cid: RustString,
+        since_cid: RustString,
+        path_segments: RustString,
+        revision: u32,
+    ) -> RustResult<RustBytes> {
+        trace!("**********************read_revision_native started**************");
+        let store = match bridged_store(block_store_interface, extensions, "read_revision_native") {
+            Ok(store) => store,
+            Err(err) => return err,
+        };
+        let cid_res: Result<Cid, String> = cid.try_into();
+        let since_res: Result<Cid, String> = since_cid.try_into();
+        let (cid, since) = match (cid_res, since_res) {
+            (Ok(cid), Ok(since)) => (cid, since),
+            (Err(msg), _) | (_, Err(msg)) => {
+                return error_result_with_code(
+                    "read_revision_native",
+                    WnfsErrorCode::InvalidCid,
+                    msg,
+                )
+            }
+        };
+        let path_segments = prepare_path_segments(path_segments);
+        let result = read_revision(store, cid, since, &path_segments, revision);
+        trace!("**********************read_revision_native finished**************");
+        match result {
+            Ok(content) => RustResult::ok(content.into()),
+            Err(msg) => error_result("read_revision_native", msg),
+        }
+    }
+
+    /// Makes `revision` of `path_segments`, numbered as by `revisions_native`
+    /// with the same `since_cid`, the current one in the forest at `cid` and
+    /// returns the new root CID.
+    #[no_mangle]
+    pub unsafe extern "C" fn restore_revision_native(
+        block_store_interface: BlockStoreInterface,
+        extensions: *const BlockStoreExtensions,
+        cid: RustString,
+        since_cid: RustString,
+        path_segments: RustString,
+        revision: u32,
+    ) -> RustResult<RustString> {
+        trace!("**********************restore_revision_native started**************");
+        let store =
+            match bridged_store(block_store_interface, extensions, "restore_revision_native") {
+                Ok(store) => store,
+                Err(err) => return err,
+            };
+        let cid_res: Result<Cid, String> = cid.try_into();
+        let since_res: Result<Cid, String> = since_cid.try_into();
+        let (cid, since) = match (cid_res, since_res) {
+            (Ok(cid), Ok(since)) => (cid, since),
+            (Err(msg), _) | (_, Err(msg)) => {
+                return error_result_with_code(
+                    "restore_revision_native",
+                    WnfsErrorCode::InvalidCid,
+                    msg,
+                )
+            }
+        };
+        let path_segments = prepare_path_segments(path_segments);
+        let result = restore_revision(store, cid, since, &path_segments, revision);
+        trace!("**********************restore_revision_native finished**************");
+        match result {
+            Ok(new_cid) => RustResult::ok(new_cid.into()),
+            Err(msg) => error_result("restore_revision_native", msg),
+        }
+    }
+
+    /// Lists what was added, removed or modified below `path_segments` going
+    /// from `cid_a` to `cid_b`, encoded as `format`. Paths are relative to
+    /// `path_segments`. Files are compared by node CID, so every write shows
+    /// up, and directories whose node did not change are skipped. Sizes are
+    /// only reported with `with_content`.
+    #[no_mangle]
+    pub unsafe extern "C" fn diff_native(
+        block_store_interface: BlockStoreInterface,
+        extensions: *const BlockStoreExtensions,
+        cid_a: RustString,
+        cid_b: RustString,
+        path_segments: RustString,
+        format: OutputFormat,
+        with_content: bool,
+    ) -> RustResult<RustBytes> {
+        trace!("**********************diff_native started**************");
+        let store = match bridged_store(block_store_interface, extensions, "diff_native") {
+            Ok(store) => store,
+            Err(err) => return err,
+        };
+        let cid_a_res: Result<Cid, String> = cid_a.try_into();
+        let cid_b_res: Result<Cid, String> = cid_b.try_into();
+        let (cid_a, cid_b) = match (cid_a_res, cid_b_res) {
+            (Ok(cid_a), Ok(cid_b)) => (cid_a, cid_b),
+            (Err(msg), _) | (_, Err(msg)) => {
+                return error_result_with_code("diff_native", WnfsErrorCode::InvalidCid, msg)
             }
+        };
+        let path_segments = prepare_path_segments(path_segments);
+        let output = diff_roots(store, cid_a, cid_b, &path_segments, with_content)
+            .and_then(|diff| encode_output(&diff, format));
+        trace!("**********************diff_native finished**************");
+        match output {
+            Ok(output) => RustResult::ok(output.into()),
+            Err(msg) => error_result("diff_native", msg),
+        }
+    }
+
+    /// Three-way merge: replays what changed from `base_cid` to `theirs_cid`
+    /// onto `ours_cid`. Files changed differently on both sides keep our
+    /// version in place and get theirs next to it, named with
+    /// `conflict_label` ("conflict" when empty). Returns
+    /// `{version, cid, actions}` encoded as `format`, where `cid` is the
+    /// merged root and `actions` reports every path that was touched. User
+    /// metadata travels with the entries copied from theirs but is never
+    /// merged on its own; metadata edits to directories and symlinks are not
+    /// seen as changes.
+    #[no_mangle]
+    pub unsafe extern "C" fn merge_native(
+        block_store_interface: BlockStoreInterface,
+        extensions: *const BlockStoreExtensions,
+        base_cid: RustString,
+        ours_cid: RustString,
+        theirs_cid: RustString,
+        conflict_label: RustString,
+        format: OutputFormat,
+    ) -> RustResult<RustBytes> {
+        trace!("**********************merge_native started**************");
+        let store = match bridged_store(block_store_interface, extensions, "merge_native") {
+            Ok(store) => store,
+            Err(err) => return err,
+        };
+        let base_res: Result<Cid, String> = base_cid.try_into();
+        let ours_res: Result<Cid, String> = ours_cid.try_into();
+        let theirs_res: Result<Cid, String> = theirs_cid.try_into();
+        let (base, ours, theirs) = match (base_res, ours_res, theirs_res) {
+            (Ok(base), Ok(ours), Ok(theirs)) => (base, ours, theirs),
+            (Err(msg), _, _) | (_, Err(msg), _) | (_, _, Err(msg)) => {
+                return error_result_with_code("merge_native", WnfsErrorCode::InvalidCid, msg)
+            }
+        };
+        let conflict_label = conflict_label.to_string_lossy();
+        let output = merge_roots(store, base, ours, theirs, &conflict_label)
+            .and_then(|merge| encode_output(&merge, format));
+        trace!("**********************merge_native finished**************");
+        match output {
+            Ok(output) => RustResult::ok(output.into()),
+            Err(msg) => error_result("merge_native", msg),
+        }
+    }
+
+    /// Loads the private forest at `cid` through `store`.
+    pub fn load_helper(
+        store: BridgedStore,
+        cid: Cid,
+    ) -> Result<PrivateDirectoryHelper<'static>, String> {
+        let block_store = &mut FFIFriendlyBlockStore::new(Box::new(store));
+        PrivateDirectoryHelper::synced_reload(block_store, cid)
+    }
+
+    fn load_private_helper<T: Empty>(
+        block_store_interface: BlockStoreInterface,
+        cid: RustString,
+        fn_name: &str,
+    ) -> Result<PrivateDirectoryHelper<'static>, RustResult<T>> {
+        let cid: Cid = cid
+            .try_into()
+            .map_err(|msg| error_result_with_code(fn_name, WnfsErrorCode::InvalidCid, msg))?;
+        load_helper(BridgedStore::new(block_store_interface), cid)
+            .map_err(|msg| load_error_result(fn_name, msg))
+    }
+
+    /// `load_private_helper` through a store using the host's `extensions`.
+    unsafe fn load_private_helper_with_extensions<T: Empty>(
+        block_store_interface: BlockStoreInterface,
+        extensions: *const BlockStoreExtensions,
+        cid: RustString,
+        fn_name: &str,
+    ) -> Result<PrivateDirectoryHelper<'static>, RustResult<T>> {
+        let store = bridged_store(block_store_interface, extensions, fn_name)?;
+        let cid: Cid = cid
+            .try_into()
+            .map_err(|msg| error_result_with_code(fn_name, WnfsErrorCode::InvalidCid, msg))?;
+        load_helper(store, cid).map_err(|msg| load_error_result(fn_name, msg))
+    }
+
+    /// Creates a symlink at `link_path_segments` pointing to
+    /// `target_path_segments` and returns the new root CID. The target does
+    /// not have to exist. A link is an empty private file whose metadata
+    /// holds the target, the way wnfs keeps any node attribute: it shows up
+    /// with kind `symlink` in structured listings and as a file in
+    /// `ls_native` and to other wnfs clients. Links follow `mv_native` and
+    /// `cp_native`, are removed by `unlink_native` or `rm_native`, and
+    /// writing a file over one fails.
+    #[no_mangle]
+    pub extern "C" fn symlink_native(
+        block_store_interface: BlockStoreInterface,
+        cid: RustString,
+        link_path_segments: RustString,
+        target_path_segments: RustString,
+    ) -> RustResult<RustString> {
+        trace!("**********************symlink_native started**************");
+        let mut helper = match load_private_helper(block_store_interface, cid, "symlink_native") {
+            Ok(helper) => helper,
+            Err(err) => return err,
+        };
+        let link_path_segments = unsafe { prepare_path_segments(link_path_segments) };
+        let target: String = target_path_segments.into();
+        let result = symlink(&mut helper, &link_path_segments, &target);
+        trace!("**********************symlink_native finished**************");
+        session_cid_result(result, "symlink_native")
+    }
+
+    #[no_mangle]
+    pub extern "C" fn unlink_native(
+        block_store_interface: BlockStoreInterface,
+        cid: RustString,
+        link_path_segments: RustString,
+    ) -> RustResult<RustString> {
+        trace!("**********************unlink_native started**************");
+        let mut helper = match load_private_helper(block_store_interface, cid, "unlink_native") {
+            Ok(helper) => helper,
+            Err(err) => return err,
+        };
+        let link_path_segments = unsafe { prepare_path_segments(link_path_segments) };
+        let result = unlink(&mut helper, &link_path_segments);
+        trace!("**********************unlink_native finished**************");
+        session_cid_result(result, "unlink_native")
+    }
+
+    /// Returns the target of the symlink at `link_path_segments`. With
+    /// `follow` set, links are resolved until the path contains none and the
+    /// final path is returned; loops fail.
+    #[no_mangle]
+    pub extern "C" fn readlink_native(
+        block_store_interface: BlockStoreInterface,
+        cid: RustString,
+        link_path_segments: RustString,
+        follow: bool,
+    ) -> RustResult<RustString> {
+        trace!("**********************readlink_native started**************");
+        let mut helper = match load_private_helper(block_store_interface, cid, "readlink_native") {
+            Ok(helper) => helper,
+            Err(err) => return err,
+        };
+        let link_path_segments = unsafe { prepare_path_segments(link_path_segments) };
+        let result = if follow {
+            readlink(&mut helper, &link_path_segments)
+                .and_then(|_| resolve_path(&mut helper, &link_path_segments))
+                .map(|path| path.join("/"))
+        } else {
+            readlink(&mut helper, &link_path_segments)
+        };
+        trace!("**********************readlink_native finished**************");
+        match result {
+            Ok(target) => RustResult::ok(target.into()),
+            Err(msg) => error_result("readlink_native", msg),
+        }
+    }
+
+    /// Same as `read_file_native`, but symlinks anywhere in the path are
+    /// followed.
+    #[no_mangle]
+    pub extern "C" fn read_file_follow_native(
+        block_store_interface: BlockStoreInterface,
+        cid: RustString,
+        path_segments: RustString,
+    ) -> RustResult<RustBytes> {
+        trace!("**********************read_file_follow_native started**************");
+        let mut helper =
+            match load_private_helper(block_store_interface, cid, "read_file_follow_native") {
+                Ok(helper) => helper,
+                Err(err) => return err,
+            };
+        let path_segments = unsafe { prepare_path_segments(path_segments) };
+        let result = resolve_path(&mut helper, &path_segments)
+            .and_then(|path_segments| helper.synced_read_file(&path_segments));
+        trace!("**********************read_file_follow_native finished**************");
+        match result {
+            Ok(content) => RustResult::ok(content.into()),
+            Err(msg) => error_result("read_file_follow_native", msg),
+        }
+    }
+
+    /// Same as `ls_with_format_native`, but every entry also carries its user
+    /// metadata under `meta`.
+    #[no_mangle]
+    pub extern "C" fn ls_with_meta_native(
+        block_store_interface: BlockStoreInterface,
+        cid: RustString,
+        path_segments: RustString,
+        format: OutputFormat,
+    ) -> RustResult<RustBytes> {
+        trace!("**********************ls_with_meta_native started**************");
+        let mut helper =
+            match load_private_helper(block_store_interface, cid, "ls_with_meta_native") {
+                Ok(helper) => helper,
+                Err(err) => return err,
+            };
+        let path_segments = unsafe { prepare_path_segments(path_segments) };
+        let output = prepare_ls_output_with_format(&mut helper, &path_segments, format, true);
+        trace!("**********************ls_with_meta_native finished**************");
+        match output {
+            Ok(output) => RustResult::ok(output.into()),
+            Err(msg) => error_result("ls_with_meta_native", msg),
+        }
+    }
+
+    /// Returns every user metadata key of the node at `path_segments`,
+    /// encoded as `format`. The drive root has metadata too.
+    #[no_mangle]
+    pub extern "C" fn get_metadata_native(
+        block_store_interface: BlockStoreInterface,
+        cid: RustString,
+        path_segments: RustString,
+        format: OutputFormat,
+    ) -> RustResult<RustBytes> {
+        trace!("**********************get_metadata_native started**************");
+        let mut helper =
+            match load_private_helper(block_store_interface, cid, "get_metadata_native") {
+                Ok(helper) => helper,
+                Err(err) => return err,
+            };
+        let path_segments = unsafe { prepare_path_segments(path_segments) };
+        let output =
+            get_meta(&mut helper, &path_segments).and_then(|meta| encode_output(&meta, format));
+        trace!("**********************get_metadata_native finished**************");
+        match output {
+            Ok(output) => RustResult::ok(output.into()),
+            Err(msg) => error_result("get_metadata_native", msg),
+        }
+    }
+
+    fn set_metadata(
+        block_store_interface: BlockStoreInterface,
+        cid: RustString,
+        path_segments: RustString,
+        key: RustString,
+        value: MetaValue,
+        fn_name: &str,
+    ) -> RustResult<RustString> {
+        let mut helper = match load_private_helper(block_store_interface, cid, fn_name) {
+            Ok(helper) => helper,
+            Err(err) => return err,
+        };
+        let path_segments = unsafe { prepare_path_segments(path_segments) };
+        let key: String = key.into();
+        let result = set_meta(&mut helper, &path_segments, &key, value);
+        session_cid_result(result, fn_name)
+    }
+
+    /// Sets a string metadata key on the node at `path_segments` and returns
+    /// the new root CID. Metadata is kept in the node's own wnfs metadata,
+    /// encrypted with the node: anyone who can read the node, including
+    /// through a share, can read it. It follows the node through `mv_native`
+    /// and `cp_native`, goes away with it on `rm_native`, and every change
+    /// makes a new revision of the node.
+    #[no_mangle]
+    pub extern "C" fn set_metadata_string_native(
+        block_store_interface: BlockStoreInterface,
+        cid: RustString,
+        path_segments: RustString,
+        key: RustString,
+        value: RustString,
+    ) -> RustResult<RustString> {
+        trace!("**********************set_metadata_string_native started**************");
+        let value = MetaValue::String(value.into());
+        set_metadata(
+            block_store_interface,
+            cid,
+            path_segments,
+            key,
+            value,
+            "set_metadata_string_native",
+        )
+    }
+
+    /// Same as `set_metadata_string_native` for a number, which must be
+    /// finite.
+    #[no_mangle]
+    pub extern "C" fn set_metadata_number_native(
+        block_store_interface: BlockStoreInterface,
+        cid: RustString,
+        path_segments: RustString,
+        key: RustString,
+        value: f64,
+    ) -> RustResult<RustString> {
+        trace!("**********************set_metadata_number_native started**************");
+        set_metadata(
+            block_store_interface,
+            cid,
+            path_segments,
+            key,
+            MetaValue::Number(value),
+            "set_metadata_number_native",
+        )
+    }
+
+    /// Same as `set_metadata_string_native` for raw bytes.
+    #[no_mangle]
+    pub extern "C" fn set_metadata_bytes_native(
+        block_store_interface: BlockStoreInterface,
+        cid: RustString,
+        path_segments: RustString,
+        key: RustString,
+        value: RustBytes,
+    ) -> RustResult<RustString> {
+        trace!("**********************set_metadata_bytes_native started**************");
+        let value = MetaValue::Bytes(value.into());
+        set_metadata(
+            block_store_interface,
+            cid,
+            path_segments,
+            key,
+            value,
+            "set_metadata_bytes_native",
+        )
+    }
+
+    /// Removes a metadata key from the node at `path_segments` and returns
+    /// the new root CID. Fails with `NotFound` if the key is not set.
+    #[no_mangle]
+    pub extern "C" fn delete_metadata_native(
+        block_store_interface: BlockStoreInterface,
+        cid: RustString,
+        path_segments: RustString,
+        key: RustString,
+    ) -> RustResult<RustString> {
+        trace!("**********************delete_metadata_native started**************");
+        let mut helper =
+            match load_private_helper(block_store_interface, cid, "delete_metadata_native") {
+                Ok(helper) => helper,
+                Err(err) => return err,
+            };
+        let path_segments = unsafe { prepare_path_segments(path_segments) };
+        let key: String = key.into();
+        let result = delete_meta(&mut helper, &path_segments, &key);
+        trace!("**********************delete_metadata_native finished**************");
+        session_cid_result(result, "delete_metadata_native")
+    }
+
+    /// Searches everything below `path_segments` for entries whose name
+    /// matches `pattern` and returns them in one `tree_native` shaped result
+    /// encoded as `format`, with paths relative to `path_segments`. Symlinks
+    /// are listed but not followed.
+    #[no_mangle]
+    pub unsafe extern "C" fn find_native(
+        block_store_interface: BlockStoreInterface,
+        extensions: *const BlockStoreExtensions,
+        cid: RustString,
+        path_segments: RustString,
+        pattern: RustString,
+        options: FindOptions,
+        format: OutputFormat,
+    ) -> RustResult<RustBytes> {
+        trace!("**********************find_native started**************");
+        let pattern: String = pattern.into();
+        let pattern = match compile_pattern(&pattern, options.syntax, options.case_insensitive) {
+            Ok(pattern) => pattern,
+            Err(msg) => {
+                return error_result_with_code("find_native", WnfsErrorCode::InvalidArgument, msg)
+            }
+        };
+        let mut helper = match load_private_helper_with_extensions(
+            block_store_interface,
+            extensions,
+            cid,
+            "find_native",
+        ) {
+            Ok(helper) => helper,
+            Err(err) => return err,
+        };
+        let path_segments = prepare_path_segments(path_segments);
+        let output = find_entries(&mut helper, &path_segments, &pattern, &options)
+            .and_then(|found| encode_output(&found, format));
+        trace!("**********************find_native finished**************");
+        match output {
+            Ok(output) => RustResult::ok(output.into()),
+            Err(msg) => error_result("find_native", msg),
+        }
+    }
+
+    /// Recreates the directory at `path_segments` and everything below it
+    /// under `local_dir`, keeping modification times. Existing local files
+    /// are skipped or overwritten per `existing`. Failures of single entries
+    /// do not stop the export; they are listed in the result, encoded as
+    /// `format`, next to the number of files written and skipped.
+    /// Symlinks found under `local_dir` are never written through.
+    #[no_mangle]
+    pub unsafe extern "C" fn export_dir_to_path_native(
+        block_store_interface: BlockStoreInterface,
+        extensions: *const BlockStoreExtensions,
+        cid: RustString,
+        path_segments: RustString,
+        local_dir: RustString,
+        existing: ExistingFiles,
+        format: OutputFormat,
+    ) -> RustResult<RustBytes> {
+        trace!("**********************export_dir_to_path_native started**************");
+        let mut helper = match load_private_helper_with_extensions(
+            block_store_interface,
+            extensions,
+            cid,
+            "export_dir_to_path_native",
+        ) {
+            Ok(helper) => helper,
+            Err(err) => return err,
+        };
+        let path_segments = prepare_path_segments(path_segments);
+        let local_dir: String = local_dir.into();
+        let output = export_dir(&mut helper, &path_segments, Path::new(&local_dir), existing)
+            .and_then(|exported| encode_output(&exported, format));
+        trace!("**********************export_dir_to_path_native finished**************");
+        match output {
+            Ok(output) => RustResult::ok(output.into()),
+            Err(msg) => error_result("export_dir_to_path_native", msg),
+        }
+    }
+
+    /// Copies the local directory `local_dir` into `path_segments` and
+    /// returns the final root CID with a status for every file, encoded as
+    /// `format`. Everything is written through one loaded forest, so no
+    /// intermediate roots are handed out.
+    ///
+    /// `include` and `exclude` are glob lists matched against names.
+    /// Excluded files and directories are left out of the report; when
+    /// `include` is not empty, only files matching one of its globs are
+    /// imported. Files already in the drive are skipped or overwritten per
+    /// `existing`. Files are streamed from disk, and files and directories
+    /// keep their modification times.
+    #[no_mangle]
+    pub unsafe extern "C" fn import_dir_from_path_native(
+        block_store_interface: BlockStoreInterface,
+        extensions: *const BlockStoreExtensions,
+        cid: RustString,
+        local_dir: RustString,
+        path_segments: RustString,
+        include: *const RustString,
+        include_len: usize,
+        exclude: *const RustString,
+        exclude_len: usize,
+        existing: ExistingFiles,
+        format: OutputFormat,
+    ) -> RustResult<RustBytes> {
+        trace!("**********************import_dir_from_path_native started**************");
+        let filter = match ImportFilter::new(
+            &prepare_strings(include, include_len),
+            &prepare_strings(exclude, exclude_len),
+        ) {
+            Ok(filter) => filter,
+            Err(msg) => {
+                return error_result_with_code(
+                    "import_dir_from_path_native",
+                    WnfsErrorCode::InvalidArgument,
+                    msg,
+                )
+            }
+        };
+        let cid: Cid = match cid.try_into() {
+            Ok(cid) => cid,
+            Err(msg) => {
+                return error_result_with_code(
+                    "import_dir_from_path_native",
+                    WnfsErrorCode::InvalidCid,
+                    msg,
+                )
+            }
+        };
+        let store = match bridged_store(
+            block_store_interface,
+            extensions,
+            "import_dir_from_path_native",
+        ) {
+            Ok(store) => store,
+            Err(err) => return err,
+        };
+        let mut helper = match load_helper(store, cid) {
+            Ok(helper) => helper,
+            Err(msg) => return load_error_result("import_dir_from_path_native", msg),
+        };
+        let path_segments = prepare_path_segments(path_segments);
+        let local_dir: String = local_dir.into();
+        let output = import_dir(
+            &mut helper,
+            cid,
+            Path::new(&local_dir),
+            &path_segments,
+            &filter,
+            existing,
+        )
+        .and_then(|imported| encode_output(&imported, format));
+        trace!("**********************import_dir_from_path_native finished**************");
+        match output {
+            Ok(output) => RustResult::ok(output.into()),
+            Err(msg) => error_result("import_dir_from_path_native", msg),
+        }
+    }
+
+    /// Writes everything reachable from `cid`, content included, to a CAR
+    /// file at `output_path` with `cid` as its root, and returns a summary
+    /// encoded as `format`. Blocks are streamed to disk as they are fetched,
+    /// several at a time when `extensions` has a `get_many_fn`.
+    #[no_mangle]
+    pub unsafe extern "C" fn export_car_native(
+        block_store_interface: BlockStoreInterface,
+        extensions: *const BlockStoreExtensions,
+        cid: RustString,
+        output_path: RustString,
+        car_version: CarVersion,
+        format: OutputFormat,
+    ) -> RustResult<RustBytes> {
+        trace!("**********************export_car_native started**************");
+        let cid: Cid = match cid.try_into() {
+            Ok(cid) => cid,
+            Err(msg) => {
+                return error_result_with_code("export_car_native", WnfsErrorCode::InvalidCid, msg)
+            }
+        };
+        let store = match bridged_store(block_store_interface, extensions, "export_car_native") {
+            Ok(store) => store,
+            Err(err) => return err,
+        };
+        let output_path: String = output_path.into();
+        let output = export_car(&store, cid, Path::new(&output_path), car_version)
+            .and_then(|exported| encode_output(&exported, format));
+        trace!("**********************export_car_native finished**************");
+        match output {
+            Ok(output) => RustResult::ok(output.into()),
+            Err(msg) => error_result("export_car_native", msg),
+        }
+    }
+
+    /// Stores every block of the CAR file at `car_path` in the host block
+    /// store, checking each against its CID first, and returns the header
+    /// roots encoded as `format`. CARv1 and CARv2 are both read. Blocks are
+    /// stored in batches when `extensions` has a `put_many_fn`, leaving out
+    /// those its `has_fn` reports as present.
+    #[no_mangle]
+    pub unsafe extern "C" fn import_car_native(
+        block_store_interface: BlockStoreInterface,
+        extensions: *const BlockStoreExtensions,
+        car_path: RustString,
+        format: OutputFormat,
+    ) -> RustResult<RustBytes> {
+        trace!("**********************import_car_native started**************");
+        let store = match bridged_store(block_store_interface, extensions, "import_car_native") {
+            Ok(store) => store,
+            Err(err) => return err,
+        };
+        let car_path: String = car_path.into();
+        let output = import_car(&store, Path::new(&car_path))
+            .and_then(|imported| encode_output(&imported, format));
+        trace!("**********************import_car_native finished**************");
+        match output {
+            Ok(output) => RustResult::ok(output.into()),
+            Err(msg) => error_result("import_car_native", msg),
+        }
+    }
+
+    fn load_public_helper<T: Empty>(
+        block_store_interface: BlockStoreInterface,
+        cid: RustString,
+        fn_name: &str,
+    ) -> Result<PublicDirectoryHelper<'static>, RustResult<T>> {
+        let cid: Cid = cid
+            .try_into()
+            .map_err(|msg| error_result_with_code(fn_name, WnfsErrorCode::InvalidCid, msg))?;
+        PublicDirectoryHelper::synced_load(BridgedStore::new(block_store_interface), cid)
+            .map_err(|msg| error_result(fn_name, msg))
+    }
+
+    /// Creates an empty public directory and returns its root CID. Public
+    /// roots are independent of the private forest and need no key.
+    #[no_mangle]
+    pub extern "C" fn public_init_native(
+        block_store_interface: BlockStoreInterface,
+    ) -> RustResult<RustString> {
+        trace!("**********************public_init_native started**************");
+        let helper_res =
+            PublicDirectoryHelper::synced_init(BridgedStore::new(block_store_interface));
+        trace!("**********************public_init_native finished**************");
+        match helper_res {
+            Ok((_, cid)) => RustResult::ok(cid.into()),
+            Err(msg) => error_result("public_init_native", msg),
+        }
+    }
+
+    /// Writes `_content` to `path_segments` of the public directory at `cid`
+    /// and returns the new root CID. The content is stored as a UnixFS file
+    /// of raw leaves, so any IPFS node can read it from the file's CID.
+    #[no_mangle]
+    pub extern "C" fn public_write_file_native(
+        block_store_interface: BlockStoreInterface,
+        cid: RustString,
+        path_segments: RustString,
+        _content: RustBytes,
+    ) -> RustResult<RustString> {
+        trace!("**********************public_write_file_native started**************");
+        let mut helper =
+            match load_public_helper(block_store_interface, cid, "public_write_file_native") {
+                Ok(helper) => helper,
+                Err(err) => return err,
+            };
+        let path_segments = unsafe { prepare_path_segments(path_segments) };
+        let content: Vec<u8> = _content.into();
+        let result = helper.synced_write_file(&path_segments, content);
+        trace!("**********************public_write_file_native finished**************");
+        session_cid_result(result, "public_write_file_native")
+    }
+
+    #[no_mangle]
+    pub extern "C" fn public_read_file_native(
+        block_store_interface: BlockStoreInterface,
+        cid: RustString,
+        path_segments: RustString,
+    ) -> RustResult<RustBytes> {
+        trace!("**********************public_read_file_native started**************");
+        let mut helper =
+            match load_public_helper(block_store_interface, cid, "public_read_file_native") {
+                Ok(helper) => helper,
+                Err(err) => return err,
+            };
+        let path_segments = unsafe { prepare_path_segments(path_segments) };
+        let result = helper.synced_read_file(&path_segments);
+        trace!("**********************public_read_file_native finished**************");
+        match result {
+            Ok(content) => RustResult::ok(content.into()),
+            Err(msg) => error_result("public_read_file_native", msg),
+        }
+    }
+
+    /// Lists a public directory, encoded the same way as `ls_with_format_native`.
+    #[no_mangle]
+    pub extern "C" fn public_ls_native(
+        block_store_interface: BlockStoreInterface,
+        cid: RustString,
+        path_segments: RustString,
+        format: OutputFormat,
+    ) -> RustResult<RustBytes> {
+        trace!("**********************public_ls_native started**************");
+        let mut helper = match load_public_helper(block_store_interface, cid, "public_ls_native") {
+            Ok(helper) => helper,
+            Err(err) => return err,
+        };
+        let path_segments = unsafe { prepare_path_segments(path_segments) };
+        let output = prepare_ls_output_with_format(&mut helper, &path_segments, format, false);
+        trace!("**********************public_ls_native finished**************");
+        match output {
+            Ok(output) => RustResult::ok(output.into()),
+            Err(msg) => error_result("public_ls_native", msg),
+        }
+    }
+
+    #[no_mangle]
+    pub extern "C" fn public_mkdir_native(
+        block_store_interface: BlockStoreInterface,
+        cid: RustString,
+        path_segments: RustString,
+    ) -> RustResult<RustString> {
+        trace!("**********************public_mkdir_native started**************");
+        let mut helper = match load_public_helper(block_store_interface, cid, "public_mkdir_native")
+        {
+            Ok(helper) => helper,
+            Err(err) => return err,
+        };
+        let path_segments = unsafe { prepare_path_segments(path_segments) };
+        let result = helper.synced_mkdir(&path_segments);
+        trace!("**********************public_mkdir_native finished**************");
+        session_cid_result(result, "public_mkdir_native")
+    }
+
+    #[no_mangle]
+    pub extern "C" fn public_mv_native(
+        block_store_interface: BlockStoreInterface,
+        cid: RustString,
+        source_path_segments: RustString,
+        target_path_segments: RustString,
+    ) -> RustResult<RustString> {
+        trace!("**********************public_mv_native started**************");
+        let mut helper = match load_public_helper(block_store_interface, cid, "public_mv_native") {
+            Ok(helper) => helper,
+            Err(err) => return err,
+        };
+        let source_path_segments = unsafe { prepare_path_segments(source_path_segments) };
+        let target_path_segments = unsafe { prepare_path_segments(target_path_segments) };
+        let result = helper.synced_mv(&source_path_segments, &target_path_segments);
+        trace!("**********************public_mv_native finished**************");
+        session_cid_result(result, "public_mv_native")
+    }
+
+    /// Copies a file or a whole directory. Copied files share their content
+    /// blocks with the source.
+    #[no_mangle]
+    pub extern "C" fn public_cp_native(
+        block_store_interface: BlockStoreInterface,
+        cid: RustString,
+        source_path_segments: RustString,
+        target_path_segments: RustString,
+    ) -> RustResult<RustString> {
+        trace!("**********************public_cp_native started**************");
+        let mut helper = match load_public_helper(block_store_interface, cid, "public_cp_native") {
+            Ok(helper) => helper,
+            Err(err) => return err,
+        };
+        let source_path_segments = unsafe { prepare_path_segments(source_path_segments) };
+        let target_path_segments = unsafe { prepare_path_segments(target_path_segments) };
+        let result = helper.synced_cp(&source_path_segments, &target_path_segments);
+        trace!("**********************public_cp_native finished**************");
+        session_cid_result(result, "public_cp_native")
+    }
+
+    #[no_mangle]
+    pub extern "C" fn public_rm_native(
+        block_store_interface: BlockStoreInterface,
+        cid: RustString,
+        path_segments: RustString,
+    ) -> RustResult<RustString> {
+        trace!("**********************public_rm_native started**************");
+        let mut helper = match load_public_helper(block_store_interface, cid, "public_rm_native") {
+            Ok(helper) => helper,
+            Err(err) => return err,
+        };
+        let path_segments = unsafe { prepare_path_segments(path_segments) };
+        let result = helper.synced_rm(&path_segments);
+        trace!("**********************public_rm_native finished**************");
+        session_cid_result(result, "public_rm_native")
+    }
+
+    unsafe fn session_mut<'s>(session: *mut WnfsSession) -> Option<&'s mut WnfsSession> {
+        session.as_mut()
+    }
+
+    fn session_cid_result(result: Result<Cid, String>, fn_name: &str) -> RustResult<RustString> {
+        match result {
+            Ok(cid) => RustResult::ok(cid.into()),
+            Err(msg) => error_result(fn_name, msg),
+        }
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn wnfs_session_open(
+        block_store_interface: BlockStoreInterface,
+        extensions: *const BlockStoreExtensions,
+        wnfs_key: RustBytes,
+        cid: RustString,
+    ) -> RustResult<RustSession> {
+        trace!("**********************wnfs_session_open started**************");
+        let store = match bridged_store(block_store_interface, extensions, "wnfs_session_open") {
+            Ok(store) => store,
+            Err(err) => return err,
+        };
+        let wnfs_key: Vec<u8> = wnfs_key.into();
+        let cid_res: Result<Cid, String> = cid.try_into();
+        if cid_res.is_err() {
+            return error_result_with_code(
+                "wnfs_session_open",
+                WnfsErrorCode::InvalidCid,
+                cid_res.err().unwrap(),
+            );
+        }
+        let session_res = WnfsSession::open(store, wnfs_key, cid_res.unwrap());
+        trace!("**********************wnfs_session_open finished**************");
+        match session_res {
+            Ok(session) => RustResult::ok(session.into()),
+            Err(msg) => load_error_result("wnfs_session_open", msg),
+        }
+    }
+
+    /// Frees a session returned by `wnfs_session_open`. Passing null is a no-op.
+    #[no_mangle]
+    pub unsafe extern "C" fn wnfs_session_close(session: *mut WnfsSession) {
+        trace!("**********************wnfs_session_close started**************");
+        if !session.is_null() {
+            drop(Box::from_raw(session));
+        }
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn wnfs_session_cid(session: *mut WnfsSession) -> RustResult<RustString> {
+        match session_mut(session) {
+            Some(session) => RustResult::ok(session.cid().into()),
+            None => error_result_with_code(
+                "wnfs_session_cid",
+                WnfsErrorCode::InvalidArgument,
+                "null session".to_string(),
+            ),
+        }
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn wnfs_session_write_file(
+        session: *mut WnfsSession,
+        path_segments: RustString,
+        _content: RustBytes,
+    ) -> RustResult<RustString> {
+        trace!("**********************wnfs_session_write_file started**************");
+        let session = match session_mut(session) {
+            Some(session) => session,
+            None => {
+                return error_result_with_code(
+                    "wnfs_session_write_file",
+                    WnfsErrorCode::InvalidArgument,
+                    "null session".to_string(),
+                )
+            }
+        };
+        let path_segments = prepare_path_segments(path_segments);
+        let content: Vec<u8> = _content.into();
+        session_cid_result(
+            session.write_file(&path_segments, content),
+            "wnfs_session_write_file",
+        )
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn wnfs_session_write_file_from_path(
+        session: *mut WnfsSession,
+        path_segments: RustString,
+        _filename: RustString,
+    ) -> RustResult<RustString> {
+        trace!("**********************wnfs_session_write_file_from_path started**************");
+        let session = match session_mut(session) {
+            Some(session) => session,
+            None => {
+                return error_result_with_code(
+                    "wnfs_session_write_file_from_path",
+                    WnfsErrorCode::InvalidArgument,
+                    "null session".to_string(),
+                )
+            }
+        };
+        let path_segments = prepare_path_segments(path_segments);
+        let filename: String = _filename.into();
+        session_cid_result(
+            session.write_file_from_path(&path_segments, &filename),
+            "wnfs_session_write_file_from_path",
+        )
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn wnfs_session_read_file(
+        session: *mut WnfsSession,
+        path_segments: RustString,
+    ) -> RustResult<RustBytes> {
+        trace!("**********************wnfs_session_read_file started**************");
+        let session = match session_mut(session) {
+            Some(session) => session,
+            None => {
+                return error_result_with_code(
+                    "wnfs_session_read_file",
+                    WnfsErrorCode::InvalidArgument,
+                    "null session".to_string(),
+                )
+            }
+        };
+        let path_segments = prepare_path_segments(path_segments);
+        match session.read_file(&path_segments) {
+            Ok(content) => RustResult::ok(content.into()),
+            Err(msg) => error_result("wnfs_session_read_file", msg),
+        }
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn wnfs_session_read_file_to_path(
+        session: *mut WnfsSession,
+        path_segments: RustString,
+        _filename: RustString,
+    ) -> RustResult<RustString> {
+        trace!("**********************wnfs_session_read_file_to_path started**************");
+        let session = match session_mut(session) {
+            Some(session) => session,
+            None => {
+                return error_result_with_code(
+                    "wnfs_session_read_file_to_path",
+                    WnfsErrorCode::InvalidArgument,
+                    "null session".to_string(),
+                )
+            }
+        };
+        let path_segments = prepare_path_segments(path_segments);
+        let filename: String = _filename.into();
+        match session.read_file_to_path(&path_segments, &filename) {
+            Ok(_) => RustResult::ok(filename.into()),
+            Err(msg) => error_result("wnfs_session_read_file_to_path", msg),
+        }
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn wnfs_session_read_filestream_to_path(
+        session: *mut WnfsSession,
+        path_segments: RustString,
+        _filename: RustString,
+    ) -> RustResult<RustString> {
+        trace!("**********************wnfs_session_read_filestream_to_path started**************");
+        let session = match session_mut(session) {
+            Some(session) => session,
+            None => {
+                return error_result_with_code(
+                    "wnfs_session_read_filestream_to_path",
+                    WnfsErrorCode::InvalidArgument,
+                    "null session".to_string(),
+                )
+            }
+        };
+        let path_segments = prepare_path_segments(path_segments);
+        let filename: String = _filename.into();
+        match session.read_filestream_to_path(&path_segments, &filename) {
+            Ok(_) => RustResult::ok(filename.into()),
+            Err(msg) => error_result("wnfs_session_read_filestream_to_path", msg),
+        }
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn wnfs_session_mkdir(
+        session: *mut WnfsSession,
+        path_segments: RustString,
+    ) -> RustResult<RustString> {
+        trace!("**********************wnfs_session_mkdir started**************");
+        let session = match session_mut(session) {
+            Some(session) => session,
+            None => {
+                return error_result_with_code(
+                    "wnfs_session_mkdir",
+                    WnfsErrorCode::InvalidArgument,
+                    "null session".to_string(),
+                )
+            }
+        };
+        let path_segments = prepare_path_segments(path_segments);
+        session_cid_result(session.mkdir(&path_segments), "wnfs_session_mkdir")
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn wnfs_session_mv(
+        session: *mut WnfsSession,
+        source_path_segments: RustString,
+        target_path_segments: RustString,
+    ) -> RustResult<RustString> {
+        trace!("**********************wnfs_session_mv started**************");
+        let session = match session_mut(session) {
+            Some(session) => session,
+            None => {
+                return error_result_with_code(
+                    "wnfs_session_mv",
+                    WnfsErrorCode::InvalidArgument,
+                    "null session".to_string(),
+                )
+            }
+        };
+        let source_path_segments = prepare_path_segments(source_path_segments);
+        let target_path_segments = prepare_path_segments(target_path_segments);
+        session_cid_result(
+            session.mv(&source_path_segments, &target_path_segments),
+            "wnfs_session_mv",
+        )
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn wnfs_session_cp(
+        session: *mut WnfsSession,
+        source_path_segments: RustString,
+        target_path_segments: RustString,
+    ) -> RustResult<RustString> {
+        trace!("**********************wnfs_session_cp started**************");
+        let session = match session_mut(session) {
+            Some(session) => session,
+            None => {
+                return error_result_with_code(
+                    "wnfs_session_cp",
+                    WnfsErrorCode::InvalidArgument,
+                    "null session".to_string(),
+                )
+            }
+        };
+        let source_path_segments = prepare_path_segments(source_path_segments);
+        let target_path_segments = prepare_path_segments(target_path_segments);
+        session_cid_result(
+            session.cp(&source_path_segments, &target_path_segments),
+            "wnfs_session_cp",
+        )
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn wnfs_session_rm(
+        session: *mut WnfsSession,
+        path_segments: RustString,
+    ) -> RustResult<RustString> {
+        trace!("**********************wnfs_session_rm started**************");
+        let session = match session_mut(session) {
+            Some(session) => session,
+            None => {
+                return error_result_with_code(
+                    "wnfs_session_rm",
+                    WnfsErrorCode::InvalidArgument,
+                    "null session".to_string(),
+                )
+            }
+        };
+        let path_segments = prepare_path_segments(path_segments);
+        session_cid_result(session.rm(&path_segments), "wnfs_session_rm")
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn wnfs_session_ls(
+        session: *mut WnfsSession,
+        path_segments: RustString,
+    ) -> RustResult<RustBytes> {
+        trace!("**********************wnfs_session_ls started**************");
+        let session = match session_mut(session) {
+            Some(session) => session,
+            None => {
+                return error_result_with_code(
+                    "wnfs_session_ls",
+                    WnfsErrorCode::InvalidArgument,
+                    "null session".to_string(),
+                )
+            }
+        };
+        let path_segments = prepare_path_segments(path_segments);
+        match session.ls(&path_segments).and_then(prepare_ls_output) {
+            Ok(output) => RustResult::ok(output.into()),
+            Err(msg) => error_result("wnfs_session_ls", msg),
+        }
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn wnfs_session_ls_with_format(
+        session: *mut WnfsSession,
+        path_segments: RustString,
+        format: OutputFormat,
+    ) -> RustResult<RustBytes> {
+        trace!("**********************wnfs_session_ls_with_format started**************");
+        let session = match session_mut(session) {
+            Some(session) => session,
+            None => {
+                return error_result_with_code(
+                    "wnfs_session_ls_with_format",
+                    WnfsErrorCode::InvalidArgument,
+                    "null session".to_string(),
+                )
+            }
+        };
+        let path_segments = prepare_path_segments(path_segments);
+        let output = prepare_ls_output_with_format(session.helper(), &path_segments, format, false);
+        match output {
+            Ok(output) => RustResult::ok(output.into()),
+            Err(msg) => error_result("wnfs_session_ls_with_format", msg),
+        }
+    }
+
+    /// Starts a chunked write to `path_segments`. Nothing is written to the
+    /// forest until the writer is finalized.
+    #[no_mangle]
+    pub extern "C" fn file_writer_open_native(
+        path_segments: RustString,
+    ) -> RustResult<RustFileWriter> {
+        trace!("**********************file_writer_open_native started**************");
+        let path_segments = unsafe { prepare_path_segments(path_segments) };
+        match WnfsFileWriter::open(path_segments) {
+            Ok(writer) => RustResult::ok(writer.into()),
+            Err(msg) => error_result("file_writer_open_native", msg),
+        }
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn file_writer_push_native(
+        writer: *mut WnfsFileWriter,
+        chunk: RustBytes,
+    ) -> RustResult<RustVoid> {
+        let writer = match writer.as_mut() {
+            Some(writer) => writer,
+            None => {
+                return error_result_with_code(
+                    "file_writer_push_native",
+                    WnfsErrorCode::InvalidArgument,
+                    "null file writer".to_string(),
+                )
+            }
+        };
+        match writer.push(chunk.as_slice()) {
+            Ok(_) => RustResult::ok(RustVoid::void()),
+            Err(msg) => error_result("file_writer_push_native", msg),
+        }
+    }
+
+    /// Writes everything pushed so far to the forest at `cid` and returns the
+    /// new root CID. The writer is freed whether or not this succeeds.
+    #[no_mangle]
+    pub unsafe extern "C" fn file_writer_finalize_native(
+        writer: *mut WnfsFileWriter,
+        block_store_interface: BlockStoreInterface,
+        extensions: *const BlockStoreExtensions,
+        cid: RustString,
+    ) -> RustResult<RustString> {
+        trace!("**********************file_writer_finalize_native started**************");
+        if writer.is_null() {
+            return error_result_with_code(
+                "file_writer_finalize_native",
+                WnfsErrorCode::InvalidArgument,
+                "null file writer".to_string(),
+            );
+        }
+        let writer = Box::from_raw(writer);
+        let store = match bridged_store(
+            block_store_interface,
+            extensions,
+            "file_writer_finalize_native",
+        ) {
+            Ok(store) => store,
+            Err(err) => return err,
+        };
+        let cid_res: Result<Cid, String> = cid.try_into();
+        if cid_res.is_err() {
+            return error_result_with_code(
+                "file_writer_finalize_native",
+                WnfsErrorCode::InvalidCid,
+                cid_res.err().unwrap(),
+            );
+        }
+        let mut helper = match load_helper(store, cid_res.unwrap()) {
+            Ok(helper) => helper,
+            Err(msg) => return load_error_result("file_writer_finalize_native", msg),
+        };
+        let result = writer.finalize(|path_segments, reader| {
+            write_file_streaming(&mut helper, path_segments, reader, 0)?;
+            store_root(&mut helper)
+        });
+        trace!("**********************file_writer_finalize_native finished**************");
+        match result {
+            Ok(cid) => RustResult::ok(cid.into()),
+            Err(msg) => error_result("file_writer_finalize_native", msg),
+        }
+    }
+
+    /// Discards a writer and everything pushed to it.
+    #[no_mangle]
+    pub unsafe extern "C" fn file_writer_abort_native(writer: *mut WnfsFileWriter) {
+        trace!("**********************file_writer_abort_native started**************");
+        if !writer.is_null() {
+            drop(Box::from_raw(writer));
+        }
+    }
+
+    /// Session counterpart of `file_writer_finalize_native`.
+    #[no_mangle]
+    pub unsafe extern "C" fn wnfs_session_file_writer_finalize(
+        session: *mut WnfsSession,
+        writer: *mut WnfsFileWriter,
+    ) -> RustResult<RustString> {
+        trace!("**********************wnfs_session_file_writer_finalize started**************");
+        if writer.is_null() {
+            return error_result_with_code(
+                "wnfs_session_file_writer_finalize",
+                WnfsErrorCode::InvalidArgument,
+                "null file writer".to_string(),
+            );
+        }
+        let writer = Box::from_raw(writer);
+        let session = match session_mut(session) {
+            Some(session) => session,
+            None => {
+                return error_result_with_code(
+                    "wnfs_session_file_writer_finalize",
+                    WnfsErrorCode::InvalidArgument,
+                    "null session".to_string(),
+                )
+            }
+        };
+        session_cid_result(
+            writer.finalize(|path_segments, reader| {
+                session.write_file_streaming(path_segments, reader)
+            }),
+            "wnfs_session_file_writer_finalize",
+        )
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn wnfs_session_read_file_range(
+        session: *mut WnfsSession,
+        path_segments: RustString,
+        offset: u64,
+        length: u64,
+    ) -> RustResult<RustBytes> {
+        trace!("**********************wnfs_session_read_file_range started**************");
+        let session = match session_mut(session) {
+            Some(session) => session,
+            None => {
+                return error_result_with_code(
+                    "wnfs_session_read_file_range",
+                    WnfsErrorCode::InvalidArgument,
+                    "null session".to_string(),
+                )
+            }
+        };
+        let path_segments = prepare_path_segments(path_segments);
+        match read_file_range(session.helper(), &path_segments, offset, length) {
+            Ok(content) => RustResult::ok(content.into()),
+            Err(msg) => error_result("wnfs_session_read_file_range", msg),
+        }
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn wnfs_session_transaction(
+        session: *mut WnfsSession,
+        ops: *const TransactionOp,
+        ops_len: usize,
+    ) -> RustResult<RustString> {
+        trace!("**********************wnfs_session_transaction started**************");
+        let session = match session_mut(session) {
+            Some(session) => session,
+            None => {
+                return error_result_with_code(
+                    "wnfs_session_transaction",
+                    WnfsErrorCode::InvalidArgument,
+                    "null session".to_string(),
+                )
+            }
+        };
+        match session.transaction(ops_from_raw(ops, ops_len)) {
+            Ok(cid) => RustResult::ok(cid.into()),
+            Err(err) => transaction_error_result("wnfs_session_transaction", err),
+        }
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn wnfs_session_stat(
+        session: *mut WnfsSession,
+        path_segments: RustString,
+        format: OutputFormat,
+        with_content: bool,
+    ) -> RustResult<RustBytes> {
+        let session = match session_mut(session) {
+            Some(session) => session,
+            None => {
+                return error_result_with_code(
+                    "wnfs_session_stat",
+                    WnfsErrorCode::InvalidArgument,
+                    "null session".to_string(),
+                )
+            }
+        };
+        let path_segments = prepare_path_segments(path_segments);
+        let output = stat_path(session.helper(), &path_segments, with_content)
+            .and_then(|stat| encode_output(&stat, format));
+        match output {
+            Ok(output) => RustResult::ok(output.into()),
+            Err(msg) => error_result("wnfs_session_stat", msg),
+        }
+    }
+
+    #[no_mangle]
+    #[allow(clippy::too_many_arguments)]
+    pub unsafe extern "C" fn wnfs_session_tree(
+        session: *mut WnfsSession,
+        path_segments: RustString,
+        max_depth: u32,
+        max_entries: usize,
+        format: OutputFormat,
+        with_content: bool,
+        on_entry: Option<extern "C" fn(userdata: *mut c_void, entry: RustBytes)>,
+        userdata: *mut c_void,
+    ) -> RustResult<RustBytes> {
+        let session = match session_mut(session) {
+            Some(session) => session,
+            None => {
+                return error_result_with_code(
+                    "wnfs_session_tree",
+                    WnfsErrorCode::InvalidArgument,
+                    "null session".to_string(),
+                )
+            }
+        };
+        let path_segments = prepare_path_segments(path_segments);
+        let (max_depth, max_entries) = tree_limits(max_depth, max_entries);
+        let output = prepare_tree_output(
+            session.helper(),
+            &path_segments,
+            max_depth,
+            max_entries,
+            format,
+            with_content,
+            on_entry.map(|on_entry| {
+                move |entry: Vec<u8>| {
+                    let entry = OwnedRustBytes::new(entry);
+                    on_entry(userdata, entry.get());
+                }
+            }),
+        );
+        match output {
+            Ok(output) => RustResult::ok(output.into()),
+            Err(msg) => error_result("wnfs_session_tree", msg),
+        }
+    }
+
+    /// Opens a directory shared by `share_native` from its base64 encoded
+    /// access key and a root CID of the drive, either the one `share_native` returned
+    /// or a later one. The shared directory is the root of the returned
+    /// session, which is read-only: every `wnfs_session_*` mutation on it
+    /// fails with `ReadOnly`. Free it with `wnfs_session_close`.
+    #[no_mangle]
+    pub unsafe extern "C" fn load_shared_native(
+        block_store_interface: BlockStoreInterface,
+        extensions: *const BlockStoreExtensions,
+        access_key: RustString,
+        cid: RustString,
+    ) -> RustResult<RustSession> {
+        trace!("**********************load_shared_native started**************");
+        let store = match bridged_store(block_store_interface, extensions, "load_shared_native") {
+            Ok(store) => store,
+            Err(err) => return err,
+        };
+        let access_key = match decode_share_key(&String::from(access_key)) {
+            Ok(access_key) => access_key,
+            Err(msg) => {
+                return error_result_with_code(
+                    "load_shared_native",
+                    WnfsErrorCode::InvalidArgument,
+                    msg,
+                )
+            }
+        };
+        let cid_res: Result<Cid, String> = cid.try_into();
+        if cid_res.is_err() {
+            return error_result_with_code(
+                "load_shared_native",
+                WnfsErrorCode::InvalidCid,
+                cid_res.err().unwrap(),
+            );
+        }
+        let session_res = WnfsSession::open_shared(store, access_key, cid_res.unwrap());
+        trace!("**********************load_shared_native finished**************");
+        match session_res {
+            Ok(session) => RustResult::ok(session.into()),
+            Err(msg) => load_error_result("load_shared_native", msg),
+        }
+    }
+
+    /// Reads a file shared by `share_native` from its base64 encoded access
+    /// key and a root CID of the drive, either the one `share_native`
+    /// returned or a later one.
+    #[no_mangle]
+    pub unsafe extern "C" fn read_shared_file_native(
+        block_store_interface: BlockStoreInterface,
+        extensions: *const BlockStoreExtensions,
+        access_key: RustString,
+        cid: RustString,
+    ) -> RustResult<RustBytes> {
+        trace!("**********************read_shared_file_native started**************");
+        let store =
+            match bridged_store(block_store_interface, extensions, "read_shared_file_native") {
+                Ok(store) => store,
+                Err(err) => return err,
+            };
+        let access_key = match decode_share_key(&String::from(access_key))
+            .and_then(|access_key| decode_access_key(&access_key))
+        {
+            Ok(access_key) => access_key,
+            Err(msg) => {
+                return error_result_with_code(
+                    "read_shared_file_native",
+                    WnfsErrorCode::InvalidArgument,
+                    msg,
+                )
+            }
+        };
+        let cid_res: Result<Cid, String> = cid.try_into();
+        if cid_res.is_err() {
+            return error_result_with_code(
+                "read_shared_file_native",
+                WnfsErrorCode::InvalidCid,
+                cid_res.err().unwrap(),
+            );
+        }
+        let content = read_shared_file(store, cid_res.unwrap(), &access_key);
+        trace!("**********************read_shared_file_native finished**************");
+        match content {
+            Ok(content) => RustResult::ok(content.into()),
+            Err(msg) => error_result("read_shared_file_native", msg),
         }
     }
 }
@@ -0,0 +1,438 @@
+use std::ops::ControlFlow;
+
+use libipld::cbor::DagCborCodec;
+use libipld::codec::Codec;
+use libipld::Cid;
+use serde::Serialize;
+use wnfs::common::Metadata;
+use wnfsutils::private_forest::PrivateDirectoryHelper;
+
+use crate::attrs::{user_meta, NodeMeta};
+use crate::c_types::{prepare_ls_output, OutputFormat};
+use crate::node;
+
+/// Read-only operations shared by private and public directories, so that
+/// listings and walks work the same over both.
+pub trait DirectoryReader {
+    fn ls_files(&mut self, path_segments: &[String]) -> Result<Vec<(String, Metadata)>, String>;
+    fn read_file(&mut self, path_segments: &[String]) -> Result<Vec<u8>, String>;
+    /// Kind and CID of the node at `path_segments`, and with `with_content`
+    /// its content length, without reading the file body.
+    fn node_info(
+        &mut self,
+        path_segments: &[String],
+        with_content: bool,
+    ) -> Result<NodeInfo, String>;
+    /// Metadata and `node_info` of the node at `path_segments`, or `None`
+    /// when nothing is there, including below a file.
+    fn lookup(
+        &mut self,
+        path_segments: &[String],
+        with_content: bool,
+    ) -> Result<Option<(Metadata, NodeInfo)>, String>;
+    /// Name, metadata and `node_info` of every child of a directory, each
+    /// child loaded once.
+    fn entries(
+        &mut self,
+        path_segments: &[String],
+        with_content: bool,
+    ) -> Result<Vec<(String, Metadata, NodeInfo)>, String>;
+}
+
+impl<'a> DirectoryReader for PrivateDirectoryHelper<'a> {
+    fn ls_files(&mut self, path_segments: &[String]) -> Result<Vec<(String, Metadata)>, String> {
+        self.synced_ls_files(path_segments)
+    }
+
+    fn read_file(&mut self, path_segments: &[String]) -> Result<Vec<u8>, String> {
+        self.synced_read_file(path_segments)
+    }
+
+    fn node_info(
+        &mut self,
+        path_segments: &[String],
+        with_content: bool,
+    ) -> Result<NodeInfo, String> {
+        node::node_info(self, path_segments, with_content)
+    }
+
+    fn lookup(
+        &mut self,
+        path_segments: &[String],
+        with_content: bool,
+    ) -> Result<Option<(Metadata, NodeInfo)>, String> {
+        node::lookup(self, path_segments, with_content)
+    }
+
+    fn entries(
+        &mut self,
+        path_segments: &[String],
+        with_content: bool,
+    ) -> Result<Vec<(String, Metadata, NodeInfo)>, String> {
+        node::dir_entries(self, path_segments, with_content)
+    }
+}
+
+/// Bumped whenever a field is removed or changes meaning; new optional
+/// fields do not bump it.
+pub const LS_OUTPUT_VERSION: u32 = 1;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryKind {
+    File,
+    Dir,
+    Symlink,
+}
+
+/// What a directory knows about one of its entries.
+#[derive(Clone, Debug)]
+pub struct NodeInfo {
+    pub kind: EntryKind,
+    /// CID of the node's own block.
+    pub cid: Option<Cid>,
+    /// Content length of a file, only set when content info was asked for.
+    pub size: Option<u64>,
+    /// Target path of a symlink.
+    pub target: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct LsEntry {
+    pub name: String,
+    pub kind: EntryKind,
+    /// Content length in bytes. Only set for files, and in walks only when
+    /// content info was requested; `ls` always sets it.
+    pub size: Option<u64>,
+    /// Seconds since the unix epoch.
+    pub created: Option<i64>,
+    pub modified: Option<i64>,
+    /// CID of the file or directory node, which changes with every revision
+    /// of it.
+    pub cid: Option<String>,
+    /// Target path of a symlink.
+    pub target: Option<String>,
+    /// User metadata, only present when it was requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<NodeMeta>,
+}
+
+#[derive(Serialize)]
+struct LsOutput<'e> {
+    version: u32,
+    entries: &'e [LsEntry],
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct StatOutput {
+    pub version: u32,
+    pub exists: bool,
+    pub kind: Option<EntryKind>,
+    pub size: Option<u64>,
+    pub created: Option<i64>,
+    pub modified: Option<i64>,
+    pub cid: Option<String>,
+    pub target: Option<String>,
+}
+
+impl StatOutput {
+    fn missing() -> StatOutput {
+        StatOutput {
+            version: LS_OUTPUT_VERSION,
+            exists: false,
+            kind: None,
+            size: None,
+            created: None,
+            modified: None,
+            cid: None,
+            target: None,
+        }
+    }
+}
+
+impl From<LsEntry> for StatOutput {
+    fn from(entry: LsEntry) -> StatOutput {
+        StatOutput {
+            version: LS_OUTPUT_VERSION,
+            exists: true,
+            kind: Some(entry.kind),
+            size: entry.size,
+            created: entry.created,
+            modified: entry.modified,
+            cid: entry.cid,
+            target: entry.target,
+        }
+    }
+}
+
+pub fn child_path(path_segments: &[String], name: &str) -> Vec<String> {
+    let mut path = path_segments.to_vec();
+    path.push(name.to_string());
+    path
+}
+
+pub fn build_entry(name: String, metadata: &Metadata, info: &NodeInfo) -> LsEntry {
+    LsEntry {
+        name,
+        kind: info.kind,
+        size: info.size,
+        created: metadata.get_created().map(|t| t.timestamp()),
+        modified: metadata.get_modified().map(|t| t.timestamp()),
+        cid: info.cid.map(|cid| cid.to_string()),
+        target: info.target.clone(),
+        meta: None,
+    }
+}
+
+/// Looks up the node at `path_segments` to set `size` and `cid` on its
+/// entry.
+pub fn fill_content<R: DirectoryReader + ?Sized>(
+    helper: &mut R,
+    path_segments: &[String],
+    entry: &mut LsEntry,
+) -> Result<(), String> {
+    let info = helper.node_info(path_segments, true)?;
+    entry.size = info.size;
+    entry.cid = info.cid.map(|cid| cid.to_string());
+    Ok(())
+}
+
+/// Entries of a directory, read in one pass over its children. User
+/// metadata is only decoded with `with_meta` set.
+pub fn collect_ls_entries<R: DirectoryReader + ?Sized>(
+    helper: &mut R,
+    path_segments: &[String],
+    with_content: bool,
+    with_meta: bool,
+) -> Result<Vec<LsEntry>, String> {
+    let children = helper.entries(path_segments, with_content)?;
+    let mut entries = Vec::with_capacity(children.len());
+    for (name, metadata, info) in children {
+        let mut entry = build_entry(name, &metadata, &info);
+        if with_meta {
+            entry.meta = Some(user_meta(&metadata)?);
+        }
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Encodes any serializable payload as JSON or DAG-CBOR. `Legacy` has no
+/// generic encoding and is rejected.
+pub fn encode_output<T: Serialize>(value: &T, format: OutputFormat) -> Result<Vec<u8>, String> {
+    match format {
+        OutputFormat::Json => serde_json::to_vec(value).map_err(|e| e.to_string()),
+        OutputFormat::Cbor => {
+            let ipld = libipld::serde::to_ipld(value).map_err(|e| e.to_string())?;
+            DagCborCodec.encode(&ipld).map_err(|e| e.to_string())
+        }
+        OutputFormat::Legacy => Err("legacy format is only supported by ls".to_string()),
+    }
+}
+
+/// Lists the directory at `path_segments` as `format`. Every entry carries
+/// its size and CID, and with `with_meta` its user metadata.
+pub fn prepare_ls_output_with_format<R: DirectoryReader + ?Sized>(
+    helper: &mut R,
+    path_segments: &[String],
+    format: OutputFormat,
+    with_meta: bool,
+) -> Result<Vec<u8>, String> {
+    if format == OutputFormat::Legacy {
+        return prepare_ls_output(helper.ls_files(path_segments)?);
+    }
+    let entries = collect_ls_entries(helper, path_segments, true, with_meta)?;
+    encode_output(
+        &LsOutput {
+            version: LS_OUTPUT_VERSION,
+            entries: &entries,
+        },
+        format,
+    )
+}
+
+/// Looks a single path up, one segment at a time. A missing path, or one
+/// below a missing directory or a file, is reported with `exists: false`
+/// rather than as an error.
+///
+/// `size` is only filled in when `with_content` is set. Neither it nor `cid`
+/// needs the file body: the CID is the node's own and the size comes from
+/// the file header, decrypting at most the last block.
+pub fn stat_path<R: DirectoryReader + ?Sized>(
+    helper: &mut R,
+    path_segments: &[String],
+    with_content: bool,
+) -> Result<StatOutput, String> {
+    match helper.lookup(path_segments, with_content)? {
+        Some((metadata, info)) => {
+            let name = path_segments.last().cloned().unwrap_or_default();
+            Ok(build_entry(name, &metadata, &info).into())
+        }
+        None => Ok(StatOutput::missing()),
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct TreeEntry {
+    /// `/` separated path relative to the walk root.
+    pub path: String,
+    /// 1 for direct children of the walk root.
+    pub depth: u32,
+    #[serde(flatten)]
+    pub entry: LsEntry,
+}
+
+/// Bumped whenever a field of `TreeOutput`, which `tree` and `find` both
+/// return, is removed or changes meaning. Entries are `LsEntry` and follow
+/// `LS_OUTPUT_VERSION`.
+pub const TREE_OUTPUT_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+pub struct TreeOutput {
+    pub version: u32,
+    pub count: usize,
+    /// Set when `max_entries` stopped the walk before the whole tree was seen.
+    pub truncated: bool,
+    /// Empty when the entries were streamed instead.
+    pub entries: Vec<TreeEntry>,
+}
+
+struct TreeWalk<'f, F> {
+    root_len: usize,
+    max_depth: Option<u32>,
+    max_entries: Option<usize>,
+    with_content: bool,
+    count: usize,
+    truncated: bool,
+    on_entry: &'f mut F,
+}
+
+impl<'f, F> TreeWalk<'f, F>
+where
+    F: FnMut(TreeEntry) -> Result<ControlFlow<()>, String>,
+{
+    fn walk<R: DirectoryReader + ?Sized>(
+        &mut self,
+        helper: &mut R,
+        dir_path: &[String],
+        depth: u32,
+    ) -> Result<(), String> {
+        for (name, metadata, info) in helper.entries(dir_path, self.with_content)? {
+            if self.is_full() {
+                return Ok(());
+            }
+            let entry_path = child_path(dir_path, &name);
+            let entry = build_entry(name, &metadata, &info);
+            let kind = entry.kind;
+            self.emit(&entry_path, depth, entry)?;
+            if self.truncated {
+                return Ok(());
+            }
+            if kind == EntryKind::Dir && self.max_depth.map_or(true, |max| depth < max) {
+                self.walk(helper, &entry_path, depth + 1)?;
+                if self.truncated {
+                    return Ok(());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Marks the walk truncated once `max_entries` entries were emitted.
+    fn is_full(&mut self) -> bool {
+        if self.max_entries.map_or(false, |max| self.count >= max) {
+            self.truncated = true;
+        }
+        self.truncated
+    }
+
+    /// Hands the entry over, ending the walk as truncated if `on_entry`
+    /// asks to stop.
+    fn emit(&mut self, entry_path: &[String], depth: u32, entry: LsEntry) -> Result<(), String> {
+        let flow = (self.on_entry)(TreeEntry {
+            path: entry_path[self.root_len..].join("/"),
+            depth,
+            entry,
+        })?;
+        if flow.is_break() {
+            self.truncated = true;
+        } else {
+            self.count += 1;
+        }
+        Ok(())
+    }
+}
+
+/// Depth-first, pre-order walk of everything below `path_segments`, passing
+/// each entry to `on_entry` as soon as it is built. Returns the number of
+/// entries and whether `max_entries`, or `on_entry` returning
+/// `ControlFlow::Break`, cut the walk short. The entry `on_entry` stopped on
+/// is not counted.
+pub fn walk_tree<R, F>(
+    helper: &mut R,
+    path_segments: &[String],
+    max_depth: Option<u32>,
+    max_entries: Option<usize>,
+    with_content: bool,
+    mut on_entry: F,
+) -> Result<(usize, bool), String>
+where
+    R: DirectoryReader + ?Sized,
+    F: FnMut(TreeEntry) -> Result<ControlFlow<()>, String>,
+{
+    let mut tree_walk = TreeWalk {
+        root_len: path_segments.len(),
+        max_depth,
+        max_entries,
+        with_content,
+        count: 0,
+        truncated: false,
+        on_entry: &mut on_entry,
+    };
+    tree_walk.walk(helper, path_segments, 1)?;
+    Ok((tree_walk.count, tree_walk.truncated))
+}
+
+/// Walks the tree and encodes it as a `TreeOutput`. With `on_entry` set,
+/// every entry is encoded and handed to it instead of being collected.
+pub fn prepare_tree_output<R, F>(
+    helper: &mut R,
+    path_segments: &[String],
+    max_depth: Option<u32>,
+    max_entries: Option<usize>,
+    format: OutputFormat,
+    with_content: bool,
+    mut on_entry: Option<F>,
+) -> Result<Vec<u8>, String>
+where
+    R: DirectoryReader + ?Sized,
+    F: FnMut(Vec<u8>),
+{
+    if format == OutputFormat::Legacy {
+        return Err("legacy format is only supported by ls".to_string());
+    }
+    let mut entries = Vec::new();
+    let (count, truncated) = walk_tree(
+        helper,
+        path_segments,
+        max_depth,
+        max_entries,
+        with_content,
+        |entry| {
+            match on_entry.as_mut() {
+                Some(on_entry) => on_entry(encode_output(&entry, format)?),
+                None => entries.push(entry),
+            }
+            Ok(ControlFlow::Continue(()))
+        },
+    )?;
+    encode_output(
+        &TreeOutput {
+            version: TREE_OUTPUT_VERSION,
+            count,
+            truncated,
+            entries,
+        },
+        format,
+    )
+}
@@ -0,0 +1,311 @@
+use std::collections::BTreeSet;
+
+use libipld::Cid;
+use log::trace;
+use serde::Serialize;
+use wnfsutils::private_forest::PrivateDirectoryHelper;
+
+use crate::attrs::{copy_user_meta, create_symlink, unlink};
+use crate::blockstore::BridgedStore;
+use crate::diff::{diff_snapshots, entry_changed, snapshot, ChangeKind, TreeSnapshot};
+use crate::ios::load_helper;
+use crate::listing::{EntryKind, LsEntry};
+use crate::node::{copy_file, make_dir_with_time, same_content, store_root};
+
+/// Label used in conflict copy names when the caller gives none.
+pub const DEFAULT_CONFLICT_LABEL: &str = "conflict";
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MergeAction {
+    /// Created on their side only and copied over.
+    Added,
+    /// Removed on their side and unchanged on ours.
+    Removed,
+    /// Changed on their side and unchanged on ours.
+    Updated,
+    /// Changed differently on both sides; their version was saved next to
+    /// ours under `conflict_path`.
+    Conflict,
+    /// Removed on their side but changed on ours; our version was kept.
+    Kept,
+    /// Removed on our side but changed on theirs; their version was restored.
+    Restored,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct MergeEntry {
+    pub path: String,
+    pub action: MergeAction,
+    pub kind: Option<EntryKind>,
+    pub conflict_path: Option<String>,
+}
+
+/// Bumped whenever a field of `MergeOutput` is removed or changes meaning.
+pub const MERGE_OUTPUT_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+pub struct MergeOutput {
+    pub version: u32,
+    /// Merged root CID.
+    pub cid: String,
+    pub actions: Vec<MergeEntry>,
+}
+
+fn segments(path: &str) -> Vec<String> {
+    path.split('/').map(String::from).collect()
+}
+
+fn is_below(path: &str, prefix: &str) -> bool {
+    path.len() > prefix.len() && path.starts_with(prefix) && path[prefix.len()..].starts_with('/')
+}
+
+/// `notes.txt` becomes `notes (label).txt`, then `notes (label 2).txt` and
+/// so on until the path is free.
+fn conflict_path(path: &str, label: &str, taken: &BTreeSet<String>) -> String {
+    let (parent, name) = match path.rfind('/') {
+        Some(i) => (&path[..=i], &path[i + 1..]),
+        None => ("", path),
+    };
+    let (stem, ext) = match name.rfind('.') {
+        Some(i) if i > 0 => (&name[..i], &name[i..]),
+        _ => (name, ""),
+    };
+    let mut n = 1;
+    loop {
+        let tag = if n == 1 {
+            label.to_string()
+        } else {
+            format!("{} {}", label, n)
+        };
+        let candidate = format!("{}{} ({}){}", parent, stem, tag, ext);
+        if !taken.contains(&candidate) {
+            return candidate;
+        }
+        n += 1;
+    }
+}
+
+struct Merger<'h> {
+    ours: &'h mut PrivateDirectoryHelper<'static>,
+    theirs: &'h mut PrivateDirectoryHelper<'static>,
+    cid: Cid,
+    taken: BTreeSet<String>,
+    actions: Vec<MergeEntry>,
+}
+
+impl<'h> Merger<'h> {
+    /// Copies their node at `path` to `target`, which is free, together
+    /// with its user metadata, and stores the new root.
+    fn copy_theirs(&mut self, path: &str, entry: &LsEntry, target: &str) -> Result<(), String> {
+        let (source, target_segments) = (segments(path), segments(target));
+        match entry.kind {
+            EntryKind::Dir => make_dir_with_time(self.ours, &target_segments, 0)?,
+            EntryKind::Symlink => create_symlink(
+                self.ours,
+                &target_segments,
+                entry.target.as_deref().unwrap_or_default(),
+            )?,
+            EntryKind::File => copy_file(
+                self.theirs,
+                &source,
+                self.ours,
+                &target_segments,
+                entry.modified,
+            )?,
+        }
+        copy_user_meta(self.theirs, &source, self.ours, &target_segments)?;
+        self.cid = store_root(self.ours)?;
+        self.taken.insert(target.to_string());
+        Ok(())
+    }
+
+    /// Whether their file at `path` and ours at `target` hold the same bytes.
+    /// Node CIDs differ as soon as both sides wrote a file, even with the
+    /// same content, so this is checked before reporting a conflict.
+    fn same_content(&self, path: &str, target: &str) -> Result<bool, String> {
+        same_content(self.theirs, &segments(path), self.ours, &segments(target))
+    }
+
+    fn remove_ours(&mut self, target: &str, kind: EntryKind) -> Result<(), String> {
+        self.cid = match kind {
+            EntryKind::Symlink => unlink(self.ours, &segments(target))?,
+            _ => self.ours.synced_rm(&segments(target))?,
+        };
+        self.taken.remove(target);
+        Ok(())
+    }
+
+    fn record(
+        &mut self,
+        path: &str,
+        action: MergeAction,
+        kind: Option<EntryKind>,
+        conflict_path: Option<String>,
+    ) {
+        self.actions.push(MergeEntry {
+            path: path.to_string(),
+            action,
+            kind,
+            conflict_path,
+        });
+    }
+}
+
+/// Whether anything at or below `path` differs between `base` and `ours`.
+fn changed_below(path: &str, base: &TreeSnapshot, ours: &TreeSnapshot) -> bool {
+    ours.iter()
+        .filter(|(p, _)| is_below(p, path))
+        .any(|(p, o)| base.get(p).map_or(true, |b| entry_changed(b, o)))
+        || base
+            .keys()
+            .filter(|p| is_below(p, path))
+            .any(|p| !ours.contains_key(p))
+}
+
+fn unchanged(base: Option<&LsEntry>, ours: Option<&LsEntry>) -> bool {
+    match (base, ours) {
+        (Some(b), Some(o)) => !entry_changed(b, o),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+/// Replays the changes made on `theirs` since `base` onto `ours` and returns
+/// the merged root with a report of every action taken.
+///
+/// Files are compared by the CID of their node, which only stays the same
+/// while a file is left alone, and copies keep their modification time and
+/// user metadata. When both sides changed the same file differently, which
+/// is only checked by comparing both contents once their CIDs differ, ours
+/// stays in place and theirs is saved next to it with `conflict_label` in
+/// its name. The same goes for a directory replaced on their side while
+/// something below it changed on ours. A path removed on one side and
+/// changed on the other keeps the changed version.
+///
+/// Metadata is not merged on its own. An edit to a file's metadata gives it
+/// a new CID and is taken over with the file when ours is unchanged, but
+/// when both sides end up with the same content ours is kept with its
+/// metadata. Metadata edits to directories and symlinks are not seen as
+/// changes at all.
+pub fn merge_roots(
+    store: BridgedStore,
+    base: Cid,
+    ours: Cid,
+    theirs: Cid,
+    conflict_label: &str,
+) -> Result<MergeOutput, String> {
+    let label = if conflict_label.is_empty() {
+        DEFAULT_CONFLICT_LABEL
+    } else {
+        conflict_label
+    };
+    let base_tree = snapshot(&mut load_helper(store.to_owned(), base)?, &[], true)?;
+    let theirs_helper = &mut load_helper(store.to_owned(), theirs)?;
+    let theirs_tree = snapshot(theirs_helper, &[], true)?;
+    let ours_helper = &mut load_helper(store, ours)?;
+    let ours_tree = snapshot(ours_helper, &[], true)?;
+
+    let mut merger = Merger {
+        ours: ours_helper,
+        theirs: theirs_helper,
+        cid: ours,
+        taken: ours_tree.keys().cloned().collect(),
+        actions: Vec::new(),
+    };
+    // Directories removed from ours, whose descendants need no more work,
+    // and their directories that went to a conflict path, whose descendants
+    // follow them there.
+    let mut removed: Vec<String> = Vec::new();
+    let mut redirected: Vec<(String, String)> = Vec::new();
+
+    diff_snapshots(&base_tree, &theirs_tree)
+        .into_iter()
+        .try_for_each(|change| {
+            let path = change.path.as_str();
+            if removed.iter().any(|prefix| is_below(path, prefix)) {
+                return Ok(());
+            }
+            let target = match redirected.iter().find(|(from, _)| is_below(path, from)) {
+                Some((from, to)) => format!("{}{}", to, &path[from.len()..]),
+                None => path.to_string(),
+            };
+            let base_entry = base_tree.get(path);
+            let ours_entry = ours_tree.get(&target).filter(|_| target == path);
+            match (change.change, theirs_tree.get(path)) {
+                (ChangeKind::Added, Some(theirs_entry))
+                | (ChangeKind::Modified, Some(theirs_entry)) => {
+                    let kind = theirs_entry.kind;
+                    match ours_entry {
+                        None => {
+                            merger.copy_theirs(path, theirs_entry, &target)?;
+                            let action = if base_entry.is_some() {
+                                MergeAction::Restored
+                            } else {
+                                MergeAction::Added
+                            };
+                            merger.record(&target, action, Some(kind), None);
+                        }
+                        Some(ours_entry) if !entry_changed(ours_entry, theirs_entry) => {}
+                        Some(ours_entry)
+                            if unchanged(base_entry, Some(ours_entry))
+                                && !(ours_entry.kind == EntryKind::Dir
+                                    && changed_below(&target, &base_tree, &ours_tree)) =>
+                        {
+                            if ours_entry.kind != kind || kind == EntryKind::Symlink {
+                                merger.remove_ours(&target, ours_entry.kind)?;
+                                if ours_entry.kind == EntryKind::Dir {
+                                    removed.push(target.to_owned());
+                                }
+                            }
+                            merger.copy_theirs(path, theirs_entry, &target)?;
+                            merger.record(&target, MergeAction::Updated, Some(kind), None);
+                        }
+                        Some(ours_entry)
+                            if kind == EntryKind::File
+                                && ours_entry.kind == EntryKind::File
+                                && merger.same_content(path, &target)? => {}
+                        Some(_) => {
+                            let copy = conflict_path(&target, label, &merger.taken);
+                            merger.copy_theirs(path, theirs_entry, &copy)?;
+                            if kind == EntryKind::Dir {
+                                redirected.push((path.to_string(), copy.to_owned()));
+                            }
+                            merger.record(&target, MergeAction::Conflict, Some(kind), Some(copy));
+                        }
+                    }
+                }
+                (ChangeKind::Removed, _) => match ours_entry {
+                    None => {}
+                    Some(ours_entry) => {
+                        let kind = ours_entry.kind;
+                        let keep = !unchanged(base_entry, Some(ours_entry))
+                            || (kind == EntryKind::Dir
+                                && changed_below(path, &base_tree, &ours_tree));
+                        if keep {
+                            merger.record(&target, MergeAction::Kept, Some(kind), None);
+                        } else {
+                            merger.remove_ours(&target, kind)?;
+                            removed.push(target.to_owned());
+                            merger.record(&target, MergeAction::Removed, Some(kind), None);
+                        }
+                    }
+                },
+                _ => {}
+            }
+            Ok::<(), String>(())
+        })?;
+    trace!(
+        "merged {} and {} over {} into {}: {} actions",
+        ours,
+        theirs,
+        base,
+        merger.cid,
+        merger.actions.len()
+    );
+    Ok(MergeOutput {
+        version: MERGE_OUTPUT_VERSION,
+        cid: merger.cid.to_string(),
+        actions: merger.actions,
+    })
+}
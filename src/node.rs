@@ -0,0 +1,561 @@
+use std::io::{self, Read};
+use std::rc::Rc;
+
+use chrono::{DateTime, TimeZone, Utc};
+use futures::io::{AllowStdIo, AsyncRead, AsyncReadExt};
+use futures::{StreamExt, TryStreamExt};
+use libipld::cbor::DagCborCodec;
+use libipld::codec::Codec;
+use libipld::{Cid, Ipld};
+use wnfs::common::{BlockStore, Metadata};
+use wnfs::private::{
+    AccessKey, PrivateFile, PrivateForest, PrivateNode, PrivateRef, TemporalAccessKey,
+    MAX_BLOCK_CONTENT_SIZE,
+};
+use wnfsutils::blockstore::FFIFriendlyBlockStore;
+use wnfsutils::private_forest::PrivateDirectoryHelper;
+
+use crate::attrs::symlink_target;
+use crate::blockstore::BridgedStore;
+use crate::listing::{EntryKind, NodeInfo};
+use crate::runtime::block_on;
+
+/// Looks up the node at `path_segments` below the helper's root directory.
+/// The empty path is the root itself.
+pub async fn get_node(
+    helper: &PrivateDirectoryHelper<'_>,
+    path_segments: &[String],
+) -> Result<PrivateNode, String> {
+    if path_segments.is_empty() {
+        return Ok(PrivateNode::Dir(Rc::clone(&helper.root_dir)));
+    }
+    helper
+        .root_dir
+        .get_node(path_segments, true, &helper.forest, &helper.store)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("{} not found", path_segments.join("/")))
+}
+
+pub async fn get_file(
+    helper: &PrivateDirectoryHelper<'_>,
+    path_segments: &[String],
+) -> Result<Rc<PrivateFile>, String> {
+    match get_node(helper, path_segments).await? {
+        PrivateNode::File(file) => Ok(file),
+        PrivateNode::Dir(_) => Err(format!("{} is a directory", path_segments.join("/"))),
+    }
+}
+
+/// Node at `path_segments`, or `None` when nothing is there, including when
+/// one of the segments above it is a file. Each segment is looked up in its
+/// parent directly, without listing any directory.
+pub async fn find_node(
+    helper: &PrivateDirectoryHelper<'_>,
+    path_segments: &[String],
+) -> Result<Option<PrivateNode>, String> {
+    let mut node = PrivateNode::Dir(Rc::clone(&helper.root_dir));
+    for segment in path_segments {
+        let dir = match node {
+            PrivateNode::Dir(dir) => dir,
+            PrivateNode::File(_) => return Ok(None),
+        };
+        node = match dir
+            .lookup_node(segment, true, &helper.forest, &helper.store)
+            .await
+            .map_err(|e| e.to_string())?
+        {
+            Some(child) => child,
+            None => return Ok(None),
+        };
+    }
+    Ok(Some(node))
+}
+
+pub fn node_metadata(node: &PrivateNode) -> &Metadata {
+    match node {
+        PrivateNode::File(file) => file.get_metadata(),
+        PrivateNode::Dir(dir) => dir.get_metadata(),
+    }
+}
+
+/// CID of the encrypted block of `node`. Nodes loaded from the forest
+/// remember where they were stored, so nothing is re-encrypted; the forest
+/// is cloned so that the helper's own is left as it was.
+pub async fn node_cid(
+    node: &PrivateNode,
+    forest: &Rc<PrivateForest>,
+    store: &impl BlockStore,
+) -> Result<Cid, String> {
+    let private_ref = node
+        .store(&mut Rc::clone(forest), store, &mut rand::thread_rng())
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(private_ref.content_cid)
+}
+
+/// Exact content length of `file`. Its header only records how many blocks
+/// it has, so at most the last block is decrypted to see how full it is.
+pub async fn file_size(
+    file: &PrivateFile,
+    forest: &PrivateForest,
+    store: &impl BlockStore,
+) -> Result<u64, String> {
+    let upper_bound = file.get_content_size_upper_bound();
+    if upper_bound % MAX_BLOCK_CONTENT_SIZE != 0 || upper_bound == 0 {
+        // Inline content, whose length is exact.
+        return Ok(upper_bound as u64);
+    }
+    let last_block = upper_bound / MAX_BLOCK_CONTENT_SIZE - 1;
+    let mut blocks = Box::pin(file.stream_content(last_block, forest, store));
+    let last_len = match blocks.next().await {
+        Some(block) => block.map_err(|e| e.to_string())?.len(),
+        None => 0,
+    };
+    Ok((last_block * MAX_BLOCK_CONTENT_SIZE + last_len) as u64)
+}
+
+/// `NodeInfo` of a node already loaded. Symlinks are files to wnfs and
+/// are told apart by their metadata.
+pub async fn info(
+    node: &PrivateNode,
+    with_content: bool,
+    forest: &Rc<PrivateForest>,
+    store: &impl BlockStore,
+) -> Result<NodeInfo, String> {
+    let target = symlink_target(node_metadata(node));
+    let (kind, size) = match node {
+        _ if target.is_some() => (EntryKind::Symlink, None),
+        PrivateNode::Dir(_) => (EntryKind::Dir, None),
+        PrivateNode::File(file) if with_content => {
+            (EntryKind::File, Some(file_size(file, forest, store).await?))
+        }
+        PrivateNode::File(_) => (EntryKind::File, None),
+    };
+    Ok(NodeInfo {
+        kind,
+        cid: Some(node_cid(node, forest, store).await?),
+        size,
+        target,
+    })
+}
+
+/// Kind of the node at `path_segments`, with its CID and content length
+/// when `with_content` is set.
+pub fn node_info(
+    helper: &PrivateDirectoryHelper,
+    path_segments: &[String],
+    with_content: bool,
+) -> Result<NodeInfo, String> {
+    block_on(async {
+        let node = get_node(helper, path_segments).await?;
+        info(&node, with_content, &helper.forest, &helper.store).await
+    })
+}
+
+/// Metadata and `NodeInfo` of the node at `path_segments`, or `None` as in
+/// `find_node`.
+pub fn lookup(
+    helper: &PrivateDirectoryHelper,
+    path_segments: &[String],
+    with_content: bool,
+) -> Result<Option<(Metadata, NodeInfo)>, String> {
+    block_on(async {
+        let node = match find_node(helper, path_segments).await? {
+            Some(node) => node,
+            None => return Ok(None),
+        };
+        let info = info(&node, with_content, &helper.forest, &helper.store).await?;
+        Ok(Some((node_metadata(&node).to_owned(), info)))
+    })
+}
+
+/// Name, metadata and `NodeInfo` of every child of the directory at
+/// `path_segments`. Each child is looked up once, by name, and everything is
+/// taken from that node.
+pub fn dir_entries(
+    helper: &PrivateDirectoryHelper,
+    path_segments: &[String],
+    with_content: bool,
+) -> Result<Vec<(String, Metadata, NodeInfo)>, String> {
+    block_on(async {
+        let dir = match get_node(helper, path_segments).await? {
+            PrivateNode::Dir(dir) => dir,
+            PrivateNode::File(_) => {
+                return Err(format!("{} is not a directory", path_segments.join("/")))
+            }
+        };
+        let (forest, store) = (&helper.forest, &helper.store);
+        let mut entries = Vec::new();
+        for name in dir.get_entries() {
+            let child = dir
+                .lookup_node(name, true, forest, store)
+                .await
+                .map_err(|e| e.to_string())?
+                .ok_or_else(|| format!("{} not found", name))?;
+            let info = info(&child, with_content, forest, store).await?;
+            entries.push((name.to_owned(), node_metadata(&child).to_owned(), info));
+        }
+        Ok(entries)
+    })
+}
+
+/// Access key of the node at `path_segments`: its temporal key and where it
+/// lives in the forest. It opens that node, its descendants and their later
+/// revisions, and nothing above them.
+pub fn access_key(
+    helper: &PrivateDirectoryHelper,
+    path_segments: &[String],
+) -> Result<AccessKey, String> {
+    block_on(async {
+        let node = get_node(helper, path_segments).await?;
+        let private_ref = node
+            .store(
+                &mut Rc::clone(&helper.forest),
+                &helper.store,
+                &mut rand::thread_rng(),
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(AccessKey::Temporal(TemporalAccessKey::from(&private_ref)))
+    })
+}
+
+pub fn encode_access_key(access_key: &AccessKey) -> Result<Vec<u8>, String> {
+    let ipld = libipld::serde::to_ipld(access_key).map_err(|e| e.to_string())?;
+    DagCborCodec.encode(&ipld).map_err(|e| e.to_string())
+}
+
+pub fn decode_access_key(bytes: &[u8]) -> Result<AccessKey, String> {
+    let ipld: Ipld = DagCborCodec
+        .decode(bytes)
+        .map_err(|e| format!("invalid access key: {}", e))?;
+    libipld::serde::from_ipld(ipld).map_err(|e| format!("invalid access key: {}", e))
+}
+
+/// Loads the node `access_key` opens from the forest at `cid`, at its
+/// latest revision in that forest.
+async fn load_shared_node(
+    store: &FFIFriendlyBlockStore<'static>,
+    cid: Cid,
+    access_key: &AccessKey,
+) -> Result<(Rc<PrivateForest>, PrivateNode), String> {
+    let forest: PrivateForest = store
+        .get_deserializable(&cid)
+        .await
+        .map_err(|e| e.to_string())?;
+    let forest = Rc::new(forest);
+    let node = PrivateNode::load(&PrivateRef::from(access_key), &forest, store)
+        .await
+        .map_err(|e| e.to_string())?
+        .search_latest(&forest, store)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok((forest, node))
+}
+
+/// Loads the directory `access_key` opens from the forest at `cid`, at its
+/// latest revision in that forest, as the root of a new helper.
+pub fn open_shared_dir(
+    store: BridgedStore,
+    cid: Cid,
+    access_key: &AccessKey,
+) -> Result<PrivateDirectoryHelper<'static>, String> {
+    let store = FFIFriendlyBlockStore::new(Box::new(store));
+    block_on(async {
+        let (forest, node) = load_shared_node(&store, cid, access_key).await?;
+        let root_dir = match node {
+            PrivateNode::Dir(dir) => dir,
+            PrivateNode::File(_) => {
+                return Err("the access key is a file's, read it with read_shared_file".to_string())
+            }
+        };
+        Ok(PrivateDirectoryHelper {
+            store,
+            forest,
+            root_dir,
+            rng: rand::thread_rng(),
+        })
+    })
+}
+
+/// Content of the file `access_key` opens in the forest at `cid`, at its
+/// latest revision in that forest.
+pub fn read_shared_file(
+    store: BridgedStore,
+    cid: Cid,
+    access_key: &AccessKey,
+) -> Result<Vec<u8>, String> {
+    let store = FFIFriendlyBlockStore::new(Box::new(store));
+    block_on(async {
+        let (forest, node) = load_shared_node(&store, cid, access_key).await?;
+        match node {
+            PrivateNode::File(file) => file
+                .get_content(&forest, &store)
+                .await
+                .map_err(|e| e.to_string()),
+            PrivateNode::Dir(_) => {
+                Err("the access key is a directory's, open it with load_shared".to_string())
+            }
+        }
+    })
+}
+
+/// `secs` since the unix epoch as a wnfs time, 0 meaning now as in
+/// wnfsutils' `synced_write_file`.
+fn time_from_secs(secs: i64) -> DateTime<Utc> {
+    match secs {
+        0 => Utc::now(),
+        secs => Utc.timestamp_opt(secs, 0).single().unwrap_or_else(Utc::now),
+    }
+}
+
+/// Writes what `reader` yields to the file at `path_segments`, created if
+/// needed, block by block instead of from one buffer, with `modified` as
+/// its modification time. Only the helper's root changes, in memory:
+/// `store_root` or any later wnfsutils write stores it.
+pub fn write_file_streaming(
+    helper: &mut PrivateDirectoryHelper,
+    path_segments: &[String],
+    reader: impl Read,
+    modified: i64,
+) -> Result<(), String> {
+    let time = time_from_secs(modified);
+    let PrivateDirectoryHelper {
+        store,
+        forest,
+        root_dir,
+        rng,
+        ..
+    } = helper;
+    block_on(async {
+        let file = root_dir
+            .open_file_mut(path_segments, true, time, &*forest, &*store, &mut *rng)
+            .await
+            .map_err(|e| e.to_string())?;
+        file.set_content(time, AllowStdIo::new(reader), forest, &*store, &mut *rng)
+            .await
+            .map_err(|e| e.to_string())
+    })
+}
+
+/// Writes the content of `source`, a file of `source_forest`, to the file at
+/// `path_segments`, created if needed, one block at a time, with `modified`
+/// as its modification time. Like `write_file_streaming`, the root is only
+/// changed in memory.
+pub fn copy_file_content(
+    helper: &mut PrivateDirectoryHelper,
+    path_segments: &[String],
+    source: &PrivateFile,
+    source_forest: &Rc<PrivateForest>,
+    modified: i64,
+) -> Result<(), String> {
+    let time = time_from_secs(modified);
+    let PrivateDirectoryHelper {
+        store,
+        forest,
+        root_dir,
+        rng,
+        ..
+    } = helper;
+    block_on(async {
+        let content = Box::pin(source.stream_content(0, source_forest, &*store))
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))
+            .into_async_read();
+        let file = root_dir
+            .open_file_mut(path_segments, true, time, &*forest, &*store, &mut *rng)
+            .await
+            .map_err(|e| e.to_string())?;
+        file.set_content(time, content, forest, &*store, &mut *rng)
+            .await
+            .map_err(|e| e.to_string())
+    })
+}
+
+/// Copies a single file between helpers, streaming its content one block at
+/// a time. `modified` is the source's modification time in seconds, kept on
+/// the copy; `None` stamps it with the current time. The source blocks are
+/// read through the target's store, so both helpers must share a host
+/// store. Only the target's root in memory changes: `store_root` stores it.
+pub fn copy_file(
+    source: &PrivateDirectoryHelper,
+    source_path: &[String],
+    target: &mut PrivateDirectoryHelper,
+    target_path: &[String],
+    modified: Option<i64>,
+) -> Result<(), String> {
+    let file = block_on(get_file(source, source_path))?;
+    copy_file_content(
+        target,
+        target_path,
+        &file,
+        &source.forest,
+        modified.unwrap_or(0),
+    )
+}
+
+/// Fills `buf` from `reader` unless it ends first, and returns how much
+/// was read.
+async fn read_full(reader: &mut (impl AsyncRead + Unpin), buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]).await? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+/// Whether the file at `path_a` in `a` and the one at `path_b` in `b` hold
+/// the same bytes. Content blocks are encrypted with a key of their own, so
+/// their CIDs never match; the lengths are compared first and then both
+/// files are decrypted side by side, one block at a time, until they differ.
+pub fn same_content(
+    a: &PrivateDirectoryHelper,
+    path_a: &[String],
+    b: &PrivateDirectoryHelper,
+    path_b: &[String],
+) -> Result<bool, String> {
+    block_on(async {
+        let file_a = get_file(a, path_a).await?;
+        let file_b = get_file(b, path_b).await?;
+        if file_size(&file_a, &a.forest, &a.store).await?
+            != file_size(&file_b, &b.forest, &b.store).await?
+        {
+            return Ok(false);
+        }
+        let mut reader_a = Box::pin(file_a.stream_content(0, &a.forest, &a.store))
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))
+            .into_async_read();
+        let mut reader_b = Box::pin(file_b.stream_content(0, &b.forest, &b.store))
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))
+            .into_async_read();
+        let mut buf_a = vec![0; MAX_BLOCK_CONTENT_SIZE];
+        let mut buf_b = vec![0; MAX_BLOCK_CONTENT_SIZE];
+        loop {
+            let read_a = read_full(&mut reader_a, &mut buf_a)
+                .await
+                .map_err(|e| e.to_string())?;
+            let read_b = read_full(&mut reader_b, &mut buf_b)
+                .await
+                .map_err(|e| e.to_string())?;
+            if buf_a[..read_a] != buf_b[..read_b] {
+                return Ok(false);
+            }
+            if read_a == 0 {
+                return Ok(true);
+            }
+        }
+    })
+}
+
+/// Creates the directory at `path_segments` and any missing parent with
+/// `modified` as their modification time. Like `write_file_streaming`, the
+/// root is only changed in memory.
+pub fn make_dir_with_time(
+    helper: &mut PrivateDirectoryHelper,
+    path_segments: &[String],
+    modified: i64,
+) -> Result<(), String> {
+    let time = time_from_secs(modified);
+    block_on(async {
+        helper
+            .root_dir
+            .mkdir(
+                path_segments,
+                true,
+                time,
+                &helper.forest,
+                &helper.store,
+                &mut helper.rng,
+            )
+            .await
+            .map_err(|e| e.to_string())
+    })
+}
+
+/// Applies `update` to the metadata of the node at `path_segments`, which
+/// must exist, as a new revision of it modified now unless `update` sets
+/// another time. Like `write_file_streaming`, the root is only changed in
+/// memory.
+pub fn update_metadata<F>(
+    helper: &mut PrivateDirectoryHelper,
+    path_segments: &[String],
+    update: F,
+) -> Result<(), String>
+where
+    F: FnOnce(&mut Metadata) -> Result<(), String>,
+{
+    let time = Utc::now();
+    block_on(async {
+        let is_file = matches!(get_node(helper, path_segments).await?, PrivateNode::File(_));
+        let PrivateDirectoryHelper {
+            store,
+            forest,
+            root_dir,
+            rng,
+            ..
+        } = helper;
+        let metadata = if is_file {
+            root_dir
+                .open_file_mut(path_segments, true, time, &*forest, &*store, &mut *rng)
+                .await
+                .map_err(|e| e.to_string())?
+                .get_metadata_mut()
+        } else {
+            root_dir
+                .get_or_create_leaf_dir_mut(path_segments, time, true, &*forest, &*store, &mut *rng)
+                .await
+                .map_err(|e| e.to_string())?
+                .get_metadata_mut()
+        };
+        metadata.upsert_mtime(time);
+        update(metadata)
+    })
+}
+
+/// Creates an empty file at `path_segments`, with its parents, and applies
+/// `update` to its metadata. Only the root in memory changes.
+pub fn create_file_with_metadata<F>(
+    helper: &mut PrivateDirectoryHelper,
+    path_segments: &[String],
+    update: F,
+) -> Result<(), String>
+where
+    F: FnOnce(&mut Metadata),
+{
+    let time = Utc::now();
+    block_on(async {
+        let PrivateDirectoryHelper {
+            store,
+            forest,
+            root_dir,
+            rng,
+            ..
+        } = helper;
+        let file = root_dir
+            .open_file_mut(path_segments, true, time, &*forest, &*store, &mut *rng)
+            .await
+            .map_err(|e| e.to_string())?;
+        update(file.get_metadata_mut());
+        Ok(())
+    })
+}
+
+/// Stores the helper's root directory and forest, as every wnfsutils write
+/// does after changing them, and returns the new root CID.
+pub fn store_root(helper: &mut PrivateDirectoryHelper) -> Result<Cid, String> {
+    block_on(async {
+        helper
+            .root_dir
+            .store(&mut helper.forest, &helper.store, &mut helper.rng)
+            .await
+            .map_err(|e| e.to_string())?;
+        helper
+            .store
+            .put_async_serializable(helper.forest.as_ref())
+            .await
+            .map_err(|e| e.to_string())
+    })
+}
@@ -0,0 +1,382 @@
+use std::rc::Rc;
+
+use chrono::Utc;
+use libipld::Cid;
+use log::trace;
+use wnfs::common::{BlockStore, Metadata};
+use wnfs::public::{PublicDirectory, PublicNode};
+use wnfsutils::blockstore::FFIFriendlyBlockStore;
+
+use crate::blockstore::BridgedStore;
+use crate::listing::{child_path, DirectoryReader, EntryKind, NodeInfo};
+use crate::runtime::block_on;
+use crate::unixfs;
+
+fn node_metadata(node: &PublicNode) -> &Metadata {
+    match node {
+        PublicNode::File(file) => file.get_metadata(),
+        PublicNode::Dir(dir) => dir.get_metadata(),
+    }
+}
+
+/// Public (unencrypted) counterpart of wnfsutils' `PrivateDirectoryHelper`.
+///
+/// File content is stored next to the directory blocks as a UnixFS file, so
+/// that other IPFS tools can read it from its content CID, and every
+/// mutation stores the updated root and returns its CID.
+pub struct PublicDirectoryHelper<'a> {
+    store: FFIFriendlyBlockStore<'a>,
+    /// Same host store with puts discarded, to compute node CIDs on read
+    /// paths without writing anything.
+    cid_store: FFIFriendlyBlockStore<'a>,
+    root_dir: Rc<PublicDirectory>,
+}
+
+impl<'a> PublicDirectoryHelper<'a> {
+    fn stores(store: BridgedStore) -> (FFIFriendlyBlockStore<'a>, FFIFriendlyBlockStore<'a>) {
+        let cid_store = FFIFriendlyBlockStore::new(Box::new(store.without_puts()));
+        (FFIFriendlyBlockStore::new(Box::new(store)), cid_store)
+    }
+
+    async fn init(store: BridgedStore) -> Result<(Self, Cid), String> {
+        let (mut store, cid_store) = Self::stores(store);
+        let root_dir = Rc::new(PublicDirectory::new(Utc::now()));
+        let cid = root_dir
+            .store(&mut store)
+            .await
+            .map_err(|e| e.to_string())?;
+        trace!("public directory initialized at cid={}", cid);
+        Ok((
+            PublicDirectoryHelper {
+                store,
+                cid_store,
+                root_dir,
+            },
+            cid,
+        ))
+    }
+
+    async fn load(store: BridgedStore, cid: Cid) -> Result<Self, String> {
+        let (store, cid_store) = Self::stores(store);
+        let root_dir: PublicDirectory = store
+            .get_deserializable(&cid)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(PublicDirectoryHelper {
+            store,
+            cid_store,
+            root_dir: Rc::new(root_dir),
+        })
+    }
+
+    async fn update_root(&mut self) -> Result<Cid, String> {
+        self.root_dir
+            .store(&mut self.store)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    async fn write_file(
+        &mut self,
+        path_segments: &[String],
+        content: Vec<u8>,
+    ) -> Result<Cid, String> {
+        let content_cid = unixfs::put_file(&mut self.store, &content).await?;
+        self.root_dir
+            .write(path_segments, content_cid, Utc::now(), &self.store)
+            .await
+            .map_err(|e| e.to_string())?;
+        self.update_root().await
+    }
+
+    async fn read_file(&mut self, path_segments: &[String]) -> Result<Vec<u8>, String> {
+        let content_cid = self
+            .root_dir
+            .read(path_segments, &self.store)
+            .await
+            .map_err(|e| e.to_string())?;
+        unixfs::read_file(&self.store, &content_cid).await
+    }
+
+    async fn mkdir(&mut self, path_segments: &[String]) -> Result<Cid, String> {
+        self.root_dir
+            .mkdir(path_segments, Utc::now(), &self.store)
+            .await
+            .map_err(|e| e.to_string())?;
+        self.update_root().await
+    }
+
+    async fn mv(
+        &mut self,
+        source_path_segments: &[String],
+        target_path_segments: &[String],
+    ) -> Result<Cid, String> {
+        self.root_dir
+            .basic_mv(
+                source_path_segments,
+                target_path_segments,
+                Utc::now(),
+                &self.store,
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+        self.update_root().await
+    }
+
+    /// Copies a file or a whole directory. Files link the same content
+    /// blocks under the new path, so nothing is duplicated.
+    async fn cp(
+        &mut self,
+        source_path_segments: &[String],
+        target_path_segments: &[String],
+    ) -> Result<Cid, String> {
+        if target_path_segments.starts_with(source_path_segments) {
+            return Err(format!(
+                "cannot copy {} into itself",
+                source_path_segments.join("/")
+            ));
+        }
+        let mut pending = vec![(source_path_segments.to_vec(), target_path_segments.to_vec())];
+        while let Some((source, target)) = pending.pop() {
+            match self.get_node(&source).await? {
+                PublicNode::File(file) => {
+                    let content_cid = *file.get_content_cid();
+                    self.root_dir
+                        .write(&target, content_cid, Utc::now(), &self.store)
+                        .await
+                        .map_err(|e| e.to_string())?;
+                }
+                PublicNode::Dir(dir) => {
+                    self.root_dir
+                        .mkdir(&target, Utc::now(), &self.store)
+                        .await
+                        .map_err(|e| e.to_string())?;
+                    for (name, _) in dir.ls(&[], &self.store).await.map_err(|e| e.to_string())? {
+                        pending.push((child_path(&source, &name), child_path(&target, &name)));
+                    }
+                }
+            }
+        }
+        self.update_root().await
+    }
+
+    async fn rm(&mut self, path_segments: &[String]) -> Result<Cid, String> {
+        self.root_dir
+            .rm(path_segments, &self.store)
+            .await
+            .map_err(|e| e.to_string())?;
+        self.update_root().await
+    }
+
+    async fn ls_files(
+        &mut self,
+        path_segments: &[String],
+    ) -> Result<Vec<(String, Metadata)>, String> {
+        self.root_dir
+            .ls(path_segments, &self.store)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    async fn get_node(&mut self, path_segments: &[String]) -> Result<PublicNode, String> {
+        if path_segments.is_empty() {
+            return Ok(PublicNode::Dir(Rc::clone(&self.root_dir)));
+        }
+        self.root_dir
+            .get_node(path_segments, &self.store)
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("{} not found", path_segments.join("/")))
+    }
+
+    async fn info(&mut self, node: &PublicNode, with_content: bool) -> Result<NodeInfo, String> {
+        let kind = match node {
+            PublicNode::Dir(_) => EntryKind::Dir,
+            PublicNode::File(_) => EntryKind::File,
+        };
+        let size = match node {
+            PublicNode::File(file) if with_content => {
+                Some(unixfs::file_size(&self.store, file.get_content_cid()).await?)
+            }
+            _ => None,
+        };
+        // The node was loaded from the store, so hashing it again gives the
+        // CID it is stored under.
+        let cid = node
+            .store(&mut self.cid_store)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(NodeInfo {
+            kind,
+            cid: Some(cid),
+            size,
+            target: None,
+        })
+    }
+
+    /// Node at `path_segments`, looked up one segment at a time, or `None`
+    /// when nothing is there, including below a file.
+    async fn find_node(&mut self, path_segments: &[String]) -> Result<Option<PublicNode>, String> {
+        let mut node = PublicNode::Dir(Rc::clone(&self.root_dir));
+        for segment in path_segments {
+            let dir = match node {
+                PublicNode::Dir(dir) => dir,
+                PublicNode::File(_) => return Ok(None),
+            };
+            node = match dir
+                .lookup_node(segment, &self.store)
+                .await
+                .map_err(|e| e.to_string())?
+            {
+                Some(child) => child,
+                None => return Ok(None),
+            };
+        }
+        Ok(Some(node))
+    }
+
+    async fn lookup(
+        &mut self,
+        path_segments: &[String],
+        with_content: bool,
+    ) -> Result<Option<(Metadata, NodeInfo)>, String> {
+        let node = match self.find_node(path_segments).await? {
+            Some(node) => node,
+            None => return Ok(None),
+        };
+        let info = self.info(&node, with_content).await?;
+        Ok(Some((node_metadata(&node).to_owned(), info)))
+    }
+
+    async fn node_info(
+        &mut self,
+        path_segments: &[String],
+        with_content: bool,
+    ) -> Result<NodeInfo, String> {
+        let node = self.get_node(path_segments).await?;
+        self.info(&node, with_content).await
+    }
+
+    async fn entries(
+        &mut self,
+        path_segments: &[String],
+        with_content: bool,
+    ) -> Result<Vec<(String, Metadata, NodeInfo)>, String> {
+        let dir = match self.get_node(path_segments).await? {
+            PublicNode::Dir(dir) => dir,
+            PublicNode::File(_) => {
+                return Err(format!("{} is not a directory", path_segments.join("/")))
+            }
+        };
+        let mut entries = Vec::new();
+        for name in dir.get_entries() {
+            let child = dir
+                .lookup_node(name, &self.store)
+                .await
+                .map_err(|e| e.to_string())?
+                .ok_or_else(|| format!("{} not found", name))?;
+            let info = self.info(&child, with_content).await?;
+            entries.push((name.to_owned(), node_metadata(&child).to_owned(), info));
+        }
+        Ok(entries)
+    }
+}
+
+impl<'a> PublicDirectoryHelper<'a> {
+    pub fn synced_init(store: BridgedStore) -> Result<(Self, Cid), String> {
+        block_on(Self::init(store))
+    }
+
+    pub fn synced_load(store: BridgedStore, cid: Cid) -> Result<Self, String> {
+        block_on(Self::load(store, cid))
+    }
+
+    pub fn synced_write_file(
+        &mut self,
+        path_segments: &[String],
+        content: Vec<u8>,
+    ) -> Result<Cid, String> {
+        block_on(self.write_file(path_segments, content))
+    }
+
+    pub fn synced_read_file(&mut self, path_segments: &[String]) -> Result<Vec<u8>, String> {
+        block_on(self.read_file(path_segments))
+    }
+
+    pub fn synced_mkdir(&mut self, path_segments: &[String]) -> Result<Cid, String> {
+        block_on(self.mkdir(path_segments))
+    }
+
+    pub fn synced_mv(
+        &mut self,
+        source_path_segments: &[String],
+        target_path_segments: &[String],
+    ) -> Result<Cid, String> {
+        block_on(self.mv(source_path_segments, target_path_segments))
+    }
+
+    pub fn synced_cp(
+        &mut self,
+        source_path_segments: &[String],
+        target_path_segments: &[String],
+    ) -> Result<Cid, String> {
+        block_on(self.cp(source_path_segments, target_path_segments))
+    }
+
+    pub fn synced_rm(&mut self, path_segments: &[String]) -> Result<Cid, String> {
+        block_on(self.rm(path_segments))
+    }
+
+    pub fn synced_ls_files(
+        &mut self,
+        path_segments: &[String],
+    ) -> Result<Vec<(String, Metadata)>, String> {
+        block_on(self.ls_files(path_segments))
+    }
+}
+
+impl<'a> DirectoryReader for PublicDirectoryHelper<'a> {
+    fn ls_files(&mut self, path_segments: &[String]) -> Result<Vec<(String, Metadata)>, String> {
+        self.synced_ls_files(path_segments)
+    }
+
+    fn read_file(&mut self, path_segments: &[String]) -> Result<Vec<u8>, String> {
+        self.synced_read_file(path_segments)
+    }
+
+    fn node_info(
+        &mut self,
+        path_segments: &[String],
+        with_content: bool,
+    ) -> Result<NodeInfo, String> {
+        block_on(PublicDirectoryHelper::node_info(
+            self,
+            path_segments,
+            with_content,
+        ))
+    }
+
+    fn lookup(
+        &mut self,
+        path_segments: &[String],
+        with_content: bool,
+    ) -> Result<Option<(Metadata, NodeInfo)>, String> {
+        block_on(PublicDirectoryHelper::lookup(
+            self,
+            path_segments,
+            with_content,
+        ))
+    }
+
+    fn entries(
+        &mut self,
+        path_segments: &[String],
+        with_content: bool,
+    ) -> Result<Vec<(String, Metadata, NodeInfo)>, String> {
+        block_on(PublicDirectoryHelper::entries(
+            self,
+            path_segments,
+            with_content,
+        ))
+    }
+}
@@ -0,0 +1,42 @@
+use futures::StreamExt;
+use wnfs::private::MAX_BLOCK_CONTENT_SIZE;
+use wnfsutils::private_forest::PrivateDirectoryHelper;
+
+use crate::node::get_file;
+use crate::runtime::block_on;
+
+/// Reads `length` bytes starting at `offset` from a private file.
+///
+/// Only the content blocks that overlap the range are fetched and
+/// decrypted, so the cost does not depend on where in the file the range
+/// lies. Ranges past the end of the file are truncated.
+pub fn read_file_range(
+    helper: &PrivateDirectoryHelper,
+    path_segments: &[String],
+    offset: u64,
+    length: u64,
+) -> Result<Vec<u8>, String> {
+    if length == 0 {
+        return Ok(Vec::new());
+    }
+    let block_size = MAX_BLOCK_CONTENT_SIZE as u64;
+    let first_block = offset / block_size;
+    let last_block = offset.saturating_add(length - 1) / block_size;
+    let mut skip = (offset % block_size) as usize;
+
+    block_on(async {
+        let file = get_file(helper, path_segments).await?;
+        let mut blocks = Box::pin(
+            file.stream_content(first_block as usize, &helper.forest, &helper.store)
+                .take((last_block - first_block + 1) as usize),
+        );
+        let mut content = Vec::with_capacity(length.min(block_size) as usize);
+        while let Some(block) = blocks.next().await {
+            let block = block.map_err(|e| e.to_string())?;
+            content.extend_from_slice(&block[skip.min(block.len())..]);
+            skip = 0;
+        }
+        content.truncate(length as usize);
+        Ok(content)
+    })
+}
@@ -0,0 +1,97 @@
+use std::ops::ControlFlow;
+
+use libipld::Cid;
+use log::trace;
+use wnfsutils::blockstore::FFIFriendlyBlockStore;
+use wnfsutils::private_forest::PrivateDirectoryHelper;
+
+use crate::attrs::{copy_user_meta, create_symlink};
+use crate::blockstore::BridgedStore;
+use crate::listing::{child_path, walk_tree, EntryKind};
+use crate::node::{copy_file, make_dir_with_time, store_root};
+
+/// Copies everything below `source_path` in `source` to `target_path` in
+/// `target`, which may belong to a different forest and key. Files are
+/// re-encrypted block by block and keep their modification time;
+/// directories are recreated so that empty ones survive, and symlinks keep
+/// their target. Only the latest revision of each node is copied. The whole
+/// copy, user metadata included, is built in memory and stored with a
+/// single new root. Returns that root CID, or `None` if there was nothing
+/// to copy.
+pub fn copy_tree(
+    source: &mut PrivateDirectoryHelper,
+    source_path: &[String],
+    target: &mut PrivateDirectoryHelper,
+    target_path: &[String],
+) -> Result<Option<Cid>, String> {
+    let mut entries = Vec::new();
+    walk_tree(source, source_path, None, None, false, |entry| {
+        let relative: Vec<&str> = entry.path.split('/').collect();
+        let source_segments = relative
+            .iter()
+            .fold(source_path.to_vec(), |path, name| child_path(&path, name));
+        let target_segments = relative
+            .iter()
+            .fold(target_path.to_vec(), |path, name| child_path(&path, name));
+        entries.push((source_segments, target_segments, entry.entry));
+        Ok(ControlFlow::Continue(()))
+    })?;
+
+    for (source_segments, target_segments, entry) in entries.iter() {
+        match entry.kind {
+            EntryKind::Dir => make_dir_with_time(target, target_segments, 0)?,
+            EntryKind::File => copy_file(
+                source,
+                source_segments,
+                target,
+                target_segments,
+                entry.modified,
+            )?,
+            EntryKind::Symlink => create_symlink(
+                target,
+                target_segments,
+                entry.target.as_deref().unwrap_or_default(),
+            )?,
+        }
+    }
+
+    let mut copied_meta = copy_user_meta(source, source_path, target, target_path)?;
+    for (source_segments, target_segments, _) in entries.iter() {
+        copied_meta |= copy_user_meta(source, source_segments, target, target_segments)?;
+    }
+    if entries.is_empty() && !copied_meta {
+        return Ok(None);
+    }
+    trace!(
+        "copied {} entries from {:?} to {:?}",
+        entries.len(),
+        source_path,
+        target_path
+    );
+    store_root(target).map(Some)
+}
+
+/// Re-creates the private tree at `cid` in a new forest initialized with
+/// `new_key` and returns the new root.
+///
+/// Nothing in the new forest can be decrypted with `old_key`. Only the
+/// latest revision of each node is copied, so the new root has no revision
+/// history. Files keep their modified time; created times and directory
+/// times are those of the copy. The old root and its blocks are left
+/// untouched for the caller to collect.
+pub fn rotate_key(
+    store: BridgedStore,
+    old_key: Vec<u8>,
+    new_key: Vec<u8>,
+    cid: Cid,
+) -> Result<Cid, String> {
+    let old_store = &mut FFIFriendlyBlockStore::new(Box::new(store.to_owned()));
+    let old_helper =
+        &mut PrivateDirectoryHelper::synced_load_with_wnfs_key(old_store, cid, old_key)?;
+    let new_store = &mut FFIFriendlyBlockStore::new(Box::new(store));
+    let (mut new_helper, _, new_cid) = PrivateDirectoryHelper::synced_init(new_store, new_key)?;
+
+    let new_cid = copy_tree(old_helper, &[], &mut new_helper, &[])?.unwrap_or(new_cid);
+    trace!("rotated cid={} to cid={}", cid, new_cid);
+    Ok(new_cid)
+}
@@ -0,0 +1,62 @@
+use std::future::Future;
+
+use libc::c_void;
+use once_cell::sync::Lazy;
+use tokio::runtime::{Builder, Runtime};
+
+use crate::c_types::{RustBytes, RustResult, RustString, RustVoid};
+
+/// Runtime shared by every `*_native_async` entry point.
+///
+/// The sync helpers in wnfsutils spin up their own current-thread runtime
+/// and block on it, so work is always scheduled with `spawn_blocking`.
+pub static RUNTIME: Lazy<Runtime> = Lazy::new(|| {
+    Builder::new_multi_thread()
+        .enable_all()
+        .thread_name("wnfs-ffi")
+        .build()
+        .expect("Unable to create the wnfs ffi runtime")
+});
+
+/// Drives a wnfs future to completion on the calling thread using the shared
+/// runtime. The futures hold `Rc`s, so they cannot be spawned.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    RUNTIME.block_on(future)
+}
+
+pub type StringCallback = extern "C" fn(userdata: *mut c_void, result: RustResult<RustString>);
+pub type BytesCallback = extern "C" fn(userdata: *mut c_void, result: RustResult<RustBytes>);
+pub type VoidCallback = extern "C" fn(userdata: *mut c_void, result: RustResult<RustVoid>);
+
+/// Moves FFI values that are not `Send` onto a worker thread. The host is
+/// responsible for the thread safety of what they point to.
+pub struct AssertSend<T>(T);
+
+unsafe impl<T> Send for AssertSend<T> {}
+
+impl<T> AssertSend<T> {
+    pub fn new(value: T) -> Self {
+        AssertSend(value)
+    }
+
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+/// Runs `f` on the shared runtime and hands its result to `callback` along
+/// with `userdata`. The callback fires on a runtime worker thread.
+pub fn spawn_with_callback<T, F>(
+    callback: extern "C" fn(userdata: *mut c_void, result: RustResult<T>),
+    userdata: *mut c_void,
+    f: F,
+) where
+    T: 'static,
+    F: FnOnce() -> RustResult<T> + Send + 'static,
+{
+    let userdata = AssertSend::new(userdata);
+    RUNTIME.spawn_blocking(move || {
+        let result = f();
+        callback(userdata.into_inner(), result);
+    });
+}
@@ -0,0 +1,225 @@
+use std::io::Read;
+
+use libipld::Cid;
+use log::trace;
+use wnfs::common::Metadata;
+use wnfsutils::blockstore::FFIFriendlyBlockStore;
+use wnfsutils::private_forest::PrivateDirectoryHelper;
+
+use crate::attrs::{write_file, write_file_from_path, write_file_streaming};
+use crate::blockstore::BridgedStore;
+use crate::error::READ_ONLY_SESSION;
+use crate::node::{decode_access_key, open_shared_dir, store_root};
+use crate::transaction::{apply_operations, TransactionError, TransactionOp};
+
+/// A private forest loaded once and kept alive across FFI calls.
+///
+/// Every `*_native` function reloads and decrypts the root before doing any
+/// work. A session does that once in `open` and then reuses the same helper,
+/// tracking the latest root CID as mutations go through it.
+pub struct WnfsSession {
+    helper: PrivateDirectoryHelper<'static>,
+    cid: Cid,
+    /// Store every load goes through, with the host's extensions if any.
+    store: BridgedStore,
+    /// Drive key, or the encoded access key of a shared session.
+    wnfs_key: Vec<u8>,
+    read_only: bool,
+}
+
+impl WnfsSession {
+    pub fn open(store: BridgedStore, wnfs_key: Vec<u8>, cid: Cid) -> Result<WnfsSession, String> {
+        let helper = Self::load(store.to_owned(), wnfs_key.to_owned(), cid)?;
+        trace!("wnfs session opened at cid={}", cid);
+        Ok(WnfsSession {
+            helper,
+            cid,
+            store,
+            wnfs_key,
+            read_only: false,
+        })
+    }
+
+    /// Opens the directory shared by `share_subtree` in the forest at `cid`
+    /// with its encoded access key, as the root of the session. Every
+    /// mutation through the session fails with `READ_ONLY_SESSION`.
+    pub fn open_shared(
+        store: BridgedStore,
+        access_key: Vec<u8>,
+        cid: Cid,
+    ) -> Result<WnfsSession, String> {
+        let helper = open_shared_dir(store.to_owned(), cid, &decode_access_key(&access_key)?)?;
+        trace!("wnfs shared session opened at cid={}", cid);
+        Ok(WnfsSession {
+            helper,
+            cid,
+            store,
+            wnfs_key: access_key,
+            read_only: true,
+        })
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn check_writable(&self) -> Result<(), String> {
+        if self.read_only {
+            Err(READ_ONLY_SESSION.to_string())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn load(
+        store: BridgedStore,
+        wnfs_key: Vec<u8>,
+        cid: Cid,
+    ) -> Result<PrivateDirectoryHelper<'static>, String> {
+        let block_store = &mut FFIFriendlyBlockStore::new(Box::new(store));
+        PrivateDirectoryHelper::synced_load_with_wnfs_key(block_store, cid, wnfs_key)
+    }
+
+    /// Drops any partially applied state by reloading the last committed root.
+    /// A read-only session never has any.
+    pub fn rollback(&mut self) -> Result<(), String> {
+        if self.read_only {
+            return Ok(());
+        }
+        trace!("wnfs session rolling back to cid={}", self.cid);
+        self.helper = Self::load(self.store.to_owned(), self.wnfs_key.to_owned(), self.cid)?;
+        Ok(())
+    }
+
+    /// Applies `ops` atomically: either all of them are committed or the
+    /// session stays at its current root.
+    pub fn transaction(&mut self, ops: &[TransactionOp]) -> Result<Cid, TransactionError> {
+        if let Err(message) = self.check_writable() {
+            return Err(TransactionError { index: 0, message });
+        }
+        match apply_operations(&mut self.helper, ops) {
+            Ok(Some(cid)) => {
+                self.cid = cid;
+                Ok(cid)
+            }
+            Ok(None) => Ok(self.cid),
+            Err(err) => {
+                if let Err(msg) = self.rollback() {
+                    trace!("wnfsError rolling back session: {:?}", msg);
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// Root CID after the last successful mutation.
+    pub fn cid(&self) -> Cid {
+        self.cid
+    }
+
+    pub fn helper(&mut self) -> &mut PrivateDirectoryHelper<'static> {
+        &mut self.helper
+    }
+
+    fn commit(&mut self, result: Result<Cid, String>) -> Result<Cid, String> {
+        let cid = result?;
+        self.cid = cid;
+        Ok(cid)
+    }
+
+    pub fn write_file(
+        &mut self,
+        path_segments: &[String],
+        content: Vec<u8>,
+    ) -> Result<Cid, String> {
+        self.check_writable()?;
+        let result = write_file(&mut self.helper, path_segments, content, 0);
+        self.commit(result)
+    }
+
+    pub fn write_file_from_path(
+        &mut self,
+        path_segments: &[String],
+        filename: &String,
+    ) -> Result<Cid, String> {
+        self.check_writable()?;
+        let result = write_file_from_path(&mut self.helper, path_segments, filename);
+        self.commit(result)
+    }
+
+    /// Writes what `reader` yields to the file at `path_segments` without
+    /// buffering the whole content.
+    pub fn write_file_streaming(
+        &mut self,
+        path_segments: &[String],
+        reader: impl Read,
+    ) -> Result<Cid, String> {
+        self.check_writable()?;
+        let result = write_file_streaming(&mut self.helper, path_segments, reader, 0)
+            .and_then(|_| store_root(&mut self.helper));
+        self.commit(result)
+    }
+
+    pub fn read_file(&mut self, path_segments: &[String]) -> Result<Vec<u8>, String> {
+        self.helper.synced_read_file(path_segments)
+    }
+
+    pub fn read_file_to_path(
+        &mut self,
+        path_segments: &[String],
+        filename: &String,
+    ) -> Result<String, String> {
+        self.helper
+            .synced_read_file_to_path(path_segments, filename)
+    }
+
+    pub fn read_filestream_to_path(
+        &mut self,
+        path_segments: &[String],
+        filename: &String,
+    ) -> Result<(), String> {
+        self.helper
+            .synced_read_filestream_to_path(filename, path_segments, 0)
+            .map(|_| ())
+    }
+
+    pub fn mkdir(&mut self, path_segments: &[String]) -> Result<Cid, String> {
+        self.check_writable()?;
+        let result = self.helper.synced_mkdir(path_segments);
+        self.commit(result)
+    }
+
+    pub fn mv(
+        &mut self,
+        source_path_segments: &[String],
+        target_path_segments: &[String],
+    ) -> Result<Cid, String> {
+        self.check_writable()?;
+        let result = self
+            .helper
+            .synced_mv(source_path_segments, target_path_segments);
+        self.commit(result)
+    }
+
+    pub fn cp(
+        &mut self,
+        source_path_segments: &[String],
+        target_path_segments: &[String],
+    ) -> Result<Cid, String> {
+        self.check_writable()?;
+        let result = self
+            .helper
+            .synced_cp(source_path_segments, target_path_segments);
+        self.commit(result)
+    }
+
+    pub fn rm(&mut self, path_segments: &[String]) -> Result<Cid, String> {
+        self.check_writable()?;
+        let result = self.helper.synced_rm(path_segments);
+        self.commit(result)
+    }
+
+    pub fn ls(&mut self, path_segments: &[String]) -> Result<Vec<(String, Metadata)>, String> {
+        self.helper.synced_ls_files(path_segments)
+    }
+}
@@ -0,0 +1,64 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use libipld::Cid;
+use log::trace;
+use serde::Serialize;
+
+use crate::blockstore::BridgedStore;
+use crate::ios::load_helper;
+use crate::listing::{stat_path, EntryKind};
+use crate::node::{access_key, encode_access_key};
+
+/// Bumped whenever a field of `ShareOutput` is removed or changes meaning.
+pub const SHARE_OUTPUT_VERSION: u32 = 1;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ShareOutput {
+    pub version: u32,
+    /// Forest the node was shared from, to be passed with the key to
+    /// `load_shared_native` or `read_shared_file_native`.
+    pub cid: String,
+    /// Whether the key opens a directory or a file.
+    pub kind: EntryKind,
+    /// Base64 encoded DAG-CBOR wnfs access key of the node.
+    pub access_key: String,
+}
+
+/// Base64 form of an encoded access key, as handed out in `ShareOutput`.
+pub fn encode_share_key(access_key: &[u8]) -> String {
+    BASE64.encode(access_key)
+}
+
+pub fn decode_share_key(access_key: &str) -> Result<Vec<u8>, String> {
+    BASE64
+        .decode(access_key.trim())
+        .map_err(|e| format!("invalid access key: {}", e))
+}
+
+/// Hands out the access key of the file or directory at `path_segments`, so
+/// that it can be opened without the drive's key and without exposing
+/// anything above it.
+///
+/// The key is the node's own, not a copy's: opening it against a later
+/// root of the same drive shows the node as it is in that root.
+pub fn share_subtree(
+    store: BridgedStore,
+    cid: Cid,
+    path_segments: &[String],
+) -> Result<ShareOutput, String> {
+    if path_segments.is_empty() {
+        return Err("the drive root cannot be shared, share a path below it".to_string());
+    }
+    let helper = &mut load_helper(store, cid)?;
+    let kind = stat_path(helper, path_segments, false)?
+        .kind
+        .ok_or_else(|| format!("{} not found", path_segments.join("/")))?;
+    let access_key = encode_access_key(&access_key(helper, path_segments)?)?;
+    trace!("shared {:?} from cid={}", path_segments, cid);
+    Ok(ShareOutput {
+        version: SHARE_OUTPUT_VERSION,
+        cid: cid.to_string(),
+        kind,
+        access_key: encode_share_key(&access_key),
+    })
+}
@@ -1,13 +1,21 @@
 #[cfg(test)]
 mod ios_tests {
     use crate::{
-        blockstore_interface::BlockStoreInterface,
-        c_types::{RustBytes, RustResult, RustString, RustVoid, prepare_ls_output},
+        blockstore::BridgedStore,
+        car::CarVersion,
+        blockstore_interface::{BlockStoreExtensions, BlockStoreInterface, BLOCK_STORE_EXTENSIONS_VERSION},
+        error::WnfsErrorCode,
+        export::ExistingFiles,
+        find::{FindOptions, KindFilter, PatternSyntax},
+        c_types::{Empty, HostResult, OutputFormat, RustBytes, RustResult, RustString, RustVoid, prepare_ls_output},
         ios::*,
+        ios_async::*,
+        transaction::{TransactionOp, TransactionOpKind},
     };
     use libc::c_void;
     use libipld::Cid;
     use once_cell::sync::Lazy;
+    use libipld::multihash::MultihashDigest;
     use sha256::digest;
     use std::{fs, ptr};
     use wnfs::common::CODEC_DAG_CBOR;
@@ -23,28 +31,28 @@ mod ios_tests {
     static STORE: Lazy<KVBlockStore> =
         Lazy::new(|| KVBlockStore::new(String::from("./tmp/test_db"), CODEC_DAG_CBOR));
 
-    extern "C" fn get(_userdata: *mut c_void, _cid: RustBytes) -> RustResult<RustBytes> {
+    extern "C" fn get(_userdata: *mut c_void, _cid: RustBytes) -> HostResult<RustBytes> {
         let cid = _cid.into();
         let data = STORE.get_block(cid).unwrap();
         let tmp1 = RustBytes::from(data);
-        RustResult::ok(tmp1)
+        HostResult::ok(tmp1)
     }
 
     extern "C" fn put(
         _userdata: *mut c_void,
         _cid: RustBytes,
         _bytes: RustBytes,
-    ) -> RustResult<RustVoid> {
+    ) -> HostResult<RustVoid> {
         let cid: Vec<u8> = _cid.into();
         let bytes = _bytes.into();
         let _data = STORE.put_block(cid.into(), bytes).unwrap();
-        RustResult::ok(RustVoid::void())
+        HostResult::ok(RustVoid::void())
     }
 
-    extern "C" fn dealloc_after_get(obj: RustResult<RustBytes>) {
+    extern "C" fn dealloc_after_get(obj: HostResult<RustBytes>) {
         println!("obj: {}", obj.ok);
     }
-    extern "C" fn dealloc_after_put(obj: RustResult<RustVoid>) {
+    extern "C" fn dealloc_after_put(obj: HostResult<RustVoid>) {
         println!("obj: {}", obj.ok);
     }
 
@@ -327,4 +335,2005 @@ mod ios_tests {
             }
         }
     }
+
+    #[test]
+    fn test_session() {
+        unsafe {
+            let wnfs_key = &mut digest("test_session").as_bytes().to_vec();
+            let wnfs_key_string = wnfs_key[..32].to_vec();
+            let cid = test_cfg(init_native(
+                get_block_store_interface(),
+                wnfs_key_string.to_owned().into(),
+            ));
+
+            let opened = wnfs_session_open(
+                get_block_store_interface(),
+                ptr::null(),
+                wnfs_key_string.to_owned().into(),
+                cid.into(),
+            );
+            assert!(opened.ok, "session should open");
+            let session = opened.result.session;
+
+            let test_content = "Hello, Session!";
+            test_cfg(wnfs_session_mkdir(
+                session,
+                RustString::from("root/session".to_string()),
+            ));
+            test_cfg(wnfs_session_write_file(
+                session,
+                RustString::from("root/session/a.txt".to_string()),
+                test_content.as_bytes().to_vec().into(),
+            ));
+            let cid_after_mv = test_cfg(wnfs_session_mv(
+                session,
+                RustString::from("root/session/a.txt".to_string()),
+                RustString::from("root/session/b.txt".to_string()),
+            ));
+            assert_eq!(test_cfg(wnfs_session_cid(session)), cid_after_mv);
+
+            let content = wnfs_session_read_file(
+                session,
+                RustString::from("root/session/b.txt".to_string()),
+            );
+            assert!(content.ok);
+            let content: Vec<u8> = content.result.into();
+            assert_eq!(content, test_content.as_bytes().to_vec());
+
+            // The CID minted by the session must be readable without it.
+            let content_reloaded = read_file_native(
+                get_block_store_interface(),
+                cid_after_mv.into(),
+                RustString::from("root/session/b.txt".to_string()),
+            );
+            let content: Vec<u8> = content_reloaded.result.into();
+            assert_eq!(content, test_content.as_bytes().to_vec());
+
+            wnfs_session_close(session);
+        }
+    }
+
+    #[test]
+    fn test_ls_with_format() {
+        unsafe {
+            let wnfs_key = &mut digest("test_ls_with_format").as_bytes().to_vec();
+            let wnfs_key_string = wnfs_key[..32].to_vec();
+            let mut cid = test_cfg(init_native(
+                get_block_store_interface(),
+                wnfs_key_string.to_owned().into(),
+            ));
+            cid = test_cfg(mkdir_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/dir???!!!".to_string()),
+            ));
+            cid = test_cfg(write_file_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/file.txt".to_string()),
+                b"12345".to_vec().into(),
+            ));
+
+            let listing = ls_with_format_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root".to_string()),
+                OutputFormat::Json,
+            );
+            assert!(listing.ok);
+            let listing: Vec<u8> = listing.result.into();
+            let listing: serde_json::Value = serde_json::from_slice(&listing).unwrap();
+            assert_eq!(listing["version"], 1);
+            let entries = listing["entries"].as_array().unwrap();
+            assert_eq!(entries.len(), 2);
+            for entry in entries {
+                if entry["name"] == "file.txt" {
+                    assert_eq!(entry["kind"], "file");
+                    assert_eq!(entry["size"], 5);
+                    assert!(entry["cid"].is_string());
+                } else {
+                    assert_eq!(entry["name"], "dir???!!!");
+                    assert_eq!(entry["kind"], "dir");
+                    assert!(entry["size"].is_null());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_error_codes() {
+        unsafe {
+            let result = mkdir_native(
+                get_block_store_interface(),
+                RustString::from("not-a-cid".to_string()),
+                RustString::from("root/test1".to_string()),
+            );
+            assert!(!result.ok);
+            assert_eq!(result.code, WnfsErrorCode::InvalidCid);
+            let detail: String = result.detail.into();
+            let detail: serde_json::Value = serde_json::from_str(&detail).unwrap();
+            assert_eq!(detail["function"], "mkdir_native");
+
+            let result = wnfs_session_mkdir(
+                ptr::null_mut(),
+                RustString::from("root/test1".to_string()),
+            );
+            assert!(!result.ok);
+            assert_eq!(result.code, WnfsErrorCode::InvalidArgument);
+
+            let wnfs_key = &mut digest("test_error_codes").as_bytes().to_vec();
+            let wnfs_key_string = wnfs_key[..32].to_vec();
+            let cid = test_cfg(init_native(
+                get_block_store_interface(),
+                wnfs_key_string.to_owned().into(),
+            ));
+            let result = write_file_from_path_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/missing.txt".to_string()),
+                RustString::from("./tmp/does-not-exist.txt".to_string()),
+            );
+            assert!(!result.ok, "a failed local read must not be reported as ok");
+            assert_eq!(result.code, WnfsErrorCode::LocalIo);
+
+            let wrong_key = &mut digest("test_error_codes_wrong").as_bytes().to_vec();
+            let result = load_with_wnfs_key_native(
+                get_block_store_interface(),
+                wrong_key[..32].to_vec().into(),
+                cid.into(),
+            );
+            assert!(!result.ok);
+            assert_eq!(result.code, WnfsErrorCode::WrongKey);
+        }
+    }
+
+    #[test]
+    fn test_file_writer() {
+        unsafe {
+            let wnfs_key = &mut digest("test_file_writer").as_bytes().to_vec();
+            let wnfs_key_string = wnfs_key[..32].to_vec();
+            let cid = test_cfg(init_native(
+                get_block_store_interface(),
+                wnfs_key_string.to_owned().into(),
+            ));
+
+            let opened = file_writer_open_native(RustString::from("root/chunked.bin".to_string()));
+            assert!(opened.ok);
+            let writer = opened.result.writer;
+            let mut expected: Vec<u8> = Vec::new();
+            for i in 0..3u8 {
+                let chunk = vec![i; 1024 * 1024];
+                expected.extend_from_slice(&chunk);
+                assert!(file_writer_push_native(writer, chunk.into()).ok);
+            }
+            let new_cid = test_cfg(file_writer_finalize_native(
+                writer,
+                get_block_store_interface(),
+                ptr::null(),
+                cid.into(),
+            ));
+            let content = read_file_native(
+                get_block_store_interface(),
+                new_cid.into(),
+                RustString::from("root/chunked.bin".to_string()),
+            );
+            let content: Vec<u8> = content.result.into();
+            assert_eq!(content, expected);
+
+            let opened = file_writer_open_native(RustString::from("root/aborted.bin".to_string()));
+            let writer = opened.result.writer;
+            assert!(file_writer_push_native(writer, b"discard me".to_vec().into()).ok);
+            file_writer_abort_native(writer);
+            let content = read_file_native(
+                get_block_store_interface(),
+                new_cid.into(),
+                RustString::from("root/aborted.bin".to_string()),
+            );
+            assert!(!content.ok);
+        }
+    }
+
+    #[test]
+    fn test_read_file_range() {
+        unsafe {
+            let wnfs_key = &mut digest("test_read_file_range").as_bytes().to_vec();
+            let wnfs_key_string = wnfs_key[..32].to_vec();
+            let mut cid = test_cfg(init_native(
+                get_block_store_interface(),
+                wnfs_key_string.to_owned().into(),
+            ));
+            let data: Vec<u8> = (0..600 * 1024).map(|i| (i % 251) as u8).collect();
+            cid = test_cfg(write_file_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/range.bin".to_string()),
+                data.to_owned().into(),
+            ));
+
+            // Crosses the boundary between the first and second block.
+            let (offset, length) = (262_000u64, 1_000u64);
+            let range = read_file_range_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/range.bin".to_string()),
+                offset,
+                length,
+            );
+            assert!(range.ok);
+            let range: Vec<u8> = range.result.into();
+            assert_eq!(range, data[offset as usize..(offset + length) as usize].to_vec());
+
+            let tail = read_file_range_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/range.bin".to_string()),
+                data.len() as u64 - 10,
+                100,
+            );
+            let tail: Vec<u8> = tail.result.into();
+            assert_eq!(tail, data[data.len() - 10..].to_vec());
+        }
+    }
+
+    extern "C" fn send_string_result(userdata: *mut c_void, result: RustResult<RustString>) {
+        let sender = unsafe { &*(userdata as *const std::sync::mpsc::Sender<(bool, String)>) };
+        let ok = result.ok;
+        let value: String = if ok {
+            result.result.into()
+        } else {
+            result.err.into()
+        };
+        sender.send((ok, value)).unwrap();
+    }
+
+    #[test]
+    fn test_async_callbacks() {
+        unsafe {
+            let wnfs_key = &mut digest("test_async_callbacks").as_bytes().to_vec();
+            let wnfs_key_string = wnfs_key[..32].to_vec();
+            let cid = test_cfg(init_native(
+                get_block_store_interface(),
+                wnfs_key_string.to_owned().into(),
+            ));
+
+            let (sender, receiver) = std::sync::mpsc::channel::<(bool, String)>();
+            let userdata = &sender as *const _ as *mut c_void;
+            for dir in ["root/async1", "root/async2", "root/async3"] {
+                mkdir_native_async(
+                    get_block_store_interface(),
+                    cid.into(),
+                    RustString::from(dir.to_string()),
+                    send_string_result,
+                    userdata,
+                );
+            }
+            for _ in 0..3 {
+                let (ok, value) = receiver
+                    .recv_timeout(std::time::Duration::from_secs(60))
+                    .unwrap();
+                assert!(ok, "async mkdir failed: {}", value);
+                assert!(Cid::try_from(value).is_ok());
+            }
+
+            // The ops are copied before the call returns.
+            let ops = vec![
+                tx_op(TransactionOpKind::Mkdir, "root/async_tx", "", b""),
+                tx_op(TransactionOpKind::Write, "root/async_tx/a.txt", "", b"a"),
+            ];
+            transaction_native_async(
+                get_block_store_interface(),
+                ptr::null(),
+                cid.into(),
+                ops.as_ptr(),
+                ops.len(),
+                send_string_result,
+                userdata,
+            );
+            drop(ops);
+            let (ok, value) = receiver
+                .recv_timeout(std::time::Duration::from_secs(60))
+                .unwrap();
+            assert!(ok, "async transaction failed: {}", value);
+            let content = read_file_native(
+                get_block_store_interface(),
+                Cid::try_from(value).unwrap().into(),
+                RustString::from("root/async_tx/a.txt".to_string()),
+            );
+            let content: Vec<u8> = content.result.into();
+            assert_eq!(content, b"a".to_vec());
+        }
+    }
+
+    fn tx_op(kind: TransactionOpKind, path: &str, target: &str, content: &[u8]) -> TransactionOp {
+        TransactionOp {
+            kind,
+            path_segments: RustString::from(path.to_string()),
+            target: if target.is_empty() {
+                RustString::empty()
+            } else {
+                RustString::from(target.to_string())
+            },
+            content: content.to_vec().into(),
+        }
+    }
+
+    #[test]
+    fn test_transaction() {
+        unsafe {
+            let wnfs_key = &mut digest("test_transaction").as_bytes().to_vec();
+            let wnfs_key_string = wnfs_key[..32].to_vec();
+            let cid = test_cfg(init_native(
+                get_block_store_interface(),
+                wnfs_key_string.to_owned().into(),
+            ));
+
+            let ops = vec![
+                tx_op(TransactionOpKind::Mkdir, "root/tx", "", b""),
+                tx_op(TransactionOpKind::Write, "root/tx/a.txt", "", b"a"),
+                tx_op(TransactionOpKind::Write, "root/tx/b.txt", "", b"b"),
+                tx_op(TransactionOpKind::Mv, "root/tx/a.txt", "root/tx/c.txt", b""),
+            ];
+            let new_cid = test_cfg(transaction_native(
+                get_block_store_interface(),
+                ptr::null(),
+                cid.into(),
+                ops.as_ptr(),
+                ops.len(),
+            ));
+            let content = read_file_native(
+                get_block_store_interface(),
+                new_cid.into(),
+                RustString::from("root/tx/c.txt".to_string()),
+            );
+            let content: Vec<u8> = content.result.into();
+            assert_eq!(content, b"a".to_vec());
+
+            let ops = vec![
+                tx_op(TransactionOpKind::Write, "root/tx/d.txt", "", b"d"),
+                tx_op(TransactionOpKind::Rm, "root/tx/missing.txt", "", b""),
+            ];
+            let failed = transaction_native(
+                get_block_store_interface(),
+                ptr::null(),
+                new_cid.into(),
+                ops.as_ptr(),
+                ops.len(),
+            );
+            assert!(!failed.ok);
+            let detail: String = failed.detail.into();
+            let detail: serde_json::Value = serde_json::from_str(&detail).unwrap();
+            assert_eq!(detail["op_index"], 1);
+        }
+    }
+
+    static PUT_MANY_CALLS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+    extern "C" fn has(_userdata: *mut c_void, _cid: RustBytes) -> bool {
+        let cid: Vec<u8> = _cid.into();
+        STORE.get_block(cid).is_ok()
+    }
+
+    extern "C" fn put_many(
+        _userdata: *mut c_void,
+        cids: *const RustBytes,
+        blocks: *const RustBytes,
+        count: usize,
+    ) -> HostResult<RustVoid> {
+        PUT_MANY_CALLS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let cids = unsafe { std::slice::from_raw_parts(cids, count) };
+        let blocks = unsafe { std::slice::from_raw_parts(blocks, count) };
+        for (cid, block) in cids.iter().zip(blocks) {
+            STORE
+                .put_block(cid.to_owned().into(), block.to_owned().into())
+                .unwrap();
+        }
+        HostResult::ok(RustVoid::void())
+    }
+
+    #[test]
+    fn test_block_store_extensions() {
+        let extensions = BlockStoreExtensions {
+            size: std::mem::size_of::<BlockStoreExtensions>(),
+            version: BLOCK_STORE_EXTENSIONS_VERSION,
+            has_fn: Some(has),
+            delete_fn: None,
+            put_many_fn: Some(put_many),
+            get_many_fn: None,
+            dealloc_after_get_many: None,
+        };
+        let read = unsafe { BlockStoreExtensions::from_ptr(&extensions) }.unwrap();
+        let store = BridgedStore::with_extensions(get_block_store_interface(), read);
+
+        let blocks: Vec<(Vec<u8>, Vec<u8>)> = ["extension block a", "extension block b"]
+            .iter()
+            .map(|content| {
+                let hash = libipld::multihash::Code::Sha2_256.digest(content.as_bytes());
+                let cid = Cid::new_v1(0x55, hash);
+                (cid.to_bytes(), content.as_bytes().to_vec())
+            })
+            .collect();
+        let calls_before = PUT_MANY_CALLS.load(std::sync::atomic::Ordering::SeqCst);
+        store.put_blocks(&blocks).unwrap();
+        // Everything is present now, so has_fn short-circuits the second put.
+        store.put_blocks(&blocks).unwrap();
+        let calls_after = PUT_MANY_CALLS.load(std::sync::atomic::Ordering::SeqCst);
+        assert_eq!(calls_after - calls_before, 1);
+        assert_eq!(store.has_block(&blocks[0].0), Some(true));
+        assert!(!store.supports_delete());
+
+        // No get_many_fn: falls back to one get_fn call per block.
+        let cids: Vec<Vec<u8>> = blocks.iter().map(|(cid, _)| cid.to_owned()).collect();
+        let fetched = store.get_blocks(&cids).unwrap();
+        assert_eq!(fetched[0], blocks[0].1);
+        assert_eq!(fetched[1], blocks[1].1);
+
+        // A host built against a shorter layout leaves the later callbacks out.
+        let older = BlockStoreExtensions {
+            size: std::mem::size_of::<usize>() * 3,
+            ..extensions
+        };
+        let read = unsafe { BlockStoreExtensions::from_ptr(&older) }.unwrap().unwrap();
+        assert!(read.has_fn.is_some());
+        assert!(read.put_many_fn.is_none());
+        // A size ending inside a callback is a layout mismatch.
+        let torn = BlockStoreExtensions {
+            size: std::mem::size_of::<usize>() * 3 + 2,
+            ..extensions
+        };
+        assert!(unsafe { BlockStoreExtensions::from_ptr(&torn) }.is_err());
+
+        assert!(unsafe { BlockStoreExtensions::from_ptr(ptr::null()) }.unwrap().is_none());
+        assert_eq!(
+            BridgedStore::new(get_block_store_interface()).has_block(&cids[0]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_gc() {
+        unsafe {
+            let wnfs_key = &mut digest("test_gc").as_bytes().to_vec();
+            let wnfs_key_string = wnfs_key[..32].to_vec();
+            let mut cid = test_cfg(init_native(
+                get_block_store_interface(),
+                wnfs_key_string.to_owned().into(),
+            ));
+            cid = test_cfg(write_file_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/garbage.bin".to_string()),
+                vec![7u8; 300 * 1024].into(),
+            ));
+            let old_cid = cid;
+            cid = test_cfg(rm_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/garbage.bin".to_string()),
+            ));
+
+            let keep = vec![RustString::from(cid)];
+            let drop = vec![RustString::from(old_cid)];
+            let report = gc_native(
+                get_block_store_interface(),
+                ptr::null(),
+                keep.as_ptr(),
+                keep.len(),
+                drop.as_ptr(),
+                drop.len(),
+                false,
+                OutputFormat::Json,
+                None,
+                ptr::null_mut(),
+            );
+            assert!(report.ok);
+            let report: Vec<u8> = report.result.into();
+            let report: serde_json::Value = serde_json::from_slice(&report).unwrap();
+            let dead = report["dead"].as_array().unwrap();
+            assert!(!dead.is_empty());
+            assert!(!dead.contains(&serde_json::Value::String(cid.to_string())));
+            assert_eq!(report["deleted"], 0);
+
+            // Without a delete_fn, deleting must be refused.
+            let refused = gc_native(
+                get_block_store_interface(),
+                ptr::null(),
+                keep.as_ptr(),
+                keep.len(),
+                drop.as_ptr(),
+                drop.len(),
+                true,
+                OutputFormat::Json,
+                None,
+                ptr::null_mut(),
+            );
+            assert!(!refused.ok);
+            assert_eq!(refused.code, WnfsErrorCode::InvalidArgument);
+        }
+    }
+
+    #[test]
+    fn test_public_directory() {
+        unsafe {
+            let mut cid = test_cfg(public_init_native(get_block_store_interface()));
+            cid = test_cfg(public_mkdir_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/album".to_string()),
+            ));
+            cid = test_cfg(public_write_file_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/profile.jpg".to_string()),
+                b"not really a jpeg".to_vec().into(),
+            ));
+            cid = test_cfg(public_cp_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/profile.jpg".to_string()),
+                RustString::from("root/album/profile.jpg".to_string()),
+            ));
+            cid = test_cfg(public_mv_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/album/profile.jpg".to_string()),
+                RustString::from("root/album/old.jpg".to_string()),
+            ));
+
+            let content = public_read_file_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/album/old.jpg".to_string()),
+            );
+            assert!(content.ok);
+            let content: Vec<u8> = content.result.into();
+            assert_eq!(content, b"not really a jpeg".to_vec());
+
+            // Content larger than one chunk is split and read back whole,
+            // and copying a directory carries it along.
+            let large: Vec<u8> = (0..2 * crate::unixfs::CHUNK_SIZE + 7)
+                .map(|i| (i % 251) as u8)
+                .collect();
+            cid = test_cfg(public_write_file_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/album/large.bin".to_string()),
+                large.clone().into(),
+            ));
+            let copy_into_itself = public_cp_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/album".to_string()),
+                RustString::from("root/album/nested".to_string()),
+            );
+            assert!(!copy_into_itself.ok);
+            cid = test_cfg(public_cp_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/album".to_string()),
+                RustString::from("root/backup".to_string()),
+            ));
+            let content = public_read_file_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/backup/large.bin".to_string()),
+            );
+            assert!(content.ok);
+            let content: Vec<u8> = content.result.into();
+            assert_eq!(content, large);
+
+            cid = test_cfg(public_rm_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/profile.jpg".to_string()),
+            ));
+            cid = test_cfg(public_rm_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/backup".to_string()),
+            ));
+            let listing = public_ls_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root".to_string()),
+                OutputFormat::Json,
+            );
+            assert!(listing.ok);
+            let listing: Vec<u8> = listing.result.into();
+            let listing: serde_json::Value = serde_json::from_slice(&listing).unwrap();
+            let entries = listing["entries"].as_array().unwrap();
+            assert_eq!(entries.len(), 1);
+            assert_eq!(entries[0]["name"], "album");
+            assert_eq!(entries[0]["kind"], "dir");
+
+            // A public root is not a private forest.
+            let private = read_file_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/album/old.jpg".to_string()),
+            );
+            assert!(!private.ok);
+        }
+    }
+
+    #[test]
+    fn test_stat() {
+        unsafe {
+            let wnfs_key = &mut digest("test_stat").as_bytes().to_vec();
+            let wnfs_key_string = wnfs_key[..32].to_vec();
+            let mut cid = test_cfg(init_native(
+                get_block_store_interface(),
+                wnfs_key_string.to_owned().into(),
+            ));
+            cid = test_cfg(write_file_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/stat/file.txt".to_string()),
+                b"12345".to_vec().into(),
+            ));
+
+            let stat = |path: &str, with_content: bool| -> serde_json::Value {
+                let result = stat_native(
+                    get_block_store_interface(),
+                    cid.into(),
+                    RustString::from(path.to_string()),
+                    OutputFormat::Json,
+                    with_content,
+                );
+                assert!(result.ok, "stat of {} should succeed", path);
+                let result: Vec<u8> = result.result.into();
+                serde_json::from_slice(&result).unwrap()
+            };
+
+            let file = stat("root/stat/file.txt", false);
+            assert_eq!(file["exists"], true);
+            assert_eq!(file["kind"], "file");
+            assert!(file["modified"].is_i64());
+            assert!(file["size"].is_null());
+
+            let file = stat("root/stat/file.txt", true);
+            assert_eq!(file["size"], 5);
+            assert!(file["cid"].is_string());
+
+            let dir = stat("root/stat", true);
+            assert_eq!(dir["exists"], true);
+            assert_eq!(dir["kind"], "dir");
+            assert!(dir["size"].is_null());
+
+            assert_eq!(stat("root/stat/missing.txt", false)["exists"], false);
+            assert_eq!(stat("root/missing/file.txt", false)["exists"], false);
+            assert_eq!(stat("root/stat/file.txt/below", false)["exists"], false);
+        }
+    }
+
+    extern "C" fn collect_entry(userdata: *mut c_void, entry: RustBytes) {
+        let entries = unsafe { &mut *(userdata as *mut Vec<serde_json::Value>) };
+        let entry = unsafe { entry.as_slice() };
+        entries.push(serde_json::from_slice(entry).unwrap());
+    }
+
+    #[test]
+    fn test_tree() {
+        unsafe {
+            let wnfs_key = &mut digest("test_tree").as_bytes().to_vec();
+            let wnfs_key_string = wnfs_key[..32].to_vec();
+            let mut cid = test_cfg(init_native(
+                get_block_store_interface(),
+                wnfs_key_string.to_owned().into(),
+            ));
+            for path in ["root/tree/a.txt", "root/tree/sub/b.txt", "root/tree/sub/deep/c.txt"] {
+                cid = test_cfg(write_file_native(
+                    get_block_store_interface(),
+                    cid.into(),
+                    RustString::from(path.to_string()),
+                    b"tree".to_vec().into(),
+                ));
+            }
+
+            let tree = |max_depth: u32, max_entries: usize| -> serde_json::Value {
+                let result = tree_native(
+                    get_block_store_interface(),
+                    ptr::null(),
+                    cid.into(),
+                    RustString::from("root/tree".to_string()),
+                    max_depth,
+                    max_entries,
+                    OutputFormat::Json,
+                    false,
+                    None,
+                    ptr::null_mut(),
+                );
+                assert!(result.ok);
+                let result: Vec<u8> = result.result.into();
+                serde_json::from_slice(&result).unwrap()
+            };
+
+            let full = tree(0, 0);
+            assert_eq!(full["count"], 5);
+            assert_eq!(full["truncated"], false);
+            let mut paths: Vec<String> = full["entries"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|entry| entry["path"].as_str().unwrap().to_string())
+                .collect();
+            paths.sort();
+            assert_eq!(paths, vec!["a.txt", "sub", "sub/b.txt", "sub/deep", "sub/deep/c.txt"]);
+            for entry in full["entries"].as_array().unwrap() {
+                if entry["path"] == "sub/deep/c.txt" {
+                    assert_eq!(entry["depth"], 3);
+                    assert_eq!(entry["kind"], "file");
+                }
+            }
+
+            assert_eq!(tree(1, 0)["count"], 2);
+            let capped = tree(0, 3);
+            assert_eq!(capped["count"], 3);
+            assert_eq!(capped["truncated"], true);
+
+            let mut streamed: Vec<serde_json::Value> = Vec::new();
+            let result = tree_native(
+                get_block_store_interface(),
+                ptr::null(),
+                cid.into(),
+                RustString::from("root/tree".to_string()),
+                0,
+                0,
+                OutputFormat::Json,
+                false,
+                Some(collect_entry),
+                &mut streamed as *mut Vec<serde_json::Value> as *mut c_void,
+            );
+            assert!(result.ok);
+            let result: Vec<u8> = result.result.into();
+            let result: serde_json::Value = serde_json::from_slice(&result).unwrap();
+            assert_eq!(result["count"], 5);
+            assert!(result["entries"].as_array().unwrap().is_empty());
+            assert_eq!(streamed.len(), 5);
+        }
+    }
+
+    #[test]
+    fn test_rotate_key() {
+        unsafe {
+            let old_key = digest("test_rotate_key_old").as_bytes()[..32].to_vec();
+            let new_key = digest("test_rotate_key_new").as_bytes()[..32].to_vec();
+            let mut cid = test_cfg(init_native(
+                get_block_store_interface(),
+                old_key.to_owned().into(),
+            ));
+            cid = test_cfg(mkdir_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/empty".to_string()),
+            ));
+            cid = test_cfg(write_file_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/docs/key.txt".to_string()),
+                b"rotate me".to_vec().into(),
+            ));
+
+            let new_cid = test_cfg(rotate_key_native(
+                get_block_store_interface(),
+                ptr::null(),
+                old_key.to_owned().into(),
+                new_key.to_owned().into(),
+                cid.into(),
+            ));
+            assert_ne!(new_cid, cid);
+
+            let loaded = load_with_wnfs_key_native(
+                get_block_store_interface(),
+                new_key.to_owned().into(),
+                new_cid.into(),
+            );
+            assert!(loaded.ok);
+            let content = read_file_native(
+                get_block_store_interface(),
+                new_cid.into(),
+                RustString::from("root/docs/key.txt".to_string()),
+            );
+            assert!(content.ok);
+            let content: Vec<u8> = content.result.into();
+            assert_eq!(content, b"rotate me".to_vec());
+
+            let stat = stat_native(
+                get_block_store_interface(),
+                new_cid.into(),
+                RustString::from("root/empty".to_string()),
+                OutputFormat::Json,
+                false,
+            );
+            let stat: Vec<u8> = stat.result.into();
+            let stat: serde_json::Value = serde_json::from_slice(&stat).unwrap();
+            assert_eq!(stat["kind"], "dir");
+
+            let wrong_key = load_with_wnfs_key_native(
+                get_block_store_interface(),
+                old_key.to_owned().into(),
+                new_cid.into(),
+            );
+            assert!(!wrong_key.ok);
+        }
+    }
+
+    #[test]
+    fn test_share() {
+        unsafe {
+            let wnfs_key = &mut digest("test_share").as_bytes().to_vec();
+            let wnfs_key_string = wnfs_key[..32].to_vec();
+            let mut cid = test_cfg(init_native(
+                get_block_store_interface(),
+                wnfs_key_string.to_owned().into(),
+            ));
+            cid = test_cfg(write_file_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/albums/summer/beach.jpg".to_string()),
+                b"sand".to_vec().into(),
+            ));
+            cid = test_cfg(write_file_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/private/diary.txt".to_string()),
+                b"secret".to_vec().into(),
+            ));
+
+            let share = share_native(
+                get_block_store_interface(),
+                ptr::null(),
+                cid.into(),
+                RustString::from("root/albums/summer".to_string()),
+                OutputFormat::Json,
+            );
+            assert!(share.ok);
+            let share: Vec<u8> = share.result.into();
+            let share: serde_json::Value = serde_json::from_slice(&share).unwrap();
+            let share_cid = share["cid"].as_str().unwrap().to_string();
+            let access_key = share["access_key"].as_str().unwrap().to_string();
+
+            let opened = load_shared_native(
+                get_block_store_interface(),
+                ptr::null(),
+                RustString::from(access_key.to_owned()),
+                RustString::from(share_cid),
+            );
+            assert!(opened.ok, "share should open with its access key");
+            let session = opened.result.session;
+
+            let content = wnfs_session_read_file(
+                session,
+                RustString::from("beach.jpg".to_string()),
+            );
+            assert!(content.ok);
+            let content: Vec<u8> = content.result.into();
+            assert_eq!(content, b"sand".to_vec());
+
+            let outside = wnfs_session_read_file(
+                session,
+                RustString::from("root/private/diary.txt".to_string()),
+            );
+            assert!(!outside.ok);
+
+            let write = wnfs_session_write_file(
+                session,
+                RustString::from("new.jpg".to_string()),
+                b"nope".to_vec().into(),
+            );
+            assert!(!write.ok);
+            assert_eq!(write.code, WnfsErrorCode::ReadOnly);
+
+            wnfs_session_close(session);
+
+            // The key is the directory's own, so a later root shows later
+            // changes.
+            cid = test_cfg(write_file_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/albums/summer/waves.jpg".to_string()),
+                b"foam".to_vec().into(),
+            ));
+            let opened = load_shared_native(
+                get_block_store_interface(),
+                ptr::null(),
+                RustString::from(access_key.to_owned()),
+                cid.into(),
+            );
+            assert!(opened.ok);
+            let session = opened.result.session;
+            let content = wnfs_session_read_file(
+                session,
+                RustString::from("waves.jpg".to_string()),
+            );
+            assert!(content.ok);
+            let content: Vec<u8> = content.result.into();
+            assert_eq!(content, b"foam".to_vec());
+            wnfs_session_close(session);
+
+            let file_share = share_native(
+                get_block_store_interface(),
+                ptr::null(),
+                cid.into(),
+                RustString::from("root/private/diary.txt".to_string()),
+                OutputFormat::Json,
+            );
+            assert!(file_share.ok);
+            let file_share: Vec<u8> = file_share.result.into();
+            let file_share: serde_json::Value = serde_json::from_slice(&file_share).unwrap();
+            assert_eq!(file_share["version"], 1);
+            assert_eq!(file_share["kind"], "file");
+            let file_key = file_share["access_key"].as_str().unwrap().to_string();
+            let content = read_shared_file_native(
+                get_block_store_interface(),
+                ptr::null(),
+                RustString::from(file_key.to_owned()),
+                cid.into(),
+            );
+            assert!(content.ok);
+            let content: Vec<u8> = content.result.into();
+            assert_eq!(content, b"secret".to_vec());
+
+            // A file's key does not open a session.
+            let opened = load_shared_native(
+                get_block_store_interface(),
+                ptr::null(),
+                RustString::from(file_key),
+                cid.into(),
+            );
+            assert!(!opened.ok);
+        }
+    }
+
+    #[test]
+    fn test_revisions() {
+        unsafe {
+            let wnfs_key = &mut digest("test_revisions").as_bytes().to_vec();
+            let wnfs_key_string = wnfs_key[..32].to_vec();
+            let mut roots = vec![test_cfg(init_native(
+                get_block_store_interface(),
+                wnfs_key_string.to_owned().into(),
+            ))];
+            for content in ["first", "second", "third"] {
+                roots.push(test_cfg(write_file_native(
+                    get_block_store_interface(),
+                    roots.last().unwrap().to_owned().into(),
+                    RustString::from("root/notes.txt".to_string()),
+                    content.as_bytes().to_vec().into(),
+                )));
+            }
+            // An unrelated change does not make a new revision of the file.
+            roots.push(test_cfg(mkdir_native(
+                get_block_store_interface(),
+                roots.last().unwrap().to_owned().into(),
+                RustString::from("root/other".to_string()),
+            )));
+
+            // Written within the same second: still a revision of its own.
+            roots.push(test_cfg(write_file_native(
+                get_block_store_interface(),
+                roots.last().unwrap().to_owned().into(),
+                RustString::from("root/notes.txt".to_string()),
+                b"fourth".to_vec().into(),
+            )));
+            let current = *roots.last().unwrap();
+
+            let revisions = revisions_native(
+                get_block_store_interface(),
+                ptr::null(),
+                current.into(),
+                roots[1].into(),
+                RustString::from("root/notes.txt".to_string()),
+                OutputFormat::Json,
+                true,
+            );
+            assert!(revisions.ok);
+            let revisions: Vec<u8> = revisions.result.into();
+            let revisions: serde_json::Value = serde_json::from_slice(&revisions).unwrap();
+            assert_eq!(revisions["version"], 1);
+            let revisions = revisions["revisions"].as_array().unwrap();
+            assert_eq!(revisions.len(), 4);
+            assert_eq!(revisions[0]["revision"], 1);
+            assert_eq!(revisions[0]["size"], 5);
+            assert_eq!(revisions[3]["size"], 6);
+
+            for (revision, expected) in [(1, "first"), (2, "second"), (4, "fourth")] {
+                let content = read_revision_native(
+                    get_block_store_interface(),
+                    ptr::null(),
+                    current.into(),
+                    roots[1].into(),
+                    RustString::from("root/notes.txt".to_string()),
+                    revision,
+                );
+                assert!(content.ok);
+                let content: Vec<u8> = content.result.into();
+                assert_eq!(content, expected.as_bytes().to_vec());
+            }
+            let missing = read_revision_native(
+                get_block_store_interface(),
+                ptr::null(),
+                current.into(),
+                roots[1].into(),
+                RustString::from("root/notes.txt".to_string()),
+                5,
+            );
+            assert!(!missing.ok);
+
+            let restored = test_cfg(restore_revision_native(
+                get_block_store_interface(),
+                ptr::null(),
+                current.into(),
+                roots[1].into(),
+                RustString::from("root/notes.txt".to_string()),
+                1,
+            ));
+            let content = read_file_native(
+                get_block_store_interface(),
+                restored.into(),
+                RustString::from("root/notes.txt".to_string()),
+            );
+            let content: Vec<u8> = content.result.into();
+            assert_eq!(content, b"first".to_vec());
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        unsafe {
+            let wnfs_key = &mut digest("test_diff").as_bytes().to_vec();
+            let wnfs_key_string = wnfs_key[..32].to_vec();
+            let mut cid_a = test_cfg(init_native(
+                get_block_store_interface(),
+                wnfs_key_string.to_owned().into(),
+            ));
+            for path in ["root/diff/keep.txt", "root/diff/change.txt", "root/diff/gone.txt"] {
+                cid_a = test_cfg(write_file_native(
+                    get_block_store_interface(),
+                    cid_a.into(),
+                    RustString::from(path.to_string()),
+                    b"before".to_vec().into(),
+                ));
+            }
+            let mut cid_b = test_cfg(write_file_native(
+                get_block_store_interface(),
+                cid_a.into(),
+                RustString::from("root/diff/change.txt".to_string()),
+                b"after!!".to_vec().into(),
+            ));
+            cid_b = test_cfg(rm_native(
+                get_block_store_interface(),
+                cid_b.into(),
+                RustString::from("root/diff/gone.txt".to_string()),
+            ));
+            cid_b = test_cfg(write_file_native(
+                get_block_store_interface(),
+                cid_b.into(),
+                RustString::from("root/diff/new/file.txt".to_string()),
+                b"new".to_vec().into(),
+            ));
+
+            let diff = diff_native(
+                get_block_store_interface(),
+                ptr::null(),
+                cid_a.into(),
+                cid_b.into(),
+                RustString::from("root/diff".to_string()),
+                OutputFormat::Json,
+                true,
+            );
+            assert!(diff.ok);
+            let diff: Vec<u8> = diff.result.into();
+            let diff: serde_json::Value = serde_json::from_slice(&diff).unwrap();
+            let changes: Vec<(String, String)> = diff["changes"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|change| {
+                    (
+                        change["path"].as_str().unwrap().to_string(),
+                        change["change"].as_str().unwrap().to_string(),
+                    )
+                })
+                .collect();
+            let expected: Vec<(String, String)> = [
+                ("change.txt", "modified"),
+                ("gone.txt", "removed"),
+                ("new", "added"),
+                ("new/file.txt", "added"),
+            ]
+            .iter()
+            .map(|(path, change)| (path.to_string(), change.to_string()))
+            .collect();
+            assert_eq!(changes, expected);
+            assert_eq!(diff["changes"][0]["size_before"], 6);
+            assert_eq!(diff["changes"][0]["size_after"], 7);
+            assert_eq!(diff["version"], 1);
+
+            // A rewrite within the same second, of the same length, is still
+            // a change without content info.
+            let cid_c = test_cfg(write_file_native(
+                get_block_store_interface(),
+                cid_b.into(),
+                RustString::from("root/diff/change.txt".to_string()),
+                b"after??".to_vec().into(),
+            ));
+            let diff = diff_native(
+                get_block_store_interface(),
+                ptr::null(),
+                cid_b.into(),
+                cid_c.into(),
+                RustString::from("root/diff".to_string()),
+                OutputFormat::Json,
+                false,
+            );
+            assert!(diff.ok);
+            let diff: Vec<u8> = diff.result.into();
+            let diff: serde_json::Value = serde_json::from_slice(&diff).unwrap();
+            let changes = diff["changes"].as_array().unwrap();
+            assert_eq!(changes.len(), 1);
+            assert_eq!(changes[0]["path"], "change.txt");
+            assert_eq!(changes[0]["change"], "modified");
+            assert!(changes[0]["size_after"].is_null());
+        }
+    }
+
+    #[test]
+    fn test_merge() {
+        unsafe {
+            let wnfs_key = &mut digest("test_merge").as_bytes().to_vec();
+            let wnfs_key_string = wnfs_key[..32].to_vec();
+            let write = |cid: Cid, path: &str, content: &[u8]| -> Cid {
+                test_cfg(write_file_native(
+                    get_block_store_interface(),
+                    cid.into(),
+                    RustString::from(path.to_string()),
+                    content.to_vec().into(),
+                ))
+            };
+            let mut base = test_cfg(init_native(
+                get_block_store_interface(),
+                wnfs_key_string.to_owned().into(),
+            ));
+            base = write(base, "root/shared.txt", b"base");
+            base = write(base, "root/obsolete.txt", b"base");
+
+            let ours = write(base, "root/shared.txt", b"ours");
+            let ours = write(ours, "root/from_ours.txt", b"ours");
+            let theirs = write(base, "root/shared.txt", b"theirs");
+            let theirs = write(theirs, "root/from_theirs.txt", b"theirs");
+            let theirs = test_cfg(rm_native(
+                get_block_store_interface(),
+                theirs.into(),
+                RustString::from("root/obsolete.txt".to_string()),
+            ));
+
+            let merged = merge_native(
+                get_block_store_interface(),
+                ptr::null(),
+                base.into(),
+                ours.into(),
+                theirs.into(),
+                RustString::from("phone".to_string()),
+                OutputFormat::Json,
+            );
+            assert!(merged.ok);
+            let merged: Vec<u8> = merged.result.into();
+            let merged: serde_json::Value = serde_json::from_slice(&merged).unwrap();
+            let merged_cid = Cid::try_from(merged["cid"].as_str().unwrap()).unwrap();
+
+            let read = |path: &str| -> Option<Vec<u8>> {
+                let result = read_file_native(
+                    get_block_store_interface(),
+                    merged_cid.into(),
+                    RustString::from(path.to_string()),
+                );
+                if result.ok {
+                    Some(result.result.into())
+                } else {
+                    None
+                }
+            };
+            assert_eq!(read("root/shared.txt"), Some(b"ours".to_vec()));
+            assert_eq!(read("root/shared (phone).txt"), Some(b"theirs".to_vec()));
+            assert_eq!(read("root/from_ours.txt"), Some(b"ours".to_vec()));
+            assert_eq!(read("root/from_theirs.txt"), Some(b"theirs".to_vec()));
+            assert_eq!(read("root/obsolete.txt"), None);
+
+            let actions = merged["actions"].as_array().unwrap();
+            let conflict = actions
+                .iter()
+                .find(|action| action["path"] == "root/shared.txt")
+                .unwrap();
+            assert_eq!(conflict["action"], "conflict");
+            assert_eq!(conflict["conflict_path"], "root/shared (phone).txt");
+        }
+    }
+
+    #[test]
+    fn test_merge_dir_replaced_by_file() {
+        unsafe {
+            let wnfs_key = &mut digest("test_merge_dir_replaced_by_file")
+                .as_bytes()
+                .to_vec();
+            let wnfs_key_string = wnfs_key[..32].to_vec();
+            let write = |cid: Cid, path: &str, content: &[u8]| -> Cid {
+                test_cfg(write_file_native(
+                    get_block_store_interface(),
+                    cid.into(),
+                    RustString::from(path.to_string()),
+                    content.to_vec().into(),
+                ))
+            };
+            let rm = |cid: Cid, path: &str| -> Cid {
+                test_cfg(rm_native(
+                    get_block_store_interface(),
+                    cid.into(),
+                    RustString::from(path.to_string()),
+                ))
+            };
+            let mut base = test_cfg(init_native(
+                get_block_store_interface(),
+                wnfs_key_string.to_owned().into(),
+            ));
+            base = write(base, "root/edited/notes.txt", b"base");
+            base = write(base, "root/untouched/notes.txt", b"base");
+
+            let ours = write(base, "root/edited/notes.txt", b"ours");
+            let theirs = rm(base, "root/edited");
+            let theirs = write(theirs, "root/edited", b"theirs");
+            let theirs = rm(theirs, "root/untouched");
+            let theirs = write(theirs, "root/untouched", b"theirs");
+
+            let merged = merge_native(
+                get_block_store_interface(),
+                ptr::null(),
+                base.into(),
+                ours.into(),
+                theirs.into(),
+                RustString::from("phone".to_string()),
+                OutputFormat::Json,
+            );
+            assert!(merged.ok);
+            let merged: Vec<u8> = merged.result.into();
+            let merged: serde_json::Value = serde_json::from_slice(&merged).unwrap();
+            let merged_cid = Cid::try_from(merged["cid"].as_str().unwrap()).unwrap();
+
+            let read = |path: &str| -> Option<Vec<u8>> {
+                let result = read_file_native(
+                    get_block_store_interface(),
+                    merged_cid.into(),
+                    RustString::from(path.to_string()),
+                );
+                if result.ok {
+                    Some(result.result.into())
+                } else {
+                    None
+                }
+            };
+            // Our edit below the replaced directory survives, theirs goes
+            // next to it.
+            assert_eq!(read("root/edited/notes.txt"), Some(b"ours".to_vec()));
+            assert_eq!(read("root/edited (phone)"), Some(b"theirs".to_vec()));
+            // An untouched directory is replaced outright.
+            assert_eq!(read("root/untouched"), Some(b"theirs".to_vec()));
+
+            let actions = merged["actions"].as_array().unwrap();
+            let action = |path: &str| {
+                actions
+                    .iter()
+                    .find(|action| action["path"] == path)
+                    .map(|action| action["action"].to_owned())
+            };
+            assert_eq!(action("root/edited"), Some("conflict".into()));
+            assert_eq!(action("root/edited/notes.txt"), Some("kept".into()));
+            assert_eq!(action("root/untouched"), Some("updated".into()));
+            assert_eq!(action("root/untouched/notes.txt"), None);
+        }
+    }
+
+    #[test]
+    fn test_symlinks() {
+        unsafe {
+            let wnfs_key = &mut digest("test_symlinks").as_bytes().to_vec();
+            let wnfs_key_string = wnfs_key[..32].to_vec();
+            let mut cid = test_cfg(init_native(
+                get_block_store_interface(),
+                wnfs_key_string.to_owned().into(),
+            ));
+            cid = test_cfg(write_file_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/photos/2023/beach.jpg".to_string()),
+                b"sand".to_vec().into(),
+            ));
+            cid = test_cfg(symlink_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/latest".to_string()),
+                RustString::from("root/photos/2023".to_string()),
+            ));
+
+            let target = readlink_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/latest".to_string()),
+                false,
+            );
+            assert!(target.ok);
+            let target: String = target.result.into();
+            assert_eq!(target, "root/photos/2023");
+
+            let content = read_file_follow_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/latest/beach.jpg".to_string()),
+            );
+            assert!(content.ok);
+            let content: Vec<u8> = content.result.into();
+            assert_eq!(content, b"sand".to_vec());
+
+            let listing = ls_with_format_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root".to_string()),
+                OutputFormat::Json,
+            );
+            let listing: Vec<u8> = listing.result.into();
+            let listing: serde_json::Value = serde_json::from_slice(&listing).unwrap();
+            let entries = listing["entries"].as_array().unwrap();
+            assert_eq!(entries.len(), 2);
+            let link = entries.iter().find(|e| e["name"] == "latest").unwrap();
+            assert_eq!(link["kind"], "symlink");
+            assert_eq!(link["target"], "root/photos/2023");
+
+            // a -> b -> a never resolves.
+            cid = test_cfg(symlink_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/a".to_string()),
+                RustString::from("root/b".to_string()),
+            ));
+            cid = test_cfg(symlink_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/b".to_string()),
+                RustString::from("root/a".to_string()),
+            ));
+            let looped = readlink_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/a".to_string()),
+                true,
+            );
+            assert!(!looped.ok);
+
+            cid = test_cfg(unlink_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/latest".to_string()),
+            ));
+            let gone = readlink_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/latest".to_string()),
+                false,
+            );
+            assert!(!gone.ok);
+            let kept = read_file_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/photos/2023/beach.jpg".to_string()),
+            );
+            assert!(kept.ok);
+        }
+    }
+
+    #[test]
+    fn test_metadata() {
+        unsafe {
+            let wnfs_key = &mut digest("test_metadata").as_bytes().to_vec();
+            let wnfs_key_string = wnfs_key[..32].to_vec();
+            let mut cid = test_cfg(init_native(
+                get_block_store_interface(),
+                wnfs_key_string.to_owned().into(),
+            ));
+            cid = test_cfg(write_file_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/beach.jpg".to_string()),
+                b"sand".to_vec().into(),
+            ));
+            cid = test_cfg(set_metadata_string_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/beach.jpg".to_string()),
+                RustString::from("caption".to_string()),
+                RustString::from("At the beach".to_string()),
+            ));
+            cid = test_cfg(set_metadata_number_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/beach.jpg".to_string()),
+                RustString::from("rating".to_string()),
+                4.0,
+            ));
+            cid = test_cfg(set_metadata_bytes_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/beach.jpg".to_string()),
+                RustString::from("thumbhash".to_string()),
+                vec![1u8, 2, 3].into(),
+            ));
+
+            let meta = get_metadata_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/beach.jpg".to_string()),
+                OutputFormat::Json,
+            );
+            assert!(meta.ok);
+            let meta: Vec<u8> = meta.result.into();
+            let meta: serde_json::Value = serde_json::from_slice(&meta).unwrap();
+            assert_eq!(meta["meta"]["caption"], "At the beach");
+            assert_eq!(meta["meta"]["rating"], 4.0);
+            assert_eq!(meta["meta"]["thumbhash"], serde_json::json!([1, 2, 3]));
+
+            let missing = set_metadata_string_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/nothing.jpg".to_string()),
+                RustString::from("caption".to_string()),
+                RustString::from("Nowhere".to_string()),
+            );
+            assert!(!missing.ok);
+            assert_eq!(missing.code, WnfsErrorCode::NotFound);
+
+            // Metadata follows the file when it moves.
+            cid = test_cfg(mv_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/beach.jpg".to_string()),
+                RustString::from("root/2023.jpg".to_string()),
+            ));
+            cid = test_cfg(delete_metadata_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/2023.jpg".to_string()),
+                RustString::from("thumbhash".to_string()),
+            ));
+            let listing = ls_with_meta_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root".to_string()),
+                OutputFormat::Json,
+            );
+            assert!(listing.ok);
+            let listing: Vec<u8> = listing.result.into();
+            let listing: serde_json::Value = serde_json::from_slice(&listing).unwrap();
+            let entries = listing["entries"].as_array().unwrap();
+            assert_eq!(entries.len(), 1);
+            assert_eq!(entries[0]["name"], "2023.jpg");
+            assert_eq!(entries[0]["meta"]["caption"], "At the beach");
+            assert!(entries[0]["meta"].get("thumbhash").is_none());
+
+            // A new file under a removed file's name starts without metadata.
+            cid = test_cfg(rm_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/2023.jpg".to_string()),
+            ));
+            cid = test_cfg(write_file_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/2023.jpg".to_string()),
+                b"snow".to_vec().into(),
+            ));
+            let meta = get_metadata_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/2023.jpg".to_string()),
+                OutputFormat::Json,
+            );
+            let meta: Vec<u8> = meta.result.into();
+            let meta: serde_json::Value = serde_json::from_slice(&meta).unwrap();
+            assert_eq!(meta["meta"], serde_json::json!({}));
+        }
+    }
+
+    #[test]
+    fn test_find() {
+        unsafe {
+            let wnfs_key = &mut digest("test_find").as_bytes().to_vec();
+            let wnfs_key_string = wnfs_key[..32].to_vec();
+            let mut cid = test_cfg(init_native(
+                get_block_store_interface(),
+                wnfs_key_string.to_owned().into(),
+            ));
+            for path in [
+                "root/photos/2022/IMG_001.JPG",
+                "root/photos/2023/img_002.jpg",
+                "root/photos/2023/notes.txt",
+                "root/docs/img.pdf",
+            ] {
+                cid = test_cfg(write_file_native(
+                    get_block_store_interface(),
+                    cid.into(),
+                    RustString::from(path.to_string()),
+                    path.as_bytes().to_vec().into(),
+                ));
+            }
+            let options = FindOptions {
+                syntax: PatternSyntax::Glob,
+                kind: KindFilter::FilesOnly,
+                case_insensitive: true,
+                max_results: 0,
+                with_content: true,
+            };
+            let find = |pattern: &str, options: FindOptions| {
+                let found = find_native(
+                    get_block_store_interface(),
+                    ptr::null(),
+                    cid.into(),
+                    RustString::from("root/photos".to_string()),
+                    RustString::from(pattern.to_string()),
+                    options,
+                    OutputFormat::Json,
+                );
+                assert!(found.ok);
+                let found: Vec<u8> = found.result.into();
+                serde_json::from_slice::<serde_json::Value>(&found).unwrap()
+            };
+
+            let found = find("img_*.jpg", options);
+            assert_eq!(found["count"], 2);
+            assert_eq!(found["truncated"], false);
+            let paths: Vec<&str> = found["entries"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|e| e["path"].as_str().unwrap())
+                .collect();
+            assert_eq!(paths, vec!["2022/IMG_001.JPG", "2023/img_002.jpg"]);
+            assert_eq!(found["entries"][0]["size"], 28);
+
+            let found = find(r"^\d{4}$", FindOptions {
+                syntax: PatternSyntax::Regex,
+                kind: KindFilter::DirsOnly,
+                ..options
+            });
+            assert_eq!(found["count"], 2);
+
+            let found = find("*", FindOptions {
+                max_results: 1,
+                ..options
+            });
+            assert_eq!(found["count"], 1);
+            assert_eq!(found["truncated"], true);
+
+            let invalid = find_native(
+                get_block_store_interface(),
+                ptr::null(),
+                cid.into(),
+                RustString::from("root".to_string()),
+                RustString::from("(".to_string()),
+                FindOptions {
+                    syntax: PatternSyntax::Regex,
+                    ..options
+                },
+                OutputFormat::Json,
+            );
+            assert!(!invalid.ok);
+            assert_eq!(invalid.code, WnfsErrorCode::InvalidArgument);
+        }
+    }
+
+    #[test]
+    fn test_export_dir() {
+        unsafe {
+            let wnfs_key = &mut digest("test_export_dir").as_bytes().to_vec();
+            let wnfs_key_string = wnfs_key[..32].to_vec();
+            let mut cid = test_cfg(init_native(
+                get_block_store_interface(),
+                wnfs_key_string.to_owned().into(),
+            ));
+            cid = test_cfg(write_file_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/album/a.txt".to_string()),
+                b"first".to_vec().into(),
+            ));
+            cid = test_cfg(write_file_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/album/trip/b.txt".to_string()),
+                b"second".to_vec().into(),
+            ));
+            cid = test_cfg(mkdir_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/album/empty".to_string()),
+            ));
+
+            let local_dir = "./tmp/test_export_dir";
+            let _ = fs::remove_dir_all(local_dir);
+            let export = |existing: ExistingFiles| {
+                let exported = export_dir_to_path_native(
+                    get_block_store_interface(),
+                    ptr::null(),
+                    cid.into(),
+                    RustString::from("root/album".to_string()),
+                    RustString::from(local_dir.to_string()),
+                    existing,
+                    OutputFormat::Json,
+                );
+                assert!(exported.ok);
+                let exported: Vec<u8> = exported.result.into();
+                serde_json::from_slice::<serde_json::Value>(&exported).unwrap()
+            };
+
+            let exported = export(ExistingFiles::Skip);
+            assert_eq!(exported["exported"], 2);
+            assert_eq!(exported["failed"], serde_json::json!([]));
+            assert_eq!(
+                fs::read_to_string(format!("{}/trip/b.txt", local_dir)).unwrap(),
+                "second"
+            );
+            assert!(fs::metadata(format!("{}/empty", local_dir)).unwrap().is_dir());
+
+            let stat = stat_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/album/a.txt".to_string()),
+                OutputFormat::Json,
+                false,
+            );
+            let stat: Vec<u8> = stat.result.into();
+            let stat: serde_json::Value = serde_json::from_slice(&stat).unwrap();
+            let local_modified = fs::metadata(format!("{}/a.txt", local_dir))
+                .unwrap()
+                .modified()
+                .unwrap()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs();
+            assert_eq!(stat["modified"], local_modified);
+
+            fs::write(format!("{}/a.txt", local_dir), "changed locally").unwrap();
+            let exported = export(ExistingFiles::Skip);
+            assert_eq!(exported["exported"], 0);
+            assert_eq!(exported["skipped"], 2);
+            assert_eq!(
+                fs::read_to_string(format!("{}/a.txt", local_dir)).unwrap(),
+                "changed locally"
+            );
+            let exported = export(ExistingFiles::Overwrite);
+            assert_eq!(exported["exported"], 2);
+            assert_eq!(
+                fs::read_to_string(format!("{}/a.txt", local_dir)).unwrap(),
+                "first"
+            );
+
+            // Symlinks in the target are never followed.
+            let outside = "./tmp/test_export_dir_outside";
+            let _ = fs::remove_dir_all(outside);
+            fs::create_dir_all(outside).unwrap();
+            fs::write(format!("{}/a.txt", outside), "outside").unwrap();
+            fs::remove_file(format!("{}/a.txt", local_dir)).unwrap();
+            fs::remove_dir_all(format!("{}/trip", local_dir)).unwrap();
+            std::os::unix::fs::symlink(
+                fs::canonicalize(format!("{}/a.txt", outside)).unwrap(),
+                format!("{}/a.txt", local_dir),
+            )
+            .unwrap();
+            std::os::unix::fs::symlink(
+                fs::canonicalize(outside).unwrap(),
+                format!("{}/trip", local_dir),
+            )
+            .unwrap();
+            let exported = export(ExistingFiles::Overwrite);
+            assert_eq!(exported["exported"], 0);
+            assert_eq!(exported["failed"].as_array().unwrap().len(), 3);
+            assert_eq!(
+                fs::read_to_string(format!("{}/a.txt", outside)).unwrap(),
+                "outside"
+            );
+            assert!(fs::symlink_metadata(format!("{}/b.txt", outside)).is_err());
+        }
+    }
+
+    #[test]
+    fn test_import_dir() {
+        unsafe {
+            let wnfs_key = &mut digest("test_import_dir").as_bytes().to_vec();
+            let wnfs_key_string = wnfs_key[..32].to_vec();
+            let cid = test_cfg(init_native(
+                get_block_store_interface(),
+                wnfs_key_string.to_owned().into(),
+            ));
+            let local_dir = "./tmp/test_import_dir";
+            let _ = fs::remove_dir_all(local_dir);
+            fs::create_dir_all(format!("{}/trip", local_dir)).unwrap();
+            fs::write(format!("{}/a.txt", local_dir), "first").unwrap();
+            fs::write(format!("{}/trip/b.txt", local_dir), "second").unwrap();
+            fs::write(format!("{}/.DS_Store", local_dir), "finder").unwrap();
+            fs::write(format!("{}/trip/.DS_Store", local_dir), "finder").unwrap();
+
+            let exclude = [RustString::from(".DS_Store".to_string())];
+            let import = |cid: Cid| {
+                let imported = import_dir_from_path_native(
+                    get_block_store_interface(),
+                    ptr::null(),
+                    cid.into(),
+                    RustString::from(local_dir.to_string()),
+                    RustString::from("root/upload".to_string()),
+                    ptr::null(),
+                    0,
+                    exclude.as_ptr(),
+                    exclude.len(),
+                    ExistingFiles::Skip,
+                    OutputFormat::Json,
+                );
+                assert!(imported.ok);
+                let imported: Vec<u8> = imported.result.into();
+                serde_json::from_slice::<serde_json::Value>(&imported).unwrap()
+            };
+
+            let imported = import(cid);
+            let entries = imported["entries"].as_array().unwrap();
+            let paths: Vec<&str> = entries
+                .iter()
+                .map(|e| e["path"].as_str().unwrap())
+                .collect();
+            assert_eq!(paths, vec!["a.txt", "trip/b.txt"]);
+            assert!(entries.iter().all(|e| e["status"] == "imported"));
+            let cid: Cid = imported["cid"].as_str().unwrap().parse().unwrap();
+
+            let content = read_file_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/upload/trip/b.txt".to_string()),
+            );
+            let content: Vec<u8> = content.result.into();
+            assert_eq!(content, b"second".to_vec());
+            let stored = read_file_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/upload/.DS_Store".to_string()),
+            );
+            assert!(!stored.ok);
+
+            let stat = stat_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/upload/a.txt".to_string()),
+                OutputFormat::Json,
+                false,
+            );
+            let stat: Vec<u8> = stat.result.into();
+            let stat: serde_json::Value = serde_json::from_slice(&stat).unwrap();
+            let local_modified = fs::metadata(format!("{}/a.txt", local_dir))
+                .unwrap()
+                .modified()
+                .unwrap()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs();
+            assert_eq!(stat["modified"], local_modified);
+
+            let stat = stat_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/upload/trip".to_string()),
+                OutputFormat::Json,
+                false,
+            );
+            let stat: Vec<u8> = stat.result.into();
+            let stat: serde_json::Value = serde_json::from_slice(&stat).unwrap();
+            let local_modified = fs::metadata(format!("{}/trip", local_dir))
+                .unwrap()
+                .modified()
+                .unwrap()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs();
+            assert_eq!(stat["modified"], local_modified);
+
+            let imported = import(cid);
+            let entries = imported["entries"].as_array().unwrap();
+            assert!(entries.iter().all(|e| e["status"] == "skipped"));
+        }
+    }
+
+    #[test]
+    fn test_export_car() {
+        unsafe {
+            let wnfs_key = &mut digest("test_export_car").as_bytes().to_vec();
+            let wnfs_key_string = wnfs_key[..32].to_vec();
+            let mut cid = test_cfg(init_native(
+                get_block_store_interface(),
+                wnfs_key_string.to_owned().into(),
+            ));
+            cid = test_cfg(write_file_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/backup.txt".to_string()),
+                b"keep me".to_vec().into(),
+            ));
+
+            let export = |path: &str, car_version: CarVersion| {
+                let exported = export_car_native(
+                    get_block_store_interface(),
+                    ptr::null(),
+                    cid.into(),
+                    RustString::from(path.to_string()),
+                    car_version,
+                    OutputFormat::Json,
+                );
+                assert!(exported.ok);
+                let exported: Vec<u8> = exported.result.into();
+                serde_json::from_slice::<serde_json::Value>(&exported).unwrap()
+            };
+
+            let v1_path = "./tmp/test_export_car.car";
+            let exported = export(v1_path, CarVersion::V1);
+            assert!(exported["blocks"].as_u64().unwrap() > 1);
+            let car = fs::read(v1_path).unwrap();
+            assert_eq!(exported["bytes"], car.len());
+            // The header is short enough for a single byte varint.
+            let header_len = car[0] as usize;
+            let header: libipld::Ipld =
+                libipld::codec::Codec::decode(&libipld::cbor::DagCborCodec, &car[1..1 + header_len])
+                    .unwrap();
+            assert_eq!(
+                header,
+                libipld::ipld!({ "roots": [cid], "version": 1 })
+            );
+
+            let v2_path = "./tmp/test_export_car_v2.car";
+            let exported = export(v2_path, CarVersion::V2);
+            let car_v2 = fs::read(v2_path).unwrap();
+            assert_eq!(
+                car_v2[..11],
+                [0x0a, 0xa1, 0x67, 0x76, 0x65, 0x72, 0x73, 0x69, 0x6f, 0x6e, 0x02]
+            );
+            assert_eq!(&car_v2[51..], &car[..]);
+            assert_eq!(exported["bytes"], car_v2.len());
+        }
+    }
+
+    #[test]
+    fn test_import_car() {
+        unsafe {
+            let wnfs_key = &mut digest("test_import_car").as_bytes().to_vec();
+            let wnfs_key_string = wnfs_key[..32].to_vec();
+            let mut cid = test_cfg(init_native(
+                get_block_store_interface(),
+                wnfs_key_string.to_owned().into(),
+            ));
+            cid = test_cfg(write_file_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/restore.txt".to_string()),
+                b"restore me".to_vec().into(),
+            ));
+
+            for (path, car_version) in [
+                ("./tmp/test_import_car.car", CarVersion::V1),
+                ("./tmp/test_import_car_v2.car", CarVersion::V2),
+            ] {
+                let exported = export_car_native(
+                    get_block_store_interface(),
+                    ptr::null(),
+                    cid.into(),
+                    RustString::from(path.to_string()),
+                    car_version,
+                    OutputFormat::Json,
+                );
+                let exported: Vec<u8> = exported.result.into();
+                let exported: serde_json::Value = serde_json::from_slice(&exported).unwrap();
+
+                let imported = import_car_native(
+                    get_block_store_interface(),
+                    ptr::null(),
+                    RustString::from(path.to_string()),
+                    OutputFormat::Json,
+                );
+                assert!(imported.ok);
+                let imported: Vec<u8> = imported.result.into();
+                let imported: serde_json::Value = serde_json::from_slice(&imported).unwrap();
+                assert_eq!(imported["roots"], serde_json::json!([cid.to_string()]));
+                assert_eq!(imported["blocks"], exported["blocks"]);
+            }
+
+            // A flipped byte in the last block must be caught.
+            let corrupt_path = "./tmp/test_import_car_corrupt.car";
+            let mut car = fs::read("./tmp/test_import_car.car").unwrap();
+            *car.last_mut().unwrap() ^= 0xff;
+            fs::write(corrupt_path, car).unwrap();
+            let imported = import_car_native(
+                get_block_store_interface(),
+                ptr::null(),
+                RustString::from(corrupt_path.to_string()),
+                OutputFormat::Json,
+            );
+            assert!(!imported.ok);
+        }
+    }
+
+    #[test]
+    fn test_symlink_mutations() {
+        unsafe {
+            let wnfs_key = &mut digest("test_symlink_mutations").as_bytes().to_vec();
+            let wnfs_key_string = wnfs_key[..32].to_vec();
+            let mut cid = test_cfg(init_native(
+                get_block_store_interface(),
+                wnfs_key_string.to_owned().into(),
+            ));
+            cid = test_cfg(mkdir_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/docs".to_string()),
+            ));
+            cid = test_cfg(symlink_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/link".to_string()),
+                RustString::from("root/docs".to_string()),
+            ));
+
+            // Nothing can be written over a link or below it.
+            for path in ["root/link", "root/link/file.txt"] {
+                let result = write_file_native(
+                    get_block_store_interface(),
+                    cid.into(),
+                    RustString::from(path.to_string()),
+                    b"data".to_vec().into(),
+                );
+                assert!(!result.ok, "writing {} should fail", path);
+            }
+            let result = mkdir_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/link".to_string()),
+            );
+            assert!(!result.ok);
+
+            cid = test_cfg(mv_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/link".to_string()),
+                RustString::from("root/moved".to_string()),
+            ));
+            cid = test_cfg(cp_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/moved".to_string()),
+                RustString::from("root/copied".to_string()),
+            ));
+            let readlink = |cid: Cid, path: &str| -> Option<String> {
+                let result = readlink_native(
+                    get_block_store_interface(),
+                    cid.into(),
+                    RustString::from(path.to_string()),
+                    false,
+                );
+                if result.ok {
+                    Some(result.result.into())
+                } else {
+                    None
+                }
+            };
+            assert_eq!(readlink(cid, "root/link"), None);
+            assert_eq!(readlink(cid, "root/moved"), Some("root/docs".to_string()));
+            assert_eq!(readlink(cid, "root/copied"), Some("root/docs".to_string()));
+
+            cid = test_cfg(rm_native(
+                get_block_store_interface(),
+                cid.into(),
+                RustString::from("root/moved".to_string()),
+            ));
+            assert_eq!(readlink(cid, "root/moved"), None);
+            assert_eq!(readlink(cid, "root/copied"), Some("root/docs".to_string()));
+        }
+    }
 }
@@ -0,0 +1,91 @@
+use libipld::Cid;
+use log::trace;
+use wnfsutils::private_forest::PrivateDirectoryHelper;
+
+use crate::attrs::{write_file, write_file_from_path};
+use crate::c_types::{prepare_path_segments, RustBytes, RustString};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(C)]
+pub enum TransactionOpKind {
+    Mkdir = 0,
+    Write = 1,
+    WriteFromPath = 2,
+    Cp = 3,
+    Mv = 4,
+    Rm = 5,
+}
+
+/// One step of a batched transaction.
+#[derive(Clone)]
+#[repr(C)]
+pub struct TransactionOp {
+    pub kind: TransactionOpKind,
+    /// Path created, written or removed; the source path for cp and mv.
+    pub path_segments: RustString,
+    /// Destination path for cp and mv, local filename for `WriteFromPath`.
+    /// Ignored by the other kinds.
+    pub target: RustString,
+    /// File content for `Write`. Ignored by the other kinds.
+    pub content: RustBytes,
+}
+
+#[derive(Debug)]
+pub struct TransactionError {
+    pub index: usize,
+    pub message: String,
+}
+
+pub fn apply_operation(
+    helper: &mut PrivateDirectoryHelper,
+    op: &TransactionOp,
+) -> Result<Cid, String> {
+    let path_segments = unsafe { prepare_path_segments(op.path_segments.clone()) };
+    match op.kind {
+        TransactionOpKind::Mkdir => helper.synced_mkdir(&path_segments),
+        TransactionOpKind::Write => {
+            let content: Vec<u8> = op.content.clone().into();
+            write_file(helper, &path_segments, content, 0)
+        }
+        TransactionOpKind::WriteFromPath => {
+            let filename: String = op.target.clone().into();
+            write_file_from_path(helper, &path_segments, &filename)
+        }
+        TransactionOpKind::Cp => {
+            let target_path_segments = unsafe { prepare_path_segments(op.target.clone()) };
+            helper.synced_cp(&path_segments, &target_path_segments)
+        }
+        TransactionOpKind::Mv => {
+            let target_path_segments = unsafe { prepare_path_segments(op.target.clone()) };
+            helper.synced_mv(&path_segments, &target_path_segments)
+        }
+        TransactionOpKind::Rm => helper.synced_rm(&path_segments),
+    }
+}
+
+/// Applies `ops` in order against a single loaded forest and returns the
+/// root CID after the last one, or `None` if `ops` is empty.
+///
+/// Intermediate roots are never handed out, so on failure the caller simply
+/// keeps using the root it started from.
+pub fn apply_operations(
+    helper: &mut PrivateDirectoryHelper,
+    ops: &[TransactionOp],
+) -> Result<Option<Cid>, TransactionError> {
+    let mut cid = None;
+    for (index, op) in ops.iter().enumerate() {
+        trace!("transaction op #{}: {:?}", index, op.kind);
+        cid = Some(
+            apply_operation(helper, op).map_err(|message| TransactionError { index, message })?,
+        );
+    }
+    Ok(cid)
+}
+
+pub unsafe fn ops_from_raw<'o>(ops: *const TransactionOp, ops_len: usize) -> &'o [TransactionOp] {
+    if ops.is_null() || ops_len == 0 {
+        &[]
+    } else {
+        std::slice::from_raw_parts(ops, ops_len)
+    }
+}
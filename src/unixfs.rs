@@ -0,0 +1,220 @@
+use std::collections::BTreeMap;
+use std::io::Cursor;
+
+use libipld::codec::Codec;
+use libipld::pb::DagPbCodec;
+use libipld::{Cid, Ipld, IpldCodec};
+use wnfs::common::{BlockStore, CODEC_RAW};
+
+use crate::car::{read_varint, write_varint};
+
+/// Size of the raw leaves file content is split into, the default chunk
+/// size of other UnixFS writers.
+pub const CHUNK_SIZE: usize = 256 * 1024;
+
+/// Most links in one DAG-PB node, as in the balanced layout of other
+/// UnixFS writers.
+const MAX_LINKS: usize = 174;
+
+/// `Type` of a UnixFS `Data` message.
+const UNIXFS_RAW: u64 = 0;
+const UNIXFS_FILE: u64 = 2;
+
+/// The fields of a UnixFS `Data` message a file reader needs.
+#[derive(Default)]
+struct UnixFsData {
+    kind: u64,
+    data: Option<Vec<u8>>,
+    filesize: Option<u64>,
+}
+
+fn encode_file_data(filesize: u64, blocksizes: &[u64]) -> Vec<u8> {
+    // Writing to a Vec cannot fail.
+    let mut buf = Vec::new();
+    write_varint(&mut buf, 1 << 3).unwrap();
+    write_varint(&mut buf, UNIXFS_FILE).unwrap();
+    write_varint(&mut buf, 3 << 3).unwrap();
+    write_varint(&mut buf, filesize).unwrap();
+    for size in blocksizes {
+        write_varint(&mut buf, 4 << 3).unwrap();
+        write_varint(&mut buf, *size).unwrap();
+    }
+    buf
+}
+
+fn decode_data(bytes: &[u8]) -> Result<UnixFsData, String> {
+    let invalid = |what: &str| format!("invalid unixfs data: {}", what);
+    let mut reader = Cursor::new(bytes);
+    let mut data = UnixFsData::default();
+    while let Some(key) = read_varint(&mut reader).map_err(|e| invalid(&e.to_string()))? {
+        let (field, wire_type) = (key >> 3, key & 7);
+        match wire_type {
+            0 => {
+                let value = read_varint(&mut reader)
+                    .map_err(|e| invalid(&e.to_string()))?
+                    .ok_or_else(|| invalid("truncated varint"))?;
+                match field {
+                    1 => data.kind = value,
+                    3 => data.filesize = Some(value),
+                    _ => {}
+                }
+            }
+            2 => {
+                let len = read_varint(&mut reader)
+                    .map_err(|e| invalid(&e.to_string()))?
+                    .ok_or_else(|| invalid("truncated length"))?;
+                let start = reader.position() as usize;
+                let end = start
+                    .checked_add(len as usize)
+                    .filter(|end| *end <= bytes.len())
+                    .ok_or_else(|| invalid("truncated field"))?;
+                if field == 2 {
+                    data.data = Some(bytes[start..end].to_vec());
+                }
+                reader.set_position(end as u64);
+            }
+            _ => return Err(invalid("unsupported wire type")),
+        }
+    }
+    Ok(data)
+}
+
+/// Links and UnixFS data of a DAG-PB block.
+fn decode_node(block: &[u8]) -> Result<(Vec<Cid>, UnixFsData), String> {
+    let ipld: Ipld = DagPbCodec.decode(block).map_err(|e| e.to_string())?;
+    let mut links = Vec::new();
+    if let Ok(Ipld::List(list)) = ipld.get("Links") {
+        for link in list {
+            match link.get("Hash") {
+                Ok(Ipld::Link(cid)) => links.push(*cid),
+                _ => return Err("invalid dag-pb link".to_string()),
+            }
+        }
+    }
+    let data = match ipld.get("Data") {
+        Ok(Ipld::Bytes(bytes)) => decode_data(bytes)?,
+        _ => UnixFsData::default(),
+    };
+    if data.kind != UNIXFS_FILE && data.kind != UNIXFS_RAW {
+        return Err("unixfs node is not a file".to_string());
+    }
+    Ok((links, data))
+}
+
+/// A stored node of the file DAG: its CID, the content length below it and
+/// the size of every block below it, as DAG-PB links record them.
+struct Stored {
+    cid: Cid,
+    filesize: u64,
+    tsize: u64,
+}
+
+/// Stores `content` as a UnixFS file: raw leaves of at most `CHUNK_SIZE`
+/// under a balanced tree of DAG-PB nodes. Content that fits in one chunk is
+/// that raw leaf alone. Returns the CID of the root.
+pub async fn put_file(store: &mut impl BlockStore, content: &[u8]) -> Result<Cid, String> {
+    let mut layer = Vec::with_capacity(content.len() / CHUNK_SIZE + 1);
+    for chunk in content.chunks(CHUNK_SIZE) {
+        let cid = store
+            .put_block(chunk.to_vec(), CODEC_RAW)
+            .await
+            .map_err(|e| e.to_string())?;
+        layer.push(Stored {
+            cid,
+            filesize: chunk.len() as u64,
+            tsize: chunk.len() as u64,
+        });
+    }
+    if layer.is_empty() {
+        return store
+            .put_block(Vec::new(), CODEC_RAW)
+            .await
+            .map_err(|e| e.to_string());
+    }
+    while layer.len() > 1 {
+        let mut parents = Vec::with_capacity(layer.len() / MAX_LINKS + 1);
+        for children in layer.chunks(MAX_LINKS) {
+            let filesize = children.iter().map(|child| child.filesize).sum();
+            let blocksizes: Vec<u64> = children.iter().map(|child| child.filesize).collect();
+            let links = children
+                .iter()
+                .map(|child| {
+                    let mut link = BTreeMap::new();
+                    link.insert("Hash".to_string(), Ipld::Link(child.cid));
+                    link.insert("Name".to_string(), Ipld::String(String::new()));
+                    link.insert("Tsize".to_string(), Ipld::Integer(child.tsize as i128));
+                    Ipld::Map(link)
+                })
+                .collect();
+            let mut node = BTreeMap::new();
+            node.insert("Links".to_string(), Ipld::List(links));
+            node.insert(
+                "Data".to_string(),
+                Ipld::Bytes(encode_file_data(filesize, &blocksizes)),
+            );
+            let bytes = DagPbCodec
+                .encode(&Ipld::Map(node))
+                .map_err(|e| e.to_string())?;
+            let tsize = bytes.len() as u64 + children.iter().map(|child| child.tsize).sum::<u64>();
+            let cid = store
+                .put_block(bytes, u64::from(IpldCodec::DagPb))
+                .await
+                .map_err(|e| e.to_string())?;
+            parents.push(Stored {
+                cid,
+                filesize,
+                tsize,
+            });
+        }
+        layer = parents;
+    }
+    Ok(layer.remove(0).cid)
+}
+
+/// Content of the UnixFS file at `cid`, read leaf by leaf in order. Raw
+/// leaves and DAG-PB leaves with inline data are both accepted.
+pub async fn read_file(store: &impl BlockStore, cid: &Cid) -> Result<Vec<u8>, String> {
+    let mut content = Vec::new();
+    let mut pending = vec![*cid];
+    while let Some(cid) = pending.pop() {
+        let block = store.get_block(&cid).await.map_err(|e| e.to_string())?;
+        if cid.codec() == CODEC_RAW {
+            content.extend_from_slice(&block);
+            continue;
+        }
+        let (links, data) = decode_node(&block)?;
+        if let Some(data) = data.data {
+            content.extend_from_slice(&data);
+        }
+        pending.extend(links.into_iter().rev());
+    }
+    Ok(content)
+}
+
+/// Content length of the UnixFS file at `cid`, read from its root block.
+pub async fn file_size(store: &impl BlockStore, cid: &Cid) -> Result<u64, String> {
+    let block = store.get_block(cid).await.map_err(|e| e.to_string())?;
+    if cid.codec() == CODEC_RAW {
+        return Ok(block.len() as u64);
+    }
+    let (_, data) = decode_node(&block)?;
+    match (data.filesize, data.data) {
+        (Some(filesize), _) => Ok(filesize),
+        (None, Some(data)) => Ok(data.len() as u64),
+        (None, None) => Ok(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_data, encode_file_data, UNIXFS_FILE};
+
+    #[test]
+    fn test_unixfs_data_round_trip() {
+        let data = decode_data(&encode_file_data(300_000, &[262_144, 37_856])).unwrap();
+        assert_eq!(data.kind, UNIXFS_FILE);
+        assert_eq!(data.filesize, Some(300_000));
+        assert!(data.data.is_none());
+        assert!(decode_data(&[0x12, 0x05, 0x01]).is_err());
+    }
+}
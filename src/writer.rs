@@ -0,0 +1,90 @@
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::PathBuf;
+
+use libipld::Cid;
+use log::trace;
+
+/// Unique path in the system temp dir for spooling content to disk.
+pub fn temp_spool_path(tag: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("wnfs-{}-{:016x}.part", tag, rand::random::<u64>()))
+}
+
+/// Incremental writer for a single private file.
+///
+/// Chunks are spooled to a temporary local file so that neither side has to
+/// hold the whole content in memory while it is being produced. The forest is
+/// only touched in `finalize`; dropping the writer without finalizing removes
+/// the spool file and leaves the forest unchanged.
+pub struct WnfsFileWriter {
+    path_segments: Vec<String>,
+    spool_path: PathBuf,
+    spool: Option<BufWriter<File>>,
+    len: u64,
+}
+
+impl WnfsFileWriter {
+    pub fn open(path_segments: Vec<String>) -> Result<WnfsFileWriter, String> {
+        let spool_path = temp_spool_path("writer");
+        let file = File::create(&spool_path).map_err(|e| e.to_string())?;
+        trace!(
+            "file writer opened for {:?} spooling to {:?}",
+            path_segments,
+            spool_path
+        );
+        Ok(WnfsFileWriter {
+            path_segments,
+            spool_path,
+            spool: Some(BufWriter::new(file)),
+            len: 0,
+        })
+    }
+
+    pub fn push(&mut self, chunk: &[u8]) -> Result<(), String> {
+        let spool = self
+            .spool
+            .as_mut()
+            .ok_or_else(|| "file writer already finalized".to_string())?;
+        spool.write_all(chunk).map_err(|e| e.to_string())?;
+        self.len += chunk.len() as u64;
+        Ok(())
+    }
+
+    /// Number of bytes pushed so far.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Flushes the spool and hands a reader over it to `write` together with
+    /// the target path, so the content is streamed into the forest rather
+    /// than read back whole. The spool file is removed whatever the outcome.
+    pub fn finalize<F>(mut self, write: F) -> Result<Cid, String>
+    where
+        F: FnOnce(&[String], BufReader<File>) -> Result<Cid, String>,
+    {
+        let mut spool = self
+            .spool
+            .take()
+            .ok_or_else(|| "file writer already finalized".to_string())?;
+        spool.flush().map_err(|e| e.to_string())?;
+        drop(spool);
+        let reader = File::open(&self.spool_path).map_err(|e| e.to_string())?;
+        trace!(
+            "file writer finalizing {} bytes into {:?}",
+            self.len,
+            self.path_segments
+        );
+        write(&self.path_segments, BufReader::new(reader))
+    }
+}
+
+impl Drop for WnfsFileWriter {
+    fn drop(&mut self) {
+        self.spool.take();
+        let _ = fs::remove_file(&self.spool_path);
+    }
+}